@@ -1,84 +1,67 @@
 extern crate wlc;
 
-use std::cmp;
 use std::env;
 use std::process;
 use wlc::*;
+use wlc::focus::{FocusManager, FocusPolicy};
+use wlc::interactive::{Binding, Interactive};
+use wlc::layout::{LayoutEngine, MasterStack};
+use wlc::placement::{self, Strategy};
+use wlc::popups::PopupManager;
+use wlc::requests::{Decision, RequestPolicy};
+use wlc::rules::{Outcome, Rules};
+use wlc::scratchpad::Scratchpad;
+use wlc::snapping::Snapping;
+use wlc::stacking::Stacking;
+use wlc::states::{PanelPolicy, States};
+use wlc::switcher::Switcher;
+use wlc::transients::Transients;
+use wlc::workspaces::{Scope, Workspaces};
 
 struct Compositor {
-    action: Action,
-}
-
-enum Action {
-    None,
-    Move(WeakView, Point),
-    Resize(WeakView, Point, ResizeEdge::Flags),
-}
-
-impl Action {
-    fn is_none(&self) -> bool {
-        match *self {
-            Action::None => true,
-            _ => false,
-        }
-    }
+    focus: FocusManager,
+    interactive: Interactive,
+    layout: LayoutEngine,
+    popups: PopupManager,
+    requests: RequestPolicy,
+    rules: Rules,
+    scratchpad: Scratchpad,
+    stacking: Stacking,
+    states: States,
+    switcher: Switcher,
+    transients: Transients,
+    workspaces: Workspaces,
 }
 
 impl Compositor {
     fn new() -> Compositor {
-        Compositor { action: Action::None }
-    }
-
-    fn start_interactive_move(&mut self, view: &View, origin: Point) {
-        let in_progess = !self.action.is_none();
-        if !in_progess {
-            view.bring_to_front();
-            self.action = Action::Move(view.weak_reference(), origin);
+        let mut interactive = Interactive::new();
+        interactive.set_binding(Some(Binding {
+                                         modifiers: Modifier::Ctrl,
+                                         move_button: Button::Left,
+                                         resize_button: Button::Right,
+                                     }));
+        interactive.set_snapping(Some(Snapping::default()));
+
+        let mut scratchpad = Scratchpad::new();
+        scratchpad.set_command("weston-terminal", "weston-terminal");
+
+        Compositor {
+            focus: FocusManager::new(FocusPolicy::ClickToFocus),
+            interactive: interactive,
+            layout: LayoutEngine::new(MasterStack::default()),
+            popups: PopupManager::new(),
+            requests: RequestPolicy::default(),
+            rules: Rules::new(Vec::new()).unwrap(),
+            scratchpad: scratchpad,
+            stacking: Stacking::new(),
+            states: States::new(PanelPolicy::Hide),
+            switcher: Switcher::new(Modifier::Alt, input::keyboard::Keysyms::KEY_Tab),
+            transients: Transients::new(),
+            workspaces: Workspaces::new(Scope::PerOutput, "1"),
         }
     }
 
-    fn start_interactive_resize(&mut self, view: &View, edges: ResizeEdge::Flags, origin: Point) {
-        let in_progess = !self.action.is_none();
-        if !in_progess {
-            let action_edges = if edges.is_empty() {
-                let geo = view.geometry();
-                let halfw = geo.origin.x + geo.size.w as i32 / 2;
-                let halfh = geo.origin.y + geo.size.h as i32 / 2;
-
-                let vertical = if origin.x < halfw {
-                    ResizeEdge::Left
-                } else if origin.x > halfw {
-                    ResizeEdge::Right
-                } else {
-                    ResizeEdge::Null
-                };
-
-                let horizontal = if origin.y < halfh {
-                    ResizeEdge::Left
-                } else if origin.y > halfh {
-                    ResizeEdge::Right
-                } else {
-                    ResizeEdge::Null
-                };
-
-                vertical | horizontal
-            } else {
-                edges
-            };
-
-            view.bring_to_front();
-            view.set_state(ViewState::Resizing, true);
-            self.action = Action::Resize(view.weak_reference(), origin, action_edges);
-        }
-    }
-
-    fn stop_interactive_action(&mut self) {
-        if let Action::Resize(ref weakview, _, _) = self.action {
-            weakview.run(|view| { view.set_state(ViewState::Resizing, false); });
-        };
-        self.action = Action::None;
-    }
-
     fn top_most<'a>(&mut self, output: &'a Output, offset: usize) -> Option<&'a View> {
         let views = output.views();
         match views.len() {
@@ -87,64 +70,52 @@ impl Compositor {
         }
     }
 
-    fn relayout(&mut self, output: &Output) {
-        // very simple layout function
-        // you probably dont want to layout certain types of windows in a wm
-
-        let size = output.virtual_resolution();
-        let len = output.views().len();
-
-        let mut toggle = false;
-        let mut y = 0;
-
-        let n = cmp::max((1 + len) / 2, 1) as u32;
-        let (width, height) = (size.w / 2, size.h / n);
-        let (ewidth, eheight) = (size.w - width * 2, size.h - height * n);
-
-        for (i, view) in output.views().into_iter().enumerate() {
-            match view.positioner() {
-                Some(pos) => {
-                    let mut size_req = pos.size();
-                    if size_req.w == 0 || size_req.h == 0 {
-                        size_req = view.geometry().size;
-                    }
-
-                    let mut geometry = Geometry {
-                        origin: pos.anchor_rect().origin,
-                        size: size_req,
-                    };
-
-                    if let Some(parent) = view.parent() {
-                        let parent_geometry = parent.geometry();
-                        geometry.origin.x += parent_geometry.origin.x;
-                        geometry.origin.y += parent_geometry.origin.y;
-                    }
-
-                    view.set_geometry(ResizeEdge::Null, geometry);
+    fn apply_rules(&mut self, view: &View, outcome: &Outcome) {
+        // geometry and states were applied by `Rules` already
+        if let Some(ref name) = outcome.output {
+            Output::with_all_outputs(|outputs| {
+                if let Some(output) = outputs.iter().find(|output| output.name() == name.as_str()) {
+                    view.set_output(output);
                 }
-                None => {
-                    let geometry = Geometry {
-                        origin: Point {
-                            x: if toggle { width + ewidth } else { 0 } as i32,
-                            y: y,
-                        },
-                        size: Size {
-                            w: if !toggle && i == len - 1 {
-                                size.w
-                            } else if toggle {
-                                width
-                            } else {
-                                width + ewidth
-                            },
-                            h: if i < 2 { height + eheight } else { height },
-                        },
-                    };
-                    view.set_geometry(ResizeEdge::Null, geometry);
-                    y += if toggle { geometry.size.h } else { 0 } as i32;
-                    toggle = !toggle;
+            });
+        }
+        if let Some(ref workspace) = outcome.workspace {
+            self.workspaces.move_view(view, workspace);
+        }
+        if outcome.sticky {
+            self.workspaces.set_sticky(view, true);
+        }
+        if let Some(floating) = outcome.floating {
+            self.layout.set_floating(view, floating);
+        }
+        self.transients.parent_changed(view);
+    }
+
+    fn relayout(&mut self, output: &Output) {
+        // popups are placed relative to their parent, everything else is tiled
+        for view in output.views() {
+            if let Some(pos) = view.positioner() {
+                let mut rules = pos.rules();
+                if rules.size.w == 0 || rules.size.h == 0 {
+                    rules.size = view.geometry().size;
                 }
+
+                let parent_geometry = match view.parent() {
+                    Some(parent) => parent.geometry(),
+                    None => Geometry {
+                        origin: Point { x: 0, y: 0 },
+                        size: Size { w: 0, h: 0 },
+                    },
+                };
+                let area = self.layout.usable_area(output);
+                view.set_geometry(ResizeEdge::Null, rules.solve(parent_geometry, area));
             }
         }
+
+        self.layout.relayout(output);
+        for view in output.views() {
+            self.transients.parent_changed(view);
+        }
     }
 }
 
@@ -153,36 +124,112 @@ impl wlc::Callback for Compositor {
         self.relayout(output);
     }
 
+    fn output_created(&mut self, output: &Output) -> bool {
+        self.workspaces.output_created(output);
+        true
+    }
+
+    fn output_destroyed(&mut self, output: &Output) {
+        self.workspaces.output_destroyed(output);
+        self.layout.output_destroyed(output);
+        self.interactive.output_destroyed(output);
+        self.focus.output_destroyed(output);
+    }
+
+    #[cfg(not(feature = "render"))]
+    fn output_render_pre(&mut self, output: &Output) {
+        self.focus.output_render_pre(output);
+    }
+
+    #[cfg(feature = "render")]
+    fn output_render_pre(&mut self, output: &mut render::RenderOutput) {
+        self.focus.output_render_pre(output);
+    }
+
     fn view_created(&mut self, view: &View) -> bool {
-        view.set_visibility(view.output().visibility());
-        view.bring_to_front();
-        view.focus();
+        self.workspaces.view_created(view);
+        let _ = self.stacking.view_created(view);
+        let area = self.layout.usable_area(view.output());
+        if self.scratchpad.view_created(view, area, &mut self.workspaces) {
+            self.layout.set_floating(view, true);
+            return true;
+        }
+        let dialog = self.transients.view_created(view);
+        let outcome = self.rules.view_created(view, &mut self.states, area);
+        self.apply_rules(view, &outcome);
+        if !dialog && outcome.geometry.is_none() && outcome.state.is_empty() && !self.layout.tiles(view) &&
+           view.positioner().is_none() {
+            placement::place(Strategy::MinimalOverlap, view, self.layout.usable_area(view.output()));
+        }
+        if !self.popups.view_created(view) && !outcome.no_focus {
+            self.focus.view_created(view);
+        }
         self.relayout(view.output());
         true
     }
 
     fn view_destroyed(&mut self, view: &View) {
-        match self.top_most(view.output(), 0) {
-            Some(view) => view.focus(),
-            None => View::set_no_focus(),
-        };
-        self.relayout(view.output());
+        self.focus.view_destroyed(view);
+        self.switcher.view_destroyed(view);
+        self.popups.view_destroyed(view);
+        self.stacking.view_destroyed(view);
+        self.transients.view_destroyed(view);
+        self.states.view_destroyed(view);
+        self.requests.view_destroyed(view);
+        self.scratchpad.view_destroyed(view);
+        self.interactive.view_destroyed(view);
+        self.workspaces.view_destroyed(view);
+        self.layout.view_destroyed(view);
+    }
+
+    fn view_move_to_output(&mut self, view: &View, from: &Output, to: &Output) {
+        self.workspaces.view_move_to_output(view, from, to);
+        self.layout.view_move_to_output(view, from, to);
     }
 
     fn view_focus(&mut self, view: &View, focus: bool) {
-        view.set_state(ViewState::Activated, focus);
+        self.focus.view_focus(view, focus);
+        self.popups.view_focus(view, focus);
+        if focus {
+            let _ = self.transients.raise(view, &self.stacking);
+        }
+        self.transients.view_focus(view, focus);
     }
 
-    fn view_request_move(&mut self, view: &View, origin: Point) {
-        self.start_interactive_move(view, origin)
+    fn view_properties_updated(&mut self, view: &View, mask: ViewPropertyUpdate::Flags) {
+        let area = self.layout.usable_area(view.output());
+        let outcome = self.rules.view_properties_updated(view, mask, &mut self.states, area);
+        self.apply_rules(view, &outcome);
     }
 
-    fn view_request_resize(&mut self, view: &View, edges: ResizeEdge::Flags, origin: Point) {
-        self.start_interactive_resize(view, edges, origin)
+    fn view_request_geometry(&mut self, view: &View, geometry: Geometry) {
+        self.requests.view_request_geometry(view, geometry, &self.layout);
+        self.transients.parent_changed(view);
     }
 
-    fn view_request_geometry(&mut self, _view: &View, _geometry: Geometry) {
-        // stub intentionally ignore geometry requests
+    fn view_request_state(&mut self, view: &View, state: ViewState::Flags, toggle: bool) {
+        match self.requests.view_request_state(view, state, toggle, &self.layout) {
+            Decision::Accept | Decision::Clamp => {}
+            Decision::Reject | Decision::Defer => return,
+        }
+        let area = self.layout.usable_area(view.output());
+        self.states.view_request_state(view, state, toggle, area);
+        self.transients.parent_changed(view);
+        let _ = self.stacking.view_request_state(view, state, toggle);
+    }
+
+    fn view_request_move(&mut self, view: &View, origin: Point) {
+        match self.requests.view_request_move(view, origin, &self.layout) {
+            Decision::Accept | Decision::Clamp => self.interactive.view_request_move(view, origin),
+            Decision::Reject | Decision::Defer => {}
+        }
+    }
+
+    fn view_request_resize(&mut self, view: &View, edges: ResizeEdge::Flags, origin: Point) {
+        match self.requests.view_request_resize(view, edges, origin, &self.layout) {
+            Decision::Accept | Decision::Clamp => self.interactive.view_request_resize(view, edges, origin),
+            Decision::Reject | Decision::Defer => {}
+        }
     }
 
     fn keyboard_key(&mut self, view: Option<&View>, _time: u32, modifiers: Modifiers, key: Key,
@@ -190,6 +237,10 @@ impl wlc::Callback for Compositor {
                     -> bool {
         use wlc::input::keyboard::Keysyms;
 
+        if self.switcher.keyboard_key(view, modifiers, key, state, &mut self.focus) {
+            return true;
+        }
+
         let sym = input::keyboard::keysym_for_key(key, Modifiers::empty());
 
         if state == KeyState::Pressed {
@@ -198,11 +249,34 @@ impl wlc::Callback for Compositor {
                     view.close();
                     return true;
                 } else if modifiers.mods.contains(Modifier::Ctrl) && sym == Keysyms::KEY_Down {
-                    view.send_to_back();
+                    let _ = self.stacking.lower(view);
                     if let Some(new_view) = self.top_most(view.output(), 0) {
                         new_view.focus();
                     }
                     return true;
+                } else if modifiers.mods.contains(Modifier::Ctrl | Modifier::Shift) &&
+                          sym == Keysyms::KEY_minus {
+                    self.scratchpad.add(view, &mut self.workspaces);
+                    self.layout.set_floating(view, true);
+                    self.transients.parent_changed(view);
+                    return true;
+                } else if modifiers.mods.contains(Modifier::Ctrl) && sym == Keysyms::KEY_minus {
+                    let area = self.layout.usable_area(view.output());
+                    let hidden = self.scratchpad.shown();
+                    self.scratchpad.toggle(view.output(), area);
+                    for changed in hidden.into_iter().chain(self.scratchpad.shown()) {
+                        changed.run(|changed| self.transients.parent_changed(changed));
+                    }
+                    return true;
+                } else if modifiers.mods.contains(Modifier::Ctrl) && sym == Keysyms::KEY_grave {
+                    let area = self.layout.usable_area(view.output());
+                    let hidden = self.scratchpad.shown();
+                    let _ = self.scratchpad
+                        .summon("weston-terminal", view.output(), area, &mut self.workspaces);
+                    for changed in hidden.into_iter().chain(self.scratchpad.shown()) {
+                        changed.run(|changed| self.transients.parent_changed(changed));
+                    }
+                    return true;
                 }
             }
 
@@ -237,73 +311,23 @@ impl wlc::Callback for Compositor {
     fn pointer_button(&mut self, view: Option<&View>, _time: u32, modifiers: Modifiers, button: Button,
                       state: ButtonState, origin: Point)
                       -> bool {
-        if state == ButtonState::Pressed {
-            match view {
-                Some(view) => {
-                    view.focus();
-                    if modifiers.mods.contains(Modifier::Ctrl) && button == Button::Left {
-                        self.start_interactive_move(view, origin);
-                    } else if modifiers.mods.contains(Modifier::Ctrl) && button == Button::Right {
-                        self.start_interactive_resize(view, ResizeEdge::Null, origin);
-                    }
-                }
-                None => View::set_no_focus(),
-            };
-        } else {
-            self.stop_interactive_action()
+        if self.popups.pointer_button(state, origin) {
+            return true;
         }
-
-        !self.action.is_none()
+        self.focus.pointer_button(view, state);
+        self.interactive.pointer_button(view, modifiers, button, state, origin)
     }
 
-    fn pointer_motion(&mut self, _view: Option<&View>, _time: u32, position: Point) -> bool {
-        match self.action {
-            Action::Resize(ref weakview, ref grab, ref edges) => {
-                weakview.run(|view| {
-                    let dx = position.x - grab.x;
-                    let dy = position.y - grab.y;
-                    let mut geo = view.geometry();
-
-                    let min_size = Size { w: 80, h: 40 };
-                    let mut n = geo;
-
-                    if edges.contains(ResizeEdge::Left) {
-                        n.size.w = (n.size.w as i32 - dx) as u32;
-                        n.origin.x += dx;
-                    } else if edges.contains(ResizeEdge::Right) {
-                        n.size.w = (n.size.w as i32 + dx) as u32;
-                    }
-
-                    if edges.contains(ResizeEdge::Top) {
-                        n.size.h = (n.size.h as i32 - dy) as u32;
-                        n.origin.y += dy;
-                    } else if edges.contains(ResizeEdge::Bottom) {
-                        n.size.h = (n.size.h as i32 + dy) as u32;
-                    }
-
-                    if n.size >= min_size {
-                        geo = n;
-                    }
-
-                    view.set_geometry(*edges, geo);
-                });
-            }
-            Action::Move(ref weakview, ref grab) => {
-                weakview.run(|view| {
-                    let dx = position.x - grab.x;
-                    let dy = position.y - grab.y;
-                    let mut geo = view.geometry();
-
-                    geo.origin.x += dx;
-                    geo.origin.y += dy;
-                    view.set_geometry(ResizeEdge::Null, geo);
-                });
-            }
-            Action::None => {}
-        };
-
+    fn pointer_motion(&mut self, view: Option<&View>, _time: u32, position: Point) -> bool {
         input::pointer::set_position(position);
-        !self.action.is_none()
+        if self.interactive.pointer_motion(position) {
+            if let Some((view, _)) = self.interactive.operation() {
+                view.run(|view| self.transients.parent_changed(view));
+            }
+            return true;
+        }
+        self.focus.pointer_motion(view);
+        false
     }
 }
 
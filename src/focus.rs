@@ -0,0 +1,377 @@
+//! Keyboard focus management
+//!
+//! The `FocusManager` keeps a most recently used history of views per output
+//! and `Visibility` of that output, which is the history of every workspace
+//! shown on it. Whenever the focused view is destroyed or hidden, the most
+//! recently used visible view of the current workspace gets the focus back.
+//!
+//! A `FocusPolicy` decides how the pointer moves the focus. Views may also be
+//! focused by direction based on their geometry.
+//!
+//! Forward `view_created`, `view_destroyed`, `view_focus`, `pointer_button`,
+//! `pointer_motion`, `output_destroyed` and `output_render_pre`, which moves
+//! the focus away from views hidden since the last frame. Call
+//! `FocusManager::refocus` to do so immediately after changing the
+//! visibility of views or outputs.
+
+use {ButtonState, Geometry, Output, View, ViewState, Visibility, WeakOutput, WeakView};
+use tree::Direction;
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// How the pointer influences the focus
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub enum FocusPolicy {
+    /// Views are focused by clicking them
+    ClickToFocus,
+    /// The view under the pointer is focused, nothing is focused if the
+    /// pointer is not above a view
+    FocusFollowsMouse,
+    /// The view under the pointer is focused, the focus stays if the pointer
+    /// leaves it for the background
+    Sloppy,
+}
+
+/// Doubled center of a geometry, to stay in integers
+fn center(geometry: &Geometry) -> (i64, i64) {
+    (geometry.origin.x as i64 * 2 + geometry.size.w as i64,
+     geometry.origin.y as i64 * 2 + geometry.size.h as i64)
+}
+
+/// Index of the candidate nearest to `from` in the given direction
+///
+/// Candidates are compared by the distance of their centers, offsets
+/// perpendicular to the direction count twice.
+pub fn nearest_in_direction(from: Geometry, candidates: &[Geometry], direction: Direction) -> Option<usize> {
+    let (x, y) = center(&from);
+    candidates.iter()
+        .enumerate()
+        .filter_map(|(i, candidate)| {
+            let (cx, cy) = center(candidate);
+            let (distance, offset) = match direction {
+                Direction::Left => (x - cx, cy - y),
+                Direction::Right => (cx - x, cy - y),
+                Direction::Up => (y - cy, cx - x),
+                Direction::Down => (cy - y, cx - x),
+            };
+            if distance > 0 {
+                Some((i, distance + offset.abs() * 2))
+            } else {
+                None
+            }
+        })
+        .min_by_key(|&(_, score)| score)
+        .map(|(i, _)| i)
+}
+
+/// Most recently used order of items, kept separately per key
+struct History<K, T> {
+    lists: HashMap<K, Vec<T>>,
+}
+
+impl<K: Eq + Hash, T: PartialEq> History<K, T> {
+    fn new() -> History<K, T> {
+        History { lists: HashMap::new() }
+    }
+
+    /// Make the item the most recently used one of the key
+    fn promote(&mut self, key: K, item: T) {
+        let list = self.lists.entry(key).or_insert_with(Vec::new);
+        list.retain(|other| *other != item);
+        list.insert(0, item);
+    }
+
+    /// Add the item as least recently used one of the key, if it is missing
+    fn push(&mut self, key: K, item: T) {
+        let list = self.lists.entry(key).or_insert_with(Vec::new);
+        if !list.contains(&item) {
+            list.push(item);
+        }
+    }
+
+    /// Remove the item from the history of all keys
+    fn remove(&mut self, item: &T) {
+        for list in self.lists.values_mut() {
+            list.retain(|other| *other != *item);
+        }
+        self.lists.retain(|_, list| !list.is_empty());
+    }
+
+    /// Remove the history of all keys matching the predicate
+    fn remove_keys<F: Fn(&K) -> bool>(&mut self, predicate: F) {
+        self.lists.retain(|key, _| !predicate(key));
+    }
+
+    /// Items of the key, most recently used first
+    fn order(&self, key: &K) -> &[T] {
+        self.lists.get(key).map(|list| list.as_slice()).unwrap_or(&[])
+    }
+}
+
+/// Key of the history of the workspace currently shown on the output
+fn history_key(output: &Output) -> (WeakOutput, Visibility::Flags) {
+    (output.weak_reference(), output.visibility())
+}
+
+fn is_visible(view: &View) -> bool {
+    view.visibility().intersects(view.output().visibility())
+}
+
+/// Tracks the focus and the most recently used order of views
+pub struct FocusManager {
+    policy: FocusPolicy,
+    focus_new: bool,
+    history: History<(WeakOutput, Visibility::Flags), WeakView>,
+    focused: Option<WeakView>,
+}
+
+impl FocusManager {
+    /// Create a new manager with the given policy, focusing new views
+    pub fn new(policy: FocusPolicy) -> FocusManager {
+        FocusManager {
+            policy: policy,
+            focus_new: true,
+            history: History::new(),
+            focused: None,
+        }
+    }
+
+    /// Currently used policy
+    pub fn policy(&self) -> FocusPolicy {
+        self.policy
+    }
+
+    /// Change the policy
+    pub fn set_policy(&mut self, policy: FocusPolicy) {
+        self.policy = policy;
+    }
+
+    /// Set if newly created views are focused
+    pub fn set_focus_new(&mut self, focus_new: bool) {
+        self.focus_new = focus_new;
+    }
+
+    /// The focused view
+    pub fn focused(&self) -> Option<WeakView> {
+        self.focused.clone()
+    }
+
+    /// Focus a view and make it the most recently used one
+    pub fn focus(&mut self, view: &View) {
+        if !self.is_focused(view) {
+            view.focus();
+        }
+        self.promote(view);
+    }
+
+    /// Remove the focus from all views
+    pub fn unfocus(&mut self) {
+        if self.focused.take().is_some() {
+            View::set_no_focus();
+        }
+    }
+
+    fn is_focused(&self, view: &View) -> bool {
+        self.focused.as_ref().map(|focused| *focused == *view).unwrap_or(false)
+    }
+
+    fn promote(&mut self, view: &View) {
+        self.history.promote(history_key(view.output()), view.weak_reference());
+        self.focused = Some(view.weak_reference());
+    }
+
+    /// Visible views of the output, most recently used on the current
+    /// workspace first
+    ///
+    /// Visible views never focused on this workspace, e.g. sticky views or
+    /// views moved here, follow from top to bottom.
+    pub fn history(&self, output: &Output) -> Vec<WeakView> {
+        let mut views: Vec<WeakView> = self.history
+            .order(&history_key(output))
+            .iter()
+            .filter(|view| view.run(|view| view.output() == output && is_visible(view)).unwrap_or(false))
+            .cloned()
+            .collect();
+        for view in output.views().into_iter().rev() {
+            if is_visible(view) && !views.iter().any(|other| *other == *view) {
+                views.push(view.weak_reference());
+            }
+        }
+        views
+    }
+
+    /// Focus the most recently used visible view of the output, unless the
+    /// focused view is still visible
+    pub fn refocus(&mut self, output: &Output) {
+        let visible = self.focused.as_ref().and_then(|view| view.run(is_visible)).unwrap_or(false);
+        if visible {
+            return;
+        }
+
+        match self.history(output).first() {
+            Some(view) => {
+                view.run(|view| self.focus(view));
+            }
+            None => self.unfocus(),
+        }
+    }
+
+    /// Focus the nearest visible view in the given direction of the focused
+    /// view on the output
+    ///
+    /// Returns `false`, if there is no view in that direction
+    pub fn focus_direction(&mut self, output: &Output, direction: Direction) -> bool {
+        let from = match self.focused.as_ref().and_then(|view| view.run(|view| view.geometry())) {
+            Some(geometry) => geometry,
+            None => return false,
+        };
+        let candidates: Vec<&View> = output.views()
+            .into_iter()
+            .filter(|view| is_visible(view) && !self.is_focused(view))
+            .collect();
+        let geometries: Vec<Geometry> = candidates.iter().map(|view| view.geometry()).collect();
+
+        match nearest_in_direction(from, &geometries, direction) {
+            Some(i) => {
+                self.focus(candidates[i]);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Forward of `Callback::view_created`
+    pub fn view_created(&mut self, view: &View) {
+        if self.focus_new {
+            self.focus(view);
+        } else {
+            self.history.push(history_key(view.output()), view.weak_reference());
+        }
+    }
+
+    /// Forward of `Callback::view_destroyed`
+    pub fn view_destroyed(&mut self, view: &View) {
+        self.history.remove(&view.weak_reference());
+        if self.is_focused(view) {
+            self.focused = None;
+            self.refocus(view.output());
+        }
+    }
+
+    /// Forward of `Callback::view_focus`
+    ///
+    /// Sets `ViewState::Activated` and keeps track of views focused by other
+    /// means than this manager.
+    pub fn view_focus(&mut self, view: &View, focus: bool) {
+        view.set_state(ViewState::Activated, focus);
+        if focus {
+            self.promote(view);
+        } else if self.is_focused(view) {
+            self.focused = None;
+        }
+    }
+
+    /// Forward of `Callback::output_destroyed`
+    pub fn output_destroyed(&mut self, output: &Output) {
+        self.history.remove_keys(|&(ref other, _)| *other == *output);
+    }
+
+    /// Forward of `Callback::output_render_pre`
+    ///
+    /// Refocuses the output, if its focused view was hidden.
+    pub fn output_render_pre(&mut self, output: &Output) {
+        let hidden = self.focused
+            .as_ref()
+            .and_then(|view| view.run(|view| view.output() == output && !is_visible(view)))
+            .unwrap_or(false);
+        if hidden {
+            self.refocus(output);
+        }
+    }
+
+    /// Forward of `Callback::pointer_button`
+    pub fn pointer_button(&mut self, view: Option<&View>, state: ButtonState) {
+        if let (Some(view), ButtonState::Pressed) = (view, state) {
+            self.focus(view);
+        }
+    }
+
+    /// Forward of `Callback::pointer_motion`
+    pub fn pointer_motion(&mut self, view: Option<&View>) {
+        match (self.policy, view) {
+            (FocusPolicy::ClickToFocus, _) => {}
+            (FocusPolicy::FocusFollowsMouse, None) => self.unfocus(),
+            (_, Some(view)) => {
+                if !self.is_focused(view) {
+                    self.focus(view);
+                }
+            }
+            (FocusPolicy::Sloppy, None) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_util::geometry;
+
+    #[test]
+    fn test_history_is_kept_per_key() {
+        let mut history = History::new();
+        history.promote(1, "a");
+        history.promote(1, "b");
+        history.promote(2, "c");
+        history.push(1, "d");
+        history.push(1, "a");
+        assert_eq!(history.order(&1), &["b", "a", "d"]);
+        assert_eq!(history.order(&2), &["c"]);
+
+        history.promote(1, "a");
+        history.promote(2, "a");
+        assert_eq!(history.order(&1), &["a", "b", "d"]);
+        assert_eq!(history.order(&2), &["a", "c"]);
+    }
+
+    #[test]
+    fn test_history_remove() {
+        let mut history = History::new();
+        history.promote(1, "a");
+        history.promote(2, "a");
+        history.promote(2, "b");
+        history.promote(3, "c");
+
+        history.remove(&"a");
+        assert!(history.order(&1).is_empty());
+        assert_eq!(history.order(&2), &["b"]);
+
+        history.remove_keys(|&key| key > 2);
+        assert!(history.order(&3).is_empty());
+        assert_eq!(history.order(&2), &["b"]);
+        assert!(history.order(&4).is_empty());
+    }
+
+    #[test]
+    fn test_nearest_in_direction() {
+        let from = geometry(500, 500, 100, 100);
+        let candidates = [geometry(0, 500, 100, 100),
+                          geometry(300, 0, 100, 100),
+                          geometry(800, 500, 100, 100),
+                          geometry(650, 900, 100, 100)];
+        assert_eq!(nearest_in_direction(from, &candidates, Direction::Left), Some(0));
+        assert_eq!(nearest_in_direction(from, &candidates, Direction::Up), Some(1));
+        assert_eq!(nearest_in_direction(from, &candidates, Direction::Right), Some(2));
+        assert_eq!(nearest_in_direction(from, &candidates, Direction::Down), Some(3));
+        assert_eq!(nearest_in_direction(from, &[], Direction::Down), None);
+    }
+
+    #[test]
+    fn test_perpendicular_offset_is_penalised() {
+        let from = geometry(0, 0, 100, 100);
+        // closer, but far off to the side
+        let candidates = [geometry(150, 300, 100, 100), geometry(400, 0, 100, 100)];
+        assert_eq!(nearest_in_direction(from, &candidates, Direction::Right), Some(1));
+    }
+}
@@ -0,0 +1,453 @@
+//! Interactive move and resize of views
+//!
+//! `Interactive` implements the grab a user performs when dragging a view
+//! around or resizing it by one of its edges. An operation is started by a
+//! client through `Callback::view_request_move` and
+//! `Callback::view_request_resize` or by the compositor through a `Binding`
+//! of modifiers and pointer buttons. While the operation is running pointer
+//! motion is applied relative to the geometry the view had, when it started,
+//! and any button release finishes it.
+//!
+//! Moves may snap to edges and zones of the output, see `snapping`. A view
+//! tiled to a `SnapZone` gets its previous size back, once it is dragged out
+//! of it again.
+//!
+//! Forward the respective callbacks and return the result of
+//! `Interactive::pointer_button` and `Interactive::pointer_motion` to stop
+//! the events from reaching the client during an operation.
+
+use {ButtonState, Geometry, Modifier, Modifiers, Output, Point, ResizeEdge, Size, View, ViewState, WeakOutput,
+     WeakView};
+use input::pointer::Button;
+use snapping::{self, SnapZone, Snapping};
+
+use std::cmp;
+use std::collections::HashMap;
+
+/// Kind of interactive operation
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operation {
+    /// The view follows the pointer
+    Move,
+    /// The given edges of the view follow the pointer
+    Resize(ResizeEdge::Flags),
+}
+
+/// Pointer buttons, that start an operation on the view under the pointer,
+/// while the modifiers are held
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Binding {
+    /// Modifiers, that need to be held
+    pub modifiers: Modifier::Flags,
+    /// Button starting a move
+    pub move_button: Button,
+    /// Button starting a resize of the edges nearest to the pointer
+    pub resize_button: Button,
+}
+
+/// Edges of the geometry nearest to the given point
+///
+/// Each axis contributes one edge, depending on which half of the geometry
+/// the point lies in. A point on the center line selects no edge for that
+/// axis.
+pub fn nearest_edges(geometry: Geometry, point: Point) -> ResizeEdge::Flags {
+    let center_x = geometry.origin.x + geometry.size.w as i32 / 2;
+    let center_y = geometry.origin.y + geometry.size.h as i32 / 2;
+
+    let horizontal = if point.x < center_x {
+        ResizeEdge::Left
+    } else if point.x > center_x {
+        ResizeEdge::Right
+    } else {
+        ResizeEdge::Null
+    };
+
+    let vertical = if point.y < center_y {
+        ResizeEdge::Top
+    } else if point.y > center_y {
+        ResizeEdge::Bottom
+    } else {
+        ResizeEdge::Null
+    };
+
+    horizontal | vertical
+}
+
+/// Geometry moved by `delta`
+pub fn moved(start: Geometry, delta: Point) -> Geometry {
+    Geometry {
+        origin: Point {
+            x: start.origin.x + delta.x,
+            y: start.origin.y + delta.y,
+        },
+        size: start.size,
+    }
+}
+
+/// Geometry with the given edges moved by `delta`
+///
+/// The size never gets smaller than `min_size`, the opposite edges stay in
+/// place.
+pub fn resized(start: Geometry, edges: ResizeEdge::Flags, delta: Point, min_size: Size) -> Geometry {
+    let mut geometry = start;
+
+    if edges.contains(ResizeEdge::Left) {
+        geometry.size.w = cmp::max(start.size.w as i32 - delta.x, min_size.w as i32) as u32;
+        geometry.origin.x = start.origin.x + start.size.w as i32 - geometry.size.w as i32;
+    } else if edges.contains(ResizeEdge::Right) {
+        geometry.size.w = cmp::max(start.size.w as i32 + delta.x, min_size.w as i32) as u32;
+    }
+
+    if edges.contains(ResizeEdge::Top) {
+        geometry.size.h = cmp::max(start.size.h as i32 - delta.y, min_size.h as i32) as u32;
+        geometry.origin.y = start.origin.y + start.size.h as i32 - geometry.size.h as i32;
+    } else if edges.contains(ResizeEdge::Bottom) {
+        geometry.size.h = cmp::max(start.size.h as i32 + delta.y, min_size.h as i32) as u32;
+    }
+
+    geometry
+}
+
+struct Grab {
+    view: WeakView,
+    operation: Operation,
+    origin: Point,
+    start: Geometry,
+    /// Size to restore on the first motion of a snapped view
+    restore: Option<Size>,
+    zone: Option<SnapZone>,
+}
+
+impl Grab {
+    fn state(&self) -> ViewState::Flags {
+        match self.operation {
+            Operation::Move => ViewState::Moving,
+            Operation::Resize(_) => ViewState::Resizing,
+        }
+    }
+}
+
+/// Usable area of the output, the whole virtual resolution if none was set
+fn usable_area(areas: &HashMap<WeakOutput, Geometry>, output: &Output) -> Geometry {
+    areas.get(&output.weak_reference()).cloned().unwrap_or_else(|| {
+        Geometry {
+            origin: Point { x: 0, y: 0 },
+            size: output.virtual_resolution(),
+        }
+    })
+}
+
+/// Snap a moved geometry inside the area according to the configuration
+fn snap_move(snapping: &Snapping, view: &View, area: Geometry, geometry: Geometry, pointer: Point)
+             -> (Geometry, Option<SnapZone>) {
+    if let Some(zone) = snapping::zone_at(area, pointer, snapping.zone_threshold) {
+        return (geometry, Some(zone));
+    }
+
+    let output = view.output();
+    let others: Vec<Geometry> = output.views()
+        .into_iter()
+        .filter(|other| *other != view && other.visibility().intersects(output.visibility()))
+        .map(|other| other.geometry())
+        .collect();
+    (snapping::snap_edges(geometry, area, &others, snapping.threshold), None)
+}
+
+/// State machine for interactive move and resize operations
+///
+/// Only one operation may run at a time.
+pub struct Interactive {
+    grab: Option<Grab>,
+    binding: Option<Binding>,
+    min_size: Size,
+    snapping: Option<Snapping>,
+    snapped: HashMap<WeakView, Size>,
+    areas: HashMap<WeakOutput, Geometry>,
+}
+
+impl Default for Interactive {
+    fn default() -> Interactive {
+        Interactive {
+            grab: None,
+            binding: None,
+            min_size: Size { w: 80, h: 40 },
+            snapping: None,
+            snapped: HashMap::new(),
+            areas: HashMap::new(),
+        }
+    }
+}
+
+impl Interactive {
+    /// Create a new state machine without a binding and a minimum size of
+    /// 80x40
+    pub fn new() -> Interactive {
+        Interactive::default()
+    }
+
+    /// Set the binding to start operations with, `None` disables it
+    pub fn set_binding(&mut self, binding: Option<Binding>) {
+        self.binding = binding;
+    }
+
+    /// Set the size views may not be resized below
+    pub fn set_min_size(&mut self, size: Size) {
+        self.min_size = size;
+    }
+
+    /// Set the snapping configuration for moves, `None` disables snapping
+    pub fn set_snapping(&mut self, snapping: Option<Snapping>) {
+        self.snapping = snapping;
+    }
+
+    /// Restrict snapping of the output to an area, e.g. the usable area of
+    /// `wayland::layer_shell::LayerShell`
+    ///
+    /// By default the whole virtual resolution is used.
+    pub fn set_usable_area(&mut self, output: &Output, area: Geometry) {
+        self.areas.insert(output.weak_reference(), area);
+    }
+
+    /// Usable area of the output
+    pub fn usable_area(&self, output: &Output) -> Geometry {
+        usable_area(&self.areas, output)
+    }
+
+    /// Check if the view was tiled to a `SnapZone` by a move
+    pub fn is_snapped(&self, view: &View) -> bool {
+        self.snapped.contains_key(&view.weak_reference())
+    }
+
+    /// Region the moved view will be tiled to, if the move finishes now
+    ///
+    /// Draw it as preview during the move.
+    pub fn preview(&self) -> Option<Geometry> {
+        let grab = match self.grab {
+            Some(ref grab) => grab,
+            None => return None,
+        };
+        grab.zone.and_then(|zone| grab.view.run(|view| zone.geometry(self.usable_area(view.output()))))
+    }
+
+    /// Minimum size of resized views
+    pub fn min_size(&self) -> Size {
+        self.min_size
+    }
+
+    /// Check if an operation is running
+    pub fn is_active(&self) -> bool {
+        self.grab.is_some()
+    }
+
+    /// Running operation and the view it is applied to
+    pub fn operation(&self) -> Option<(WeakView, Operation)> {
+        self.grab.as_ref().map(|grab| (grab.view.clone(), grab.operation))
+    }
+
+    /// Start moving the view with the pointer at `origin`
+    ///
+    /// Returns `false` if another operation is already running
+    pub fn start_move(&mut self, view: &View, origin: Point) -> bool {
+        self.start(view, Operation::Move, origin)
+    }
+
+    /// Start resizing the given edges of the view with the pointer at
+    /// `origin`
+    ///
+    /// Empty edges are replaced by the edges nearest to `origin`. Returns
+    /// `false` if another operation is already running
+    pub fn start_resize(&mut self, view: &View, edges: ResizeEdge::Flags, origin: Point) -> bool {
+        let edges = if edges.is_empty() {
+            nearest_edges(view.geometry(), origin)
+        } else {
+            edges
+        };
+        self.start(view, Operation::Resize(edges), origin)
+    }
+
+    fn start(&mut self, view: &View, operation: Operation, origin: Point) -> bool {
+        if self.grab.is_some() {
+            return false;
+        }
+
+        let weak = view.weak_reference();
+        let restore = match operation {
+            Operation::Move => self.snapped.get(&weak).cloned(),
+            Operation::Resize(_) => None,
+        };
+        let grab = Grab {
+            view: weak,
+            operation: operation,
+            origin: origin,
+            start: view.geometry(),
+            restore: restore,
+            zone: None,
+        };
+        view.bring_to_front();
+        view.set_state(grab.state(), true);
+        self.grab = Some(grab);
+        true
+    }
+
+    /// Finish the running operation, keeping the current geometry or tiling
+    /// the view to the selected `SnapZone`
+    pub fn finish(&mut self) {
+        if let Some(grab) = self.grab.take() {
+            let snapped = &mut self.snapped;
+            let areas = &self.areas;
+            grab.view.run(|view| {
+                              view.set_state(grab.state(), false);
+                              if let Some(zone) = grab.zone {
+                                  snapped.insert(grab.view.clone(), grab.start.size);
+                                  let area = usable_area(areas, view.output());
+                                  view.set_geometry(ResizeEdge::Null, zone.geometry(area));
+                              }
+                          });
+        }
+    }
+
+    /// Abort the running operation, restoring the geometry the view had
+    /// before
+    pub fn cancel(&mut self) {
+        if let Some(grab) = self.grab.take() {
+            grab.view.run(|view| {
+                              view.set_state(grab.state(), false);
+                              view.set_geometry(ResizeEdge::Null, grab.start);
+                          });
+        }
+    }
+
+    /// Forward of `Callback::output_destroyed`
+    pub fn output_destroyed(&mut self, output: &Output) {
+        self.areas.remove(&output.weak_reference());
+    }
+
+    /// Forward of `Callback::view_destroyed`
+    pub fn view_destroyed(&mut self, view: &View) {
+        let grabbed = self.grab.as_ref().map(|grab| grab.view == *view).unwrap_or(false);
+        if grabbed {
+            self.grab = None;
+        }
+        self.snapped.remove(&view.weak_reference());
+    }
+
+    /// Forward of `Callback::view_request_move`
+    pub fn view_request_move(&mut self, view: &View, origin: Point) {
+        self.start_move(view, origin);
+    }
+
+    /// Forward of `Callback::view_request_resize`
+    pub fn view_request_resize(&mut self, view: &View, edges: ResizeEdge::Flags, origin: Point) {
+        self.start_resize(view, edges, origin);
+    }
+
+    /// Forward of `Callback::pointer_button`
+    ///
+    /// Starts an operation, if the binding matches, and finishes the running
+    /// operation on button release. Returns `true` if the event was consumed.
+    pub fn pointer_button(&mut self, view: Option<&View>, modifiers: Modifiers, button: Button,
+                          state: ButtonState, origin: Point)
+                          -> bool {
+        if state == ButtonState::Released {
+            let active = self.is_active();
+            self.finish();
+            return active;
+        }
+
+        let (view, binding) = match (view, self.binding) {
+            (Some(view), Some(binding)) => (view, binding),
+            _ => return false,
+        };
+        if !modifiers.mods.contains(binding.modifiers) {
+            return false;
+        }
+
+        if button == binding.move_button {
+            self.start_move(view, origin)
+        } else if button == binding.resize_button {
+            self.start_resize(view, ResizeEdge::Null, origin)
+        } else {
+            false
+        }
+    }
+
+    /// Forward of `Callback::pointer_motion`
+    ///
+    /// Returns `true` if the event was consumed.
+    pub fn pointer_motion(&mut self, position: Point) -> bool {
+        let min_size = self.min_size;
+        let snapping = self.snapping;
+        let areas = &self.areas;
+        let grab = match self.grab {
+            Some(ref mut grab) => grab,
+            None => return false,
+        };
+
+        if let Some(size) = grab.restore.take() {
+            grab.start = snapping::unsnapped(grab.start, size, grab.origin);
+            self.snapped.remove(&grab.view);
+        }
+
+        let delta = Point {
+            x: position.x - grab.origin.x,
+            y: position.y - grab.origin.y,
+        };
+        let zone = grab.view.run(|view| {
+            match grab.operation {
+                Operation::Move => {
+                    let geometry = moved(grab.start, delta);
+                    let (geometry, zone) = match snapping {
+                        Some(ref snapping) => {
+                            let area = usable_area(areas, view.output());
+                            snap_move(snapping, view, area, geometry, position)
+                        }
+                        None => (geometry, None),
+                    };
+                    view.set_geometry(ResizeEdge::Null, geometry);
+                    zone
+                }
+                Operation::Resize(edges) => {
+                    view.set_geometry(edges, resized(grab.start, edges, delta, min_size));
+                    None
+                }
+            }
+        });
+        grab.zone = zone.and_then(|zone| zone);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_util::geometry;
+
+    #[test]
+    fn test_nearest_edges() {
+        let area = geometry(0, 0, 100, 100);
+        assert_eq!(nearest_edges(area, Point { x: 10, y: 90 }),
+                   ResizeEdge::Left | ResizeEdge::Bottom);
+        assert_eq!(nearest_edges(area, Point { x: 90, y: 10 }),
+                   ResizeEdge::Right | ResizeEdge::Top);
+        assert_eq!(nearest_edges(area, Point { x: 50, y: 10 }), ResizeEdge::Top);
+    }
+
+    #[test]
+    fn test_resized_keeps_opposite_edges() {
+        let start = geometry(100, 100, 200, 200);
+        let min = Size { w: 80, h: 40 };
+        assert_eq!(resized(start, ResizeEdge::Right | ResizeEdge::Bottom, Point { x: 10, y: -20 }, min),
+                   geometry(100, 100, 210, 180));
+        assert_eq!(resized(start, ResizeEdge::Left | ResizeEdge::Top, Point { x: 10, y: -20 }, min),
+                   geometry(110, 80, 190, 220));
+        // clamped to the minimum size, right and bottom edges do not move
+        assert_eq!(resized(start, ResizeEdge::Left | ResizeEdge::Top, Point { x: 500, y: 500 }, min),
+                   geometry(220, 260, 80, 40));
+    }
+
+    #[test]
+    fn test_moved() {
+        assert_eq!(moved(geometry(10, 10, 50, 50), Point { x: -20, y: 5 }),
+                   geometry(-10, 15, 50, 50));
+    }
+}
@@ -0,0 +1,486 @@
+//! Tiling layouts
+//!
+//! A `Layout` maps the usable area of an `Output` and the number of tiled
+//! views to a list of geometries, one for each view in order. Layouts are
+//! pure and may be used on their own, the `LayoutEngine` applies them to the
+//! views of all outputs via `View::set_geometry`.
+//!
+//! Provided layouts are `MasterStack`, `Grid`, `Columns`, `Monocle` and
+//! `Spiral`, implement `Layout` for your own.
+
+use {Geometry, Output, Point, ResizeEdge, Size, View, ViewState, WeakOutput, WeakView};
+
+use std::cmp;
+use std::collections::{HashMap, HashSet};
+
+/// Maps an area and a number of views to their geometries
+pub trait Layout {
+    /// Compute the geometries of `count` views inside `area`
+    ///
+    /// The returned `Vec` needs to contain exactly `count` geometries.
+    fn arrange(&self, area: Geometry, count: usize) -> Vec<Geometry>;
+}
+
+/// Gaps between views and around the usable area
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Gaps {
+    /// Gap between two neighbouring views
+    pub inner: u32,
+    /// Gap between the views and the borders of the area
+    pub outer: u32,
+}
+
+impl Gaps {
+    fn shrink(&self, area: Geometry) -> Geometry {
+        Geometry {
+            origin: Point {
+                x: area.origin.x + self.outer as i32,
+                y: area.origin.y + self.outer as i32,
+            },
+            size: Size {
+                w: area.size.w.saturating_sub(self.outer * 2),
+                h: area.size.h.saturating_sub(self.outer * 2),
+            },
+        }
+    }
+
+    /// Shrink cells of the (already shrunken) area, so that neighbours are
+    /// `inner` pixels apart
+    fn apply(&self, area: Geometry, cells: Vec<Geometry>) -> Vec<Geometry> {
+        let before = self.inner - self.inner / 2;
+        let after = self.inner / 2;
+        let right = area.origin.x + area.size.w as i32;
+        let bottom = area.origin.y + area.size.h as i32;
+
+        cells.into_iter()
+            .map(|cell| {
+                let left = if cell.origin.x > area.origin.x { before } else { 0 };
+                let top = if cell.origin.y > area.origin.y { before } else { 0 };
+                let right = if cell.origin.x + (cell.size.w as i32) < right { after } else { 0 };
+                let bottom = if cell.origin.y + (cell.size.h as i32) < bottom { after } else { 0 };
+                Geometry {
+                    origin: Point {
+                        x: cell.origin.x + left as i32,
+                        y: cell.origin.y + top as i32,
+                    },
+                    size: Size {
+                        w: cell.size.w.saturating_sub(left + right),
+                        h: cell.size.h.saturating_sub(top + bottom),
+                    },
+                }
+            })
+            .collect()
+    }
+}
+
+/// Split `length` into `parts` pieces of `(offset, length)`, the remainder
+/// is distributed over the first pieces
+fn split(length: u32, parts: usize) -> Vec<(u32, u32)> {
+    if parts == 0 {
+        return Vec::new();
+    }
+    let base = length / parts as u32;
+    let extra = length % parts as u32;
+
+    let mut offset = 0;
+    (0..parts as u32)
+        .map(|i| {
+                 let piece = base + if i < extra { 1 } else { 0 };
+                 let result = (offset, piece);
+                 offset += piece;
+                 result
+             })
+        .collect()
+}
+
+/// Stack `count` cells from top to bottom
+fn column(area: Geometry, count: usize) -> Vec<Geometry> {
+    split(area.size.h, count)
+        .into_iter()
+        .map(|(offset, height)| {
+                 Geometry {
+                     origin: Point {
+                         x: area.origin.x,
+                         y: area.origin.y + offset as i32,
+                     },
+                     size: Size {
+                         w: area.size.w,
+                         h: height,
+                     },
+                 }
+             })
+        .collect()
+}
+
+/// Place `count` cells from left to right
+fn row(area: Geometry, count: usize) -> Vec<Geometry> {
+    split(area.size.w, count)
+        .into_iter()
+        .map(|(offset, width)| {
+                 Geometry {
+                     origin: Point {
+                         x: area.origin.x + offset as i32,
+                         y: area.origin.y,
+                     },
+                     size: Size {
+                         w: width,
+                         h: area.size.h,
+                     },
+                 }
+             })
+        .collect()
+}
+
+/// Split an area at `ratio` of its width or height
+fn split_ratio(area: Geometry, ratio: f32, horizontal: bool) -> (Geometry, Geometry) {
+    let ratio = ratio.max(0.05).min(0.95);
+    let mut first = area;
+    let mut second = area;
+    if horizontal {
+        first.size.w = (area.size.w as f32 * ratio) as u32;
+        second.origin.x += first.size.w as i32;
+        second.size.w -= first.size.w;
+    } else {
+        first.size.h = (area.size.h as f32 * ratio) as u32;
+        second.origin.y += first.size.h as i32;
+        second.size.h -= first.size.h;
+    }
+    (first, second)
+}
+
+/// Master views on the left, all others stacked on the right
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MasterStack {
+    /// Share of the width used by the master views
+    pub ratio: f32,
+    /// Number of master views
+    pub masters: usize,
+    /// Gaps between the views
+    pub gaps: Gaps,
+}
+
+impl Default for MasterStack {
+    fn default() -> MasterStack {
+        MasterStack {
+            ratio: 0.5,
+            masters: 1,
+            gaps: Gaps::default(),
+        }
+    }
+}
+
+impl Layout for MasterStack {
+    fn arrange(&self, area: Geometry, count: usize) -> Vec<Geometry> {
+        let area = self.gaps.shrink(area);
+        let cells = if self.masters == 0 || count <= self.masters {
+            column(area, count)
+        } else {
+            let (masters, stack) = split_ratio(area, self.ratio, true);
+            let mut cells = column(masters, self.masters);
+            cells.extend(column(stack, count - self.masters));
+            cells
+        };
+        self.gaps.apply(area, cells)
+    }
+}
+
+/// All views in a grid of (almost) equal sized cells
+///
+/// The last row is stretched, if it is not full.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Grid {
+    /// Gaps between the views
+    pub gaps: Gaps,
+}
+
+impl Layout for Grid {
+    fn arrange(&self, area: Geometry, count: usize) -> Vec<Geometry> {
+        if count == 0 {
+            return Vec::new();
+        }
+
+        let area = self.gaps.shrink(area);
+        let columns = (count as f64).sqrt().ceil() as usize;
+        let rows = (count + columns - 1) / columns;
+
+        let cells = column(area, rows)
+            .into_iter()
+            .enumerate()
+            .flat_map(|(i, line)| row(line, cmp::min(columns, count - i * columns)))
+            .collect();
+        self.gaps.apply(area, cells)
+    }
+}
+
+/// All views side by side in equal sized columns
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Columns {
+    /// Gaps between the views
+    pub gaps: Gaps,
+}
+
+impl Layout for Columns {
+    fn arrange(&self, area: Geometry, count: usize) -> Vec<Geometry> {
+        let area = self.gaps.shrink(area);
+        self.gaps.apply(area, row(area, count))
+    }
+}
+
+/// Every view covers the whole area
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Monocle {
+    /// Gaps around the views, `inner` is unused
+    pub gaps: Gaps,
+}
+
+impl Layout for Monocle {
+    fn arrange(&self, area: Geometry, count: usize) -> Vec<Geometry> {
+        vec![self.gaps.shrink(area); count]
+    }
+}
+
+/// Every view takes `ratio` of the remaining area, alternating between
+/// horizontal and vertical splits
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Spiral {
+    /// Share of the remaining area used by each view
+    pub ratio: f32,
+    /// Gaps between the views
+    pub gaps: Gaps,
+}
+
+impl Default for Spiral {
+    fn default() -> Spiral {
+        Spiral {
+            ratio: 0.5,
+            gaps: Gaps::default(),
+        }
+    }
+}
+
+impl Layout for Spiral {
+    fn arrange(&self, area: Geometry, count: usize) -> Vec<Geometry> {
+        let area = self.gaps.shrink(area);
+        let mut remaining = area;
+        let mut cells = Vec::with_capacity(count);
+        for i in 0..count {
+            if i + 1 == count {
+                cells.push(remaining);
+            } else {
+                let (cell, rest) = split_ratio(remaining, self.ratio, i % 2 == 0);
+                cells.push(cell);
+                remaining = rest;
+            }
+        }
+        self.gaps.apply(area, cells)
+    }
+}
+
+/// Check if a view should be tiled
+///
+/// Views with a type (e.g. popups or splash screens), a parent or a
+/// positioner are left floating.
+pub fn is_tiled(view: &View) -> bool {
+    view.view_type().is_empty() && view.parent().is_none() && view.positioner().is_none()
+}
+
+/// Applies `Layout`s to the views of all outputs
+pub struct LayoutEngine {
+    default: Box<Layout>,
+    layouts: HashMap<WeakOutput, Box<Layout>>,
+    areas: HashMap<WeakOutput, Geometry>,
+    /// Views excluded from tiling, although `is_tiled` says otherwise
+    floating: HashSet<WeakView>,
+}
+
+impl LayoutEngine {
+    /// Create a new engine using the given layout for all outputs
+    pub fn new<L: Layout + 'static>(layout: L) -> LayoutEngine {
+        LayoutEngine {
+            default: Box::new(layout),
+            layouts: HashMap::new(),
+            areas: HashMap::new(),
+            floating: HashSet::new(),
+        }
+    }
+
+    /// Use a different layout for the given output and rearrange it
+    pub fn set_layout<L: Layout + 'static>(&mut self, output: &Output, layout: L) {
+        self.layouts.insert(output.weak_reference(), Box::new(layout));
+        self.relayout(output);
+    }
+
+    /// Restrict the tiled views of the output to an area, e.g. the usable area
+    /// of `wayland::layer_shell::LayerShell`
+    ///
+    /// By default the whole virtual resolution is used.
+    pub fn set_usable_area(&mut self, output: &Output, area: Geometry) {
+        self.areas.insert(output.weak_reference(), area);
+        self.relayout(output);
+    }
+
+    /// Usable area of the output
+    pub fn usable_area(&self, output: &Output) -> Geometry {
+        self.areas.get(&output.weak_reference()).cloned().unwrap_or_else(|| {
+            Geometry {
+                origin: Point { x: 0, y: 0 },
+                size: output.virtual_resolution(),
+            }
+        })
+    }
+
+    /// Exclude a view from tiling or tile it again and rearrange its output
+    ///
+    /// Floating views keep their geometry, e.g. views matching a floating
+    /// window rule or a summoned scratchpad.
+    pub fn set_floating(&mut self, view: &View, floating: bool) {
+        if floating {
+            self.floating.insert(view.weak_reference());
+        } else {
+            self.floating.remove(&view.weak_reference());
+        }
+        self.relayout(view.output());
+    }
+
+    /// Check if a view was excluded from tiling by `set_floating`
+    pub fn is_floating(&self, view: &View) -> bool {
+        self.floating.contains(&view.weak_reference())
+    }
+
+    /// Check if the engine sets the geometry of a view
+    ///
+    /// These are views, which `is_tiled`, that are neither floating nor
+    /// fullscreen or maximised.
+    pub fn tiles(&self, view: &View) -> bool {
+        is_tiled(view) && !self.is_floating(view) &&
+        !view.state().intersects(ViewState::Fullscreen | ViewState::Maximized)
+    }
+
+    /// Compute the geometries of the given views on the output
+    pub fn arrange(&self, output: &Output, views: &[&View]) -> Vec<Geometry> {
+        let layout = self.layouts.get(&output.weak_reference()).unwrap_or(&self.default);
+        layout.arrange(self.usable_area(output), views.len())
+    }
+
+    /// Arrange all tiled views of the output
+    pub fn relayout(&self, output: &Output) {
+        self.relayout_without(output, None)
+    }
+
+    fn relayout_without(&self, output: &Output, removed: Option<&View>) {
+        let views: Vec<&View> = output.views()
+            .into_iter()
+            .filter(|view| {
+                self.tiles(view) && view.visibility().intersects(output.visibility()) &&
+                removed.map(|removed| removed != *view).unwrap_or(true)
+            })
+            .collect();
+        for (view, geometry) in views.iter().zip(self.arrange(output, &views)) {
+            view.set_geometry(ResizeEdge::Null, geometry);
+        }
+    }
+
+    /// Forward of `Callback::output_resolution`
+    pub fn output_resolution(&mut self, output: &Output, _from: Size, _to: Size) {
+        self.relayout(output);
+    }
+
+    /// Forward of `Callback::output_destroyed`
+    pub fn output_destroyed(&mut self, output: &Output) {
+        self.layouts.remove(&output.weak_reference());
+        self.areas.remove(&output.weak_reference());
+    }
+
+    /// Forward of `Callback::view_created`
+    pub fn view_created(&mut self, view: &View) {
+        self.relayout(view.output());
+    }
+
+    /// Forward of `Callback::view_destroyed`
+    pub fn view_destroyed(&mut self, view: &View) {
+        self.floating.remove(&view.weak_reference());
+        self.relayout_without(view.output(), Some(view));
+    }
+
+    /// Forward of `Callback::view_move_to_output`
+    pub fn view_move_to_output(&mut self, _view: &View, from: &Output, to: &Output) {
+        self.relayout(from);
+        self.relayout(to);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_util::geometry;
+
+    #[test]
+    fn test_split_distributes_remainder() {
+        assert_eq!(split(10, 3), vec![(0, 4), (4, 3), (7, 3)]);
+        assert_eq!(split(10, 0), vec![]);
+    }
+
+    #[test]
+    fn test_master_stack() {
+        let layout = MasterStack::default();
+        let area = geometry(0, 0, 1000, 600);
+        assert_eq!(layout.arrange(area, 1), vec![area]);
+        assert_eq!(layout.arrange(area, 3),
+                   vec![geometry(0, 0, 500, 600), geometry(500, 0, 500, 300), geometry(500, 300, 500, 300)]);
+    }
+
+    #[test]
+    fn test_master_stack_gaps() {
+        let layout = MasterStack {
+            ratio: 0.5,
+            masters: 1,
+            gaps: Gaps {
+                inner: 10,
+                outer: 20,
+            },
+        };
+        assert_eq!(layout.arrange(geometry(0, 0, 1040, 640), 2),
+                   vec![geometry(20, 20, 495, 600), geometry(525, 20, 495, 600)]);
+    }
+
+    #[test]
+    fn test_grid_stretches_last_row() {
+        let layout = Grid::default();
+        assert_eq!(layout.arrange(geometry(0, 0, 900, 600), 5),
+                   vec![geometry(0, 0, 300, 300),
+                        geometry(300, 0, 300, 300),
+                        geometry(600, 0, 300, 300),
+                        geometry(0, 300, 450, 300),
+                        geometry(450, 300, 450, 300)]);
+    }
+
+    #[test]
+    fn test_columns_and_monocle() {
+        let area = geometry(0, 0, 900, 600);
+        assert_eq!(Columns::default().arrange(area, 3),
+                   vec![geometry(0, 0, 300, 600), geometry(300, 0, 300, 600), geometry(600, 0, 300, 600)]);
+        assert_eq!(Monocle::default().arrange(area, 2), vec![area, area]);
+    }
+
+    #[test]
+    fn test_spiral() {
+        let layout = Spiral::default();
+        assert_eq!(layout.arrange(geometry(0, 0, 800, 800), 3),
+                   vec![geometry(0, 0, 400, 800), geometry(400, 0, 400, 400), geometry(400, 400, 400, 400)]);
+    }
+
+    #[test]
+    fn test_layouts_return_count_geometries() {
+        let area = geometry(0, 0, 1920, 1080);
+        let layouts: Vec<Box<Layout>> = vec![Box::new(MasterStack::default()),
+                                             Box::new(Grid::default()),
+                                             Box::new(Columns::default()),
+                                             Box::new(Monocle::default()),
+                                             Box::new(Spiral::default())];
+        for layout in &layouts {
+            for count in 0..12 {
+                assert_eq!(layout.arrange(area, count).len(), count);
+            }
+        }
+    }
+}
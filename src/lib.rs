@@ -51,6 +51,7 @@ extern crate num_traits;
 #[macro_use]
 extern crate log;
 extern crate libc;
+extern crate regex;
 extern crate uinput_sys;
 extern crate xkbcommon;
 #[cfg(feature = "wayland")]
@@ -85,18 +86,38 @@ macro_rules! enum_serde {
 }
 
 mod output;
+mod positioner;
 mod view;
 mod types;
 mod userdata;
+#[cfg(test)]
+mod test_util;
 
 pub mod event_loop;
+pub mod focus;
 pub mod input;
+pub mod interactive;
+pub mod layout;
+pub mod placement;
+pub mod popups;
+pub mod requests;
+pub mod rules;
+pub mod scratchpad;
+pub mod selector;
+pub mod snapping;
+pub mod stacking;
+pub mod states;
+pub mod switcher;
+pub mod transients;
+pub mod tree;
+pub mod workspaces;
 #[cfg(feature = "wayland")]
 pub mod wayland;
 #[cfg(feature = "render")]
 pub mod render;
 
 pub use self::output::{Output, WeakOutput};
+pub use self::positioner::PositionerRules;
 use self::output::OUTPUTS;
 #[cfg(feature = "render")]
 use self::render::*;
@@ -0,0 +1,276 @@
+//! Placement of new floating views
+//!
+//! A `Strategy` picks the origin of a new view inside the usable area of an
+//! output: centred, under the pointer, cascaded from the top left corner or
+//! where it overlaps the least with existing views. The strategies are pure
+//! functions over `Geometry`, results are always moved inside the area.
+//!
+//! `place` applies a strategy to a view, views with a `Positioner` or a
+//! parent are placed according to these hints instead.
+
+use {Geometry, Point, ResizeEdge, Size, View};
+use input::pointer;
+
+use std::cmp;
+
+/// How new floating views are placed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub enum Strategy {
+    /// Centred on the area
+    Center,
+    /// Centred under the pointer
+    UnderPointer,
+    /// Shifted diagonally from the top left corner, until no view starts at
+    /// the same position
+    Cascade,
+    /// Where the least area of existing views is covered
+    MinimalOverlap,
+}
+
+/// Offset between cascaded views
+pub const CASCADE_STEP: i32 = 32;
+
+/// Move a geometry inside `area`, keeping its size
+///
+/// The origin is moved as little as possible, geometries larger than `area`
+/// start at its top left corner.
+pub fn fit(geometry: Geometry, area: Geometry) -> Geometry {
+    let clamp = |position: i32, length: u32, start: i32, area_length: u32| -> i32 {
+        let end = start + area_length as i32 - length as i32;
+        cmp::max(start, cmp::min(position, end))
+    };
+
+    Geometry {
+        origin: Point {
+            x: clamp(geometry.origin.x, geometry.size.w, area.origin.x, area.size.w),
+            y: clamp(geometry.origin.y, geometry.size.h, area.origin.y, area.size.h),
+        },
+        size: geometry.size,
+    }
+}
+
+/// Centre a view of `size` over `parent`
+///
+/// The result is moved into `area` as far as possible, so dialogs of views
+/// partly outside of the output stay reachable.
+pub fn centered(parent: Geometry, size: Size, area: Geometry) -> Geometry {
+    let origin = Point {
+        x: parent.origin.x + (parent.size.w as i32 - size.w as i32) / 2,
+        y: parent.origin.y + (parent.size.h as i32 - size.h as i32) / 2,
+    };
+    fit(Geometry {
+            origin: origin,
+            size: size,
+        },
+        area)
+}
+
+/// Centre a view of `size` on `area`
+pub fn center(size: Size, area: Geometry) -> Geometry {
+    centered(area, size, area)
+}
+
+/// Centre a view of `size` under the pointer
+pub fn under_pointer(size: Size, pointer: Point, area: Geometry) -> Geometry {
+    let origin = Point {
+        x: pointer.x - size.w as i32 / 2,
+        y: pointer.y - size.h as i32 / 2,
+    };
+    fit(Geometry {
+            origin: origin,
+            size: size,
+        },
+        area)
+}
+
+/// Cascade a view of `size` from the top left corner of `area`
+///
+/// The view is shifted by `CASCADE_STEP`, while another view starts at the
+/// same position, and wraps around to the top, when it would leave `area`.
+pub fn cascade(size: Size, area: Geometry, others: &[Geometry]) -> Geometry {
+    let max_x = area.origin.x + area.size.w as i32 - size.w as i32;
+    let max_y = area.origin.y + area.size.h as i32 - size.h as i32;
+    let mut origin = area.origin;
+    // every position is tried at most once
+    for _ in 0..others.len() {
+        if !others.iter().any(|other| other.origin == origin) {
+            break;
+        }
+        origin.x += CASCADE_STEP;
+        origin.y += CASCADE_STEP;
+        if origin.x > max_x || origin.y > max_y {
+            origin = area.origin;
+            break;
+        }
+    }
+    fit(Geometry {
+            origin: origin,
+            size: size,
+        },
+        area)
+}
+
+/// Area covered by both geometries
+fn overlap(a: &Geometry, b: &Geometry) -> u64 {
+    let left = cmp::max(a.origin.x, b.origin.x) as i64;
+    let right = cmp::min(a.origin.x + a.size.w as i32, b.origin.x + b.size.w as i32) as i64;
+    let top = cmp::max(a.origin.y, b.origin.y) as i64;
+    let bottom = cmp::min(a.origin.y + a.size.h as i32, b.origin.y + b.size.h as i32) as i64;
+    if right > left && bottom > top {
+        ((right - left) * (bottom - top)) as u64
+    } else {
+        0
+    }
+}
+
+/// Place a view of `size`, where it overlaps the least with `others`
+///
+/// Candidates are the corners of `area` and the positions next to the
+/// edges of other views. Ties are broken by preferring the top most, then
+/// left most position.
+pub fn minimal_overlap(size: Size, area: Geometry, others: &[Geometry]) -> Geometry {
+    let right = area.origin.x + area.size.w as i32 - size.w as i32;
+    let bottom = area.origin.y + area.size.h as i32 - size.h as i32;
+    let mut xs = vec![area.origin.x, right];
+    let mut ys = vec![area.origin.y, bottom];
+    for other in others {
+        xs.push(other.origin.x + other.size.w as i32);
+        xs.push(other.origin.x - size.w as i32);
+        ys.push(other.origin.y + other.size.h as i32);
+        ys.push(other.origin.y - size.h as i32);
+    }
+
+    let key = |geometry: &Geometry| (geometry.origin.y, geometry.origin.x);
+    let mut best: Option<(u64, Geometry)> = None;
+    for &y in &ys {
+        for &x in &xs {
+            let candidate = fit(Geometry {
+                                    origin: Point { x: x, y: y },
+                                    size: size,
+                                },
+                                area);
+            let covered: u64 = others.iter().map(|other| overlap(&candidate, other)).sum();
+            let better = match best {
+                None => true,
+                Some((best_covered, best_geometry)) => {
+                    covered < best_covered ||
+                    (covered == best_covered && key(&candidate) < key(&best_geometry))
+                }
+            };
+            if better {
+                best = Some((covered, candidate));
+            }
+        }
+    }
+    best.map(|(_, geometry)| geometry).unwrap_or_else(|| {
+        fit(Geometry {
+                origin: area.origin,
+                size: size,
+            },
+            area)
+    })
+}
+
+impl Strategy {
+    /// Geometry of a view of `size` inside `area` next to `others`
+    pub fn apply(&self, size: Size, area: Geometry, others: &[Geometry], pointer: Point) -> Geometry {
+        match *self {
+            Strategy::Center => center(size, area),
+            Strategy::UnderPointer => under_pointer(size, pointer, area),
+            Strategy::Cascade => cascade(size, area, others),
+            Strategy::MinimalOverlap => minimal_overlap(size, area, others),
+        }
+    }
+}
+
+/// Place a new floating view inside the usable `area` of its output
+///
+/// Views with a `Positioner` are placed by it, views with a parent are
+/// centred over it. Other views of the output are avoided by the strategy.
+pub fn place(strategy: Strategy, view: &View, area: Geometry) {
+    let size = view.geometry().size;
+    let geometry = match (view.positioner(), view.parent()) {
+        (Some(positioner), parent) => {
+            let mut rules = positioner.rules();
+            if rules.size.w == 0 || rules.size.h == 0 {
+                rules.size = size;
+            }
+            let parent_geometry = parent.map(|parent| parent.geometry()).unwrap_or(area);
+            rules.solve(parent_geometry, area)
+        }
+        (None, Some(parent)) => centered(parent.geometry(), size, area),
+        (None, None) => {
+            let others: Vec<Geometry> = view.output()
+                .views()
+                .into_iter()
+                .filter(|other| *other != view && other.visibility().intersects(view.visibility()))
+                .map(|other| other.geometry())
+                .collect();
+            strategy.apply(size, area, &others, pointer::position())
+        }
+    };
+    view.set_geometry(ResizeEdge::Null, geometry);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_util::geometry;
+
+    const SIZE: Size = Size { w: 400, h: 300 };
+
+    #[test]
+    fn test_fit() {
+        let area = geometry(0, 30, 1280, 690);
+        assert_eq!(fit(geometry(100, 100, 400, 300), area), geometry(100, 100, 400, 300));
+        // saved on a larger output
+        assert_eq!(fit(geometry(1800, 0, 400, 300), area), geometry(880, 30, 400, 300));
+        assert_eq!(fit(geometry(-50, 500, 2000, 300), area), geometry(0, 420, 2000, 300));
+    }
+
+    #[test]
+    fn test_centered() {
+        let area = geometry(0, 0, 1920, 1080);
+        assert_eq!(centered(geometry(100, 100, 800, 600), Size { w: 400, h: 200 }, area),
+                   geometry(300, 300, 400, 200));
+        // kept inside the output
+        assert_eq!(centered(geometry(1700, -100, 400, 200), Size { w: 600, h: 300 }, area),
+                   geometry(1320, 0, 600, 300));
+        // larger than the output
+        assert_eq!(centered(geometry(0, 0, 100, 100), Size { w: 2000, h: 100 }, area),
+                   geometry(0, 0, 2000, 100));
+    }
+
+    #[test]
+    fn test_center_and_pointer() {
+        let area = geometry(0, 30, 1000, 800);
+        assert_eq!(center(SIZE, area), geometry(300, 280, 400, 300));
+        assert_eq!(under_pointer(SIZE, Point { x: 500, y: 500 }, area),
+                   geometry(300, 350, 400, 300));
+        // kept inside the area
+        assert_eq!(under_pointer(SIZE, Point { x: 990, y: 0 }, area),
+                   geometry(600, 30, 400, 300));
+        assert_eq!(center(Size { w: 1200, h: 300 }, area), geometry(0, 280, 1200, 300));
+    }
+
+    #[test]
+    fn test_cascade() {
+        let area = geometry(0, 0, 1000, 800);
+        assert_eq!(cascade(SIZE, area, &[]), geometry(0, 0, 400, 300));
+        let others = [geometry(0, 0, 400, 300), geometry(32, 32, 400, 300)];
+        assert_eq!(cascade(SIZE, area, &others), geometry(64, 64, 400, 300));
+        assert_eq!(cascade(SIZE, area, &others[1..]), geometry(0, 0, 400, 300));
+    }
+
+    #[test]
+    fn test_minimal_overlap() {
+        let area = geometry(0, 0, 1000, 800);
+        assert_eq!(minimal_overlap(SIZE, area, &[]), geometry(0, 0, 400, 300));
+        let others = [geometry(0, 0, 500, 400)];
+        assert_eq!(minimal_overlap(SIZE, area, &others), geometry(500, 0, 400, 300));
+        // no free space, the smallest overlap wins
+        let others = [geometry(0, 0, 1000, 500), geometry(0, 500, 800, 300)];
+        assert_eq!(minimal_overlap(SIZE, area, &others), geometry(600, 500, 400, 300));
+    }
+}
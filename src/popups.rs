@@ -0,0 +1,186 @@
+//! Popup grabs
+//!
+//! Popups (`ViewType::Popup`) opened from another popup form a chain, e.g. a
+//! menu with its submenus, that belongs to the view the first popup was
+//! opened from. While a chain is open it holds a grab: the topmost popup
+//! keeps the keyboard focus and a click outside of all popups of the chain
+//! dismisses it, closing the popups outermost first.
+//!
+//! Forward `view_created`, `view_destroyed`, `view_focus` and
+//! `pointer_button`.
+
+use {ButtonState, Geometry, Point, View, ViewType, WeakView};
+
+fn contains(geometry: &Geometry, point: Point) -> bool {
+    point.x >= geometry.origin.x && point.y >= geometry.origin.y &&
+    point.x < geometry.origin.x + geometry.size.w as i32 &&
+    point.y < geometry.origin.y + geometry.size.h as i32
+}
+
+/// Check if the point lies inside any of the geometries
+fn union_contains(geometries: &[Geometry], point: Point) -> bool {
+    geometries.iter().any(|geometry| contains(geometry, point))
+}
+
+fn is_popup(view: &View) -> bool {
+    view.view_type().contains(ViewType::Popup)
+}
+
+/// Tracks the open popup chain and its grab
+#[derive(Default)]
+pub struct PopupManager {
+    owner: Option<WeakView>,
+    chain: Vec<WeakView>,
+}
+
+impl PopupManager {
+    /// Create a new manager without any open popups
+    pub fn new() -> PopupManager {
+        PopupManager::default()
+    }
+
+    /// Check if a popup chain holds the grab
+    pub fn is_grabbed(&self) -> bool {
+        !self.chain.is_empty()
+    }
+
+    /// Popups of the open chain, outermost last
+    pub fn chain(&self) -> &[WeakView] {
+        &self.chain
+    }
+
+    /// View the open chain belongs to
+    pub fn owner(&self) -> Option<WeakView> {
+        self.owner.clone()
+    }
+
+    /// Topmost popup of the open chain, which holds the keyboard focus
+    pub fn topmost(&self) -> Option<WeakView> {
+        self.chain.last().cloned()
+    }
+
+    /// Check if a point lies inside any popup of the open chain
+    pub fn contains(&self, point: Point) -> bool {
+        let geometries: Vec<Geometry> =
+            self.chain.iter().filter_map(|popup| popup.run(|popup| popup.geometry())).collect();
+        union_contains(&geometries, point)
+    }
+
+    /// Close all popups of the chain after the given position, outermost
+    /// first
+    fn close_from(&mut self, position: usize) {
+        for popup in self.chain.drain(position..).rev() {
+            popup.run(|popup| popup.close());
+        }
+    }
+
+    /// Close the open chain, outermost popup first, and give the focus back
+    /// to its owner
+    pub fn dismiss(&mut self) {
+        self.close_from(0);
+        if let Some(owner) = self.owner.take() {
+            owner.run(|owner| owner.focus());
+        }
+    }
+
+    /// Forward of `Callback::view_created`
+    ///
+    /// Returns `true` if the view is a popup, that joined or started a chain
+    pub fn view_created(&mut self, view: &View) -> bool {
+        if !is_popup(view) {
+            return false;
+        }
+        let parent = match view.parent() {
+            Some(parent) => parent,
+            None => return false,
+        };
+
+        // a submenu closes the submenus opened after its parent
+        match self.chain.iter().position(|popup| *popup == *parent) {
+            Some(position) => self.close_from(position + 1),
+            None => {
+                self.dismiss();
+                let mut owner = parent;
+                while is_popup(owner) {
+                    match owner.parent() {
+                        Some(next) => owner = next,
+                        None => break,
+                    }
+                }
+                self.owner = Some(owner.weak_reference());
+            }
+        }
+
+        self.chain.push(view.weak_reference());
+        view.focus();
+        true
+    }
+
+    /// Forward of `Callback::view_destroyed`
+    ///
+    /// Popups opened from a destroyed popup are closed as well, the focus
+    /// moves to the next popup of the chain or its owner.
+    pub fn view_destroyed(&mut self, view: &View) {
+        let owner = self.owner.as_ref().map(|owner| *owner == *view).unwrap_or(false);
+        if owner {
+            self.owner = None;
+            self.close_from(0);
+            return;
+        }
+
+        if let Some(position) = self.chain.iter().position(|popup| *popup == *view) {
+            self.chain.remove(position);
+            self.close_from(position);
+            let next = self.chain.last().or_else(|| self.owner.as_ref()).cloned();
+            if let Some(next) = next {
+                next.run(|next| next.focus());
+            }
+            if self.chain.is_empty() {
+                self.owner = None;
+            }
+        }
+    }
+
+    /// Forward of `Callback::view_focus`
+    ///
+    /// Keeps the keyboard focus on the topmost popup while the grab is active
+    pub fn view_focus(&mut self, view: &View, focus: bool) {
+        if !focus {
+            return;
+        }
+        if let Some(topmost) = self.topmost() {
+            if topmost != *view {
+                topmost.run(|topmost| topmost.focus());
+            }
+        }
+    }
+
+    /// Forward of `Callback::pointer_button`
+    ///
+    /// A press outside of the chain dismisses it. Returns `true` if the
+    /// event was consumed by dismissing.
+    pub fn pointer_button(&mut self, state: ButtonState, origin: Point) -> bool {
+        if state != ButtonState::Pressed || !self.is_grabbed() || self.contains(origin) {
+            return false;
+        }
+        self.dismiss();
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_util::geometry;
+
+    #[test]
+    fn test_union_contains() {
+        let menu = geometry(0, 0, 100, 200);
+        let submenu = geometry(100, 50, 100, 100);
+        let chain = [menu, submenu];
+        assert!(union_contains(&chain, Point { x: 50, y: 150 }));
+        assert!(union_contains(&chain, Point { x: 150, y: 60 }));
+        assert!(!union_contains(&chain, Point { x: 150, y: 160 }));
+        assert!(!union_contains(&[], Point { x: 0, y: 0 }));
+    }
+}
@@ -0,0 +1,347 @@
+//! Placement of popups following the rules of a `Positioner`
+
+use {Anchor, ConstraintAdjustment, Geometry, Gravity, Point, Positioner, Size};
+
+/// Rules of a `Positioner`, decoupled from the view they belong to
+///
+/// Obtained by `Positioner::rules` or constructed manually to compute
+/// placements without a view.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PositionerRules {
+    /// Edges of the anchor rectangle the anchor point is derived from
+    pub anchor: Anchor::Flags,
+    /// Rectangle relative to the parent's geometry the popup is placed at
+    pub anchor_rect: Geometry,
+    /// Direction the popup extends to from the anchor point
+    pub gravity: Gravity::Flags,
+    /// Offset added to the position derived from anchor and gravity
+    pub offset: Point,
+    /// Size of the popup
+    pub size: Size,
+    /// Adjustments allowed, if the popup would leave the constraint area
+    pub constraint_adjustment: ConstraintAdjustment::Flags,
+}
+
+/// Horizontal or vertical axis
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Axis {
+    X,
+    Y,
+}
+
+/// Start and length of a geometry along an axis
+fn span(geometry: &Geometry, axis: Axis) -> (i32, i32) {
+    match axis {
+        Axis::X => (geometry.origin.x, geometry.size.w as i32),
+        Axis::Y => (geometry.origin.y, geometry.size.h as i32),
+    }
+}
+
+fn set_span(geometry: &mut Geometry, axis: Axis, start: i32, length: i32) {
+    match axis {
+        Axis::X => {
+            geometry.origin.x = start;
+            geometry.size.w = length as u32;
+        }
+        Axis::Y => {
+            geometry.origin.y = start;
+            geometry.size.h = length as u32;
+        }
+    }
+}
+
+/// Check if the geometry leaves the area along an axis
+fn is_constrained(geometry: &Geometry, area: &Geometry, axis: Axis) -> bool {
+    let (start, length) = span(geometry, axis);
+    let (area_start, area_length) = span(area, axis);
+    start < area_start || start + length > area_start + area_length
+}
+
+/// Start of the popup along an axis
+///
+/// `anchor` and `gravity` tell, if the start and end edge of the axis were
+/// specified.
+fn align(rect_start: i32, rect_length: i32, anchor: (bool, bool), gravity: (bool, bool), length: i32)
+         -> i32 {
+    let point = match anchor {
+        (true, false) => rect_start,
+        (false, true) => rect_start + rect_length,
+        _ => rect_start + rect_length / 2,
+    };
+    match gravity {
+        (true, false) => point - length,
+        (false, true) => point,
+        _ => point - length / 2,
+    }
+}
+
+impl PositionerRules {
+    /// Geometry without any constraint adjustment
+    fn place(&self, anchor: Anchor::Flags, gravity: Gravity::Flags, parent: Point) -> Geometry {
+        let x = align(self.anchor_rect.origin.x,
+                      self.anchor_rect.size.w as i32,
+                      (anchor.contains(Anchor::Left), anchor.contains(Anchor::Right)),
+                      (gravity.contains(Gravity::Left), gravity.contains(Gravity::Right)),
+                      self.size.w as i32);
+        let y = align(self.anchor_rect.origin.y,
+                      self.anchor_rect.size.h as i32,
+                      (anchor.contains(Anchor::Top), anchor.contains(Anchor::Bottom)),
+                      (gravity.contains(Gravity::Top), gravity.contains(Gravity::Bottom)),
+                      self.size.h as i32);
+
+        Geometry {
+            origin: Point {
+                x: parent.x + x + self.offset.x,
+                y: parent.y + y + self.offset.y,
+            },
+            size: self.size,
+        }
+    }
+
+    /// Compute the geometry of the popup
+    ///
+    /// `parent_geometry` is the geometry of the parent view, the anchor
+    /// rectangle is relative to it. If the popup would leave the
+    /// `constraint_area` (e.g. the usable area of the output), the allowed
+    /// adjustments are tried in the order of the protocol: flip, then slide,
+    /// then resize. Each axis is adjusted independently.
+    pub fn solve(&self, parent_geometry: Geometry, constraint_area: Geometry) -> Geometry {
+        let parent = parent_geometry.origin;
+        let mut geometry = self.place(self.anchor, self.gravity, parent);
+
+        // flip, unless the flipped geometry is constrained as well
+        let adjustment = self.constraint_adjustment;
+        if adjustment.contains(ConstraintAdjustment::FlipX) &&
+           is_constrained(&geometry, &constraint_area, Axis::X) {
+            let flipped = self.place(flip_anchor(self.anchor, Anchor::Left | Anchor::Right),
+                                     flip_gravity(self.gravity, Gravity::Left | Gravity::Right),
+                                     parent);
+            if !is_constrained(&flipped, &constraint_area, Axis::X) {
+                geometry.origin.x = flipped.origin.x;
+            }
+        }
+        if adjustment.contains(ConstraintAdjustment::FlipY) &&
+           is_constrained(&geometry, &constraint_area, Axis::Y) {
+            let flipped = self.place(flip_anchor(self.anchor, Anchor::Top | Anchor::Bottom),
+                                     flip_gravity(self.gravity, Gravity::Top | Gravity::Bottom),
+                                     parent);
+            if !is_constrained(&flipped, &constraint_area, Axis::Y) {
+                geometry.origin.y = flipped.origin.y;
+            }
+        }
+
+        let axes = [(Axis::X, ConstraintAdjustment::SlideX, ConstraintAdjustment::ResizeX),
+                    (Axis::Y, ConstraintAdjustment::SlideY, ConstraintAdjustment::ResizeY)];
+        for &(axis, slide, resize) in &axes {
+            let (area_start, area_length) = span(&constraint_area, axis);
+            let area_end = area_start + area_length;
+
+            if adjustment.contains(slide) && is_constrained(&geometry, &constraint_area, axis) {
+                // slide back in, keeping the start edge visible if too large
+                let (mut start, length) = span(&geometry, axis);
+                if start + length > area_end {
+                    start = area_end - length;
+                }
+                if start < area_start {
+                    start = area_start;
+                }
+                set_span(&mut geometry, axis, start, length);
+            }
+
+            if adjustment.contains(resize) && is_constrained(&geometry, &constraint_area, axis) {
+                let (start, length) = span(&geometry, axis);
+                let clipped_start = start.max(area_start);
+                let clipped_end = (start + length).min(area_end);
+                if clipped_end > clipped_start {
+                    set_span(&mut geometry, axis, clipped_start, clipped_end - clipped_start);
+                }
+            }
+        }
+
+        geometry
+    }
+}
+
+/// Swap the given pair of opposite anchors
+fn flip_anchor(anchor: Anchor::Flags, pair: Anchor::Flags) -> Anchor::Flags {
+    if (anchor & pair).is_empty() || anchor.contains(pair) {
+        anchor
+    } else {
+        anchor ^ pair
+    }
+}
+
+/// Swap the given pair of opposite gravities
+fn flip_gravity(gravity: Gravity::Flags, pair: Gravity::Flags) -> Gravity::Flags {
+    if (gravity & pair).is_empty() || gravity.contains(pair) {
+        gravity
+    } else {
+        gravity ^ pair
+    }
+}
+
+impl Positioner {
+    /// Get all rules of the `Positioner`
+    pub fn rules(&self) -> PositionerRules {
+        PositionerRules {
+            anchor: self.anchor(),
+            anchor_rect: self.anchor_rect(),
+            gravity: self.gravity(),
+            offset: self.offset(),
+            size: self.size(),
+            constraint_adjustment: self.constraint_adjustment(),
+        }
+    }
+
+    /// Compute the geometry of the popup, see `PositionerRules::solve`
+    pub fn solve(&self, parent_geometry: Geometry, constraint_area: Geometry) -> Geometry {
+        self.rules().solve(parent_geometry, constraint_area)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_util::geometry;
+
+    /// Popup of 100x50 anchored to a 20x10 rectangle at (40, 20) of a
+    /// parent at (100, 100)
+    fn rules(anchor: Anchor::Flags, gravity: Gravity::Flags) -> PositionerRules {
+        PositionerRules {
+            anchor: anchor,
+            anchor_rect: geometry(40, 20, 20, 10),
+            gravity: gravity,
+            offset: Point { x: 0, y: 0 },
+            size: Size { w: 100, h: 50 },
+            constraint_adjustment: ConstraintAdjustment::Null,
+        }
+    }
+
+    fn parent() -> Geometry {
+        geometry(100, 100, 400, 300)
+    }
+
+    fn output() -> Geometry {
+        geometry(0, 0, 1000, 800)
+    }
+
+    fn origin(rules: PositionerRules) -> Point {
+        rules.solve(parent(), output()).origin
+    }
+
+    #[test]
+    fn test_anchor_points() {
+        // gravity bottom right places the popup's top left corner at the anchor point
+        let gravity = Gravity::Bottom | Gravity::Right;
+        let cases = [(Anchor::Null, Point { x: 150, y: 125 }),
+                     (Anchor::Top, Point { x: 150, y: 120 }),
+                     (Anchor::Bottom, Point { x: 150, y: 130 }),
+                     (Anchor::Left, Point { x: 140, y: 125 }),
+                     (Anchor::Right, Point { x: 160, y: 125 }),
+                     (Anchor::Top | Anchor::Left, Point { x: 140, y: 120 }),
+                     (Anchor::Top | Anchor::Right, Point { x: 160, y: 120 }),
+                     (Anchor::Bottom | Anchor::Left, Point { x: 140, y: 130 }),
+                     (Anchor::Bottom | Anchor::Right, Point { x: 160, y: 130 })];
+        for &(anchor, expected) in &cases {
+            assert_eq!(origin(rules(anchor, gravity)), expected, "anchor {:?}", anchor);
+        }
+    }
+
+    #[test]
+    fn test_gravity() {
+        // anchor point at the center of the anchor rect, (150, 125)
+        let cases = [(Gravity::Null, Point { x: 100, y: 100 }),
+                     (Gravity::Top, Point { x: 100, y: 75 }),
+                     (Gravity::Bottom, Point { x: 100, y: 125 }),
+                     (Gravity::Left, Point { x: 50, y: 100 }),
+                     (Gravity::Right, Point { x: 150, y: 100 }),
+                     (Gravity::Top | Gravity::Left, Point { x: 50, y: 75 }),
+                     (Gravity::Top | Gravity::Right, Point { x: 150, y: 75 }),
+                     (Gravity::Bottom | Gravity::Left, Point { x: 50, y: 125 }),
+                     (Gravity::Bottom | Gravity::Right, Point { x: 150, y: 125 })];
+        for &(gravity, expected) in &cases {
+            assert_eq!(origin(rules(Anchor::Null, gravity)), expected, "gravity {:?}", gravity);
+        }
+    }
+
+    #[test]
+    fn test_offset_and_size() {
+        let mut rules = rules(Anchor::Bottom | Anchor::Left, Gravity::Bottom | Gravity::Right);
+        rules.offset = Point { x: -5, y: 3 };
+        assert_eq!(rules.solve(parent(), output()), geometry(135, 133, 100, 50));
+    }
+
+    #[test]
+    fn test_constrained_without_adjustment() {
+        let rules = rules(Anchor::Right, Gravity::Right);
+        let area = geometry(0, 0, 200, 800);
+        assert_eq!(rules.solve(parent(), area), geometry(160, 100, 100, 50));
+    }
+
+    #[test]
+    fn test_flip_x() {
+        let mut rules = rules(Anchor::Right, Gravity::Right);
+        rules.constraint_adjustment = ConstraintAdjustment::FlipX;
+        // flipped to the left of the anchor rect: 140 - 100
+        assert_eq!(rules.solve(parent(), geometry(0, 0, 200, 800)), geometry(40, 100, 100, 50));
+        // flipped would be constrained as well, the original position is kept
+        assert_eq!(rules.solve(parent(), geometry(100, 0, 100, 800)), geometry(160, 100, 100, 50));
+        // unconstrained popups are not flipped
+        assert_eq!(rules.solve(parent(), output()), geometry(160, 100, 100, 50));
+    }
+
+    #[test]
+    fn test_flip_y() {
+        let mut rules = rules(Anchor::Bottom, Gravity::Bottom);
+        rules.constraint_adjustment = ConstraintAdjustment::FlipY;
+        // flipped above the anchor rect: 120 - 50
+        assert_eq!(rules.solve(parent(), geometry(0, 0, 1000, 150)), geometry(100, 70, 100, 50));
+    }
+
+    #[test]
+    fn test_flip_centered_axis_is_unchanged() {
+        let mut rules = rules(Anchor::Null, Gravity::Null);
+        rules.constraint_adjustment = ConstraintAdjustment::FlipX;
+        let area = geometry(120, 0, 1000, 800);
+        assert_eq!(rules.solve(parent(), area), geometry(100, 100, 100, 50));
+    }
+
+    #[test]
+    fn test_slide() {
+        let mut rules = rules(Anchor::Right, Gravity::Right);
+        rules.constraint_adjustment = ConstraintAdjustment::SlideX | ConstraintAdjustment::SlideY;
+        // slides left until the right edge fits
+        assert_eq!(rules.solve(parent(), geometry(0, 0, 200, 800)), geometry(100, 100, 100, 50));
+        // slides right until the left edge fits
+        let mut rules = rules;
+        rules.anchor = Anchor::Left;
+        rules.gravity = Gravity::Left;
+        assert_eq!(rules.solve(parent(), geometry(60, 0, 1000, 800)), geometry(60, 100, 100, 50));
+        // too large, the left edge stays visible
+        assert_eq!(rules.solve(parent(), geometry(60, 0, 50, 800)), geometry(60, 100, 100, 50));
+        // vertically
+        assert_eq!(rules.solve(parent(), geometry(0, 0, 1000, 120)), geometry(40, 70, 100, 50));
+    }
+
+    #[test]
+    fn test_resize() {
+        let mut rules = rules(Anchor::Right, Gravity::Right);
+        rules.constraint_adjustment = ConstraintAdjustment::ResizeX | ConstraintAdjustment::ResizeY;
+        assert_eq!(rules.solve(parent(), geometry(0, 0, 200, 130)), geometry(160, 100, 40, 30));
+        // the popup would have no size left
+        assert_eq!(rules.solve(parent(), geometry(0, 0, 150, 800)), geometry(160, 100, 100, 50));
+    }
+
+    #[test]
+    fn test_adjustment_precedence() {
+        let mut rules = rules(Anchor::Right, Gravity::Right);
+        rules.constraint_adjustment = ConstraintAdjustment::FlipX | ConstraintAdjustment::SlideX |
+                                      ConstraintAdjustment::ResizeX;
+        // flipping suffices
+        assert_eq!(rules.solve(parent(), geometry(0, 0, 200, 800)), geometry(40, 100, 100, 50));
+        // flipping fails, sliding suffices
+        assert_eq!(rules.solve(parent(), geometry(100, 0, 150, 800)), geometry(150, 100, 100, 50));
+        // only resizing after sliding fits
+        assert_eq!(rules.solve(parent(), geometry(100, 0, 80, 800)), geometry(100, 100, 80, 50));
+    }
+}
@@ -0,0 +1,318 @@
+//! Arbitration of client requests
+//!
+//! Clients request new geometries, interactive moves and resizes and state
+//! changes of their views. A `RequestPolicy` decides per `ViewClass`, if a
+//! request is accepted, clamped to the output, rejected or deferred until
+//! the compositor is ready to handle it. Single views may override the
+//! decisions of their class.
+//!
+//! Geometry requests are applied directly. For moves, resizes and state
+//! changes the `Decision` is returned, forward accepted ones to e.g.
+//! `Interactive` or `States`. Clamping only applies to geometries and is
+//! treated like accepting for other requests.
+//!
+//! Forward `view_request_geometry`, `view_request_move`,
+//! `view_request_resize`, `view_request_state` and `view_destroyed`. The
+//! request forwards take the `LayoutEngine` to tell tiled and floating views
+//! apart.
+
+use {Geometry, Point, ResizeEdge, Size, View, ViewState, ViewType, WeakView};
+use layout::{self, LayoutEngine};
+use placement;
+
+use std::cmp;
+use std::collections::HashMap;
+
+/// Class of a view, requests are decided by
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub enum ViewClass {
+    /// Views placed freely
+    Floating,
+    /// Views placed by a `Layout`
+    Tiled,
+    /// Popups and other views with a positioner
+    Popup,
+    /// Unmanaged and override redirect views
+    Unmanaged,
+}
+
+impl ViewClass {
+    /// Class of a view
+    ///
+    /// Views are tiled, if `layout::is_tiled` says so and they were not made
+    /// floating with `LayoutEngine::set_floating`.
+    pub fn of(view: &View, layout: &LayoutEngine) -> ViewClass {
+        let view_type = view.view_type();
+        if view_type.intersects(ViewType::Unmanaged | ViewType::OverrideRedirect) {
+            ViewClass::Unmanaged
+        } else if view_type.contains(ViewType::Popup) || view.positioner().is_some() {
+            ViewClass::Popup
+        } else if layout::is_tiled(view) && !layout.is_floating(view) {
+            ViewClass::Tiled
+        } else {
+            ViewClass::Floating
+        }
+    }
+}
+
+/// How a request is handled
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub enum Decision {
+    /// Apply the request as is
+    Accept,
+    /// Apply the request, but keep the view inside its output
+    Clamp,
+    /// Ignore the request
+    Reject,
+    /// Keep the request, until it is taken with `RequestPolicy::take_deferred`
+    Defer,
+}
+
+/// Decisions for every kind of request
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub struct Decisions {
+    /// Requested geometries
+    pub geometry: Decision,
+    /// Requested interactive moves
+    pub moving: Decision,
+    /// Requested interactive resizes
+    pub resizing: Decision,
+    /// Requested state changes
+    pub state: Decision,
+}
+
+impl Decisions {
+    /// Same decision for all requests
+    pub fn all(decision: Decision) -> Decisions {
+        Decisions {
+            geometry: decision,
+            moving: decision,
+            resizing: decision,
+            state: decision,
+        }
+    }
+}
+
+/// A request of a client
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub enum Request {
+    /// See `Callback::view_request_geometry`
+    Geometry(Geometry),
+    /// See `Callback::view_request_move`
+    Move(Point),
+    /// See `Callback::view_request_resize`
+    Resize(ResizeEdge::Flags, Point),
+    /// See `Callback::view_request_state`
+    State(ViewState::Flags, bool),
+}
+
+/// Shrink a geometry to fit into `area` and move it inside
+pub fn clamp_to(geometry: Geometry, area: Geometry) -> Geometry {
+    let size = Size {
+        w: cmp::min(geometry.size.w, area.size.w),
+        h: cmp::min(geometry.size.h, area.size.h),
+    };
+    placement::fit(Geometry {
+                       origin: geometry.origin,
+                       size: size,
+                   },
+                   area)
+}
+
+/// Decides client requests per `ViewClass`
+pub struct RequestPolicy {
+    floating: Decisions,
+    tiled: Decisions,
+    popup: Decisions,
+    unmanaged: Decisions,
+    overrides: HashMap<WeakView, Decisions>,
+    deferred: Vec<(WeakView, Request)>,
+}
+
+impl Default for RequestPolicy {
+    fn default() -> RequestPolicy {
+        RequestPolicy::tiling()
+    }
+}
+
+impl RequestPolicy {
+    /// Create a new policy with the given decisions per class
+    pub fn new(floating: Decisions, tiled: Decisions, popup: Decisions, unmanaged: Decisions)
+               -> RequestPolicy {
+        RequestPolicy {
+            floating: floating,
+            tiled: tiled,
+            popup: popup,
+            unmanaged: unmanaged,
+            overrides: HashMap::new(),
+            deferred: Vec::new(),
+        }
+    }
+
+    /// Accept every request
+    pub fn permissive() -> RequestPolicy {
+        let accept = Decisions::all(Decision::Accept);
+        RequestPolicy::new(accept, accept, accept, accept)
+    }
+
+    /// Preset for floating compositors
+    ///
+    /// Geometries of floating views and popups are clamped to the output,
+    /// unmanaged views are left alone.
+    pub fn floating() -> RequestPolicy {
+        let clamp = Decisions {
+            geometry: Decision::Clamp,
+            ..Decisions::all(Decision::Accept)
+        };
+        RequestPolicy::new(clamp, clamp, clamp, Decisions::all(Decision::Accept))
+    }
+
+    /// Preset for tiling compositors, this is the default
+    ///
+    /// Tiled views may only change their state, the layout decides about
+    /// their geometry. Other views are handled like in `floating`.
+    pub fn tiling() -> RequestPolicy {
+        let mut policy = RequestPolicy::floating();
+        policy.tiled = Decisions {
+            state: Decision::Accept,
+            ..Decisions::all(Decision::Reject)
+        };
+        policy
+    }
+
+    /// Decisions for a class
+    pub fn decisions(&self, class: ViewClass) -> Decisions {
+        match class {
+            ViewClass::Floating => self.floating,
+            ViewClass::Tiled => self.tiled,
+            ViewClass::Popup => self.popup,
+            ViewClass::Unmanaged => self.unmanaged,
+        }
+    }
+
+    /// Change the decisions for a class
+    pub fn set_decisions(&mut self, class: ViewClass, decisions: Decisions) {
+        match class {
+            ViewClass::Floating => self.floating = decisions,
+            ViewClass::Tiled => self.tiled = decisions,
+            ViewClass::Popup => self.popup = decisions,
+            ViewClass::Unmanaged => self.unmanaged = decisions,
+        }
+    }
+
+    /// Override the decisions of its class for a single view, `None`
+    /// removes the override
+    pub fn set_override(&mut self, view: &View, decisions: Option<Decisions>) {
+        match decisions {
+            Some(decisions) => {
+                self.overrides.insert(view.weak_reference(), decisions);
+            }
+            None => {
+                self.overrides.remove(&view.weak_reference());
+            }
+        }
+    }
+
+    /// Decisions for a view, considering overrides
+    pub fn decisions_for(&self, view: &View, layout: &LayoutEngine) -> Decisions {
+        match self.overrides.get(&view.weak_reference()) {
+            Some(decisions) => *decisions,
+            None => self.decisions(ViewClass::of(view, layout)),
+        }
+    }
+
+    /// Take the deferred requests of a view, oldest first
+    pub fn take_deferred(&mut self, view: &View) -> Vec<Request> {
+        let (taken, kept): (Vec<_>, Vec<_>) =
+            self.deferred.drain(..).partition(|&(ref other, _)| *other == *view);
+        self.deferred = kept;
+        taken.into_iter().map(|(_, request)| request).collect()
+    }
+
+    fn defer(&mut self, view: &View, decision: Decision, request: Request) -> Decision {
+        if decision == Decision::Defer {
+            self.deferred.push((view.weak_reference(), request));
+        }
+        decision
+    }
+
+    /// Forward of `Callback::view_request_geometry`
+    ///
+    /// Accepted and clamped geometries are applied.
+    pub fn view_request_geometry(&mut self, view: &View, geometry: Geometry, layout: &LayoutEngine)
+                                 -> Decision {
+        let decision = self.decisions_for(view, layout).geometry;
+        match decision {
+            Decision::Accept => view.set_geometry(ResizeEdge::Null, geometry),
+            Decision::Clamp => {
+                let area = Geometry {
+                    origin: Point { x: 0, y: 0 },
+                    size: view.output().virtual_resolution(),
+                };
+                view.set_geometry(ResizeEdge::Null, clamp_to(geometry, area));
+            }
+            Decision::Reject | Decision::Defer => {}
+        }
+        self.defer(view, decision, Request::Geometry(geometry))
+    }
+
+    /// Forward of `Callback::view_request_move`
+    pub fn view_request_move(&mut self, view: &View, origin: Point, layout: &LayoutEngine) -> Decision {
+        let decision = self.decisions_for(view, layout).moving;
+        self.defer(view, decision, Request::Move(origin))
+    }
+
+    /// Forward of `Callback::view_request_resize`
+    pub fn view_request_resize(&mut self, view: &View, edges: ResizeEdge::Flags, origin: Point,
+                               layout: &LayoutEngine)
+                               -> Decision {
+        let decision = self.decisions_for(view, layout).resizing;
+        self.defer(view, decision, Request::Resize(edges, origin))
+    }
+
+    /// Forward of `Callback::view_request_state`
+    pub fn view_request_state(&mut self, view: &View, state: ViewState::Flags, toggle: bool,
+                              layout: &LayoutEngine)
+                              -> Decision {
+        let decision = self.decisions_for(view, layout).state;
+        self.defer(view, decision, Request::State(state, toggle))
+    }
+
+    /// Forward of `Callback::view_destroyed`
+    pub fn view_destroyed(&mut self, view: &View) {
+        self.overrides.remove(&view.weak_reference());
+        self.deferred.retain(|&(ref other, _)| *other != *view);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_util::geometry;
+
+    #[test]
+    fn test_clamp_to() {
+        let area = geometry(0, 0, 1920, 1080);
+        assert_eq!(clamp_to(geometry(100, 100, 800, 600), area), geometry(100, 100, 800, 600));
+        assert_eq!(clamp_to(geometry(1500, -20, 800, 600), area), geometry(1120, 0, 800, 600));
+        assert_eq!(clamp_to(geometry(-100, 500, 2500, 1200), area), geometry(0, 0, 1920, 1080));
+    }
+
+    #[test]
+    fn test_presets() {
+        let tiling = RequestPolicy::default();
+        assert_eq!(tiling.decisions(ViewClass::Tiled).geometry, Decision::Reject);
+        assert_eq!(tiling.decisions(ViewClass::Tiled).state, Decision::Accept);
+        assert_eq!(tiling.decisions(ViewClass::Floating).geometry, Decision::Clamp);
+        assert_eq!(tiling.decisions(ViewClass::Unmanaged), Decisions::all(Decision::Accept));
+
+        let mut policy = RequestPolicy::permissive();
+        policy.set_decisions(ViewClass::Popup, Decisions::all(Decision::Defer));
+        assert_eq!(policy.decisions(ViewClass::Popup).moving, Decision::Defer);
+        assert_eq!(policy.decisions(ViewClass::Floating).resizing, Decision::Accept);
+    }
+}
@@ -0,0 +1,445 @@
+//! Declarative window rules
+//!
+//! A `Rule` consists of `Criteria` matching on the properties of a view and
+//! a list of `Action`s applied to every matching view. Text criteria are
+//! regular expressions, that only need to match a part of the property,
+//! anchor them with `^` and `$` to match it completely. All set criteria
+//! have to match, empty criteria match every view.
+//!
+//! Rules are evaluated in order, later rules override the actions of
+//! earlier ones. Geometry and size actions are applied to the view directly,
+//! state actions through `States`. The others are returned as an `Outcome`,
+//! as they depend on other managers (e.g. `LayoutEngine` or `Workspaces`) of
+//! the compositor.
+//!
+//! With the `serialization` feature rules may be loaded with serde, e.g. as
+//! a `Vec<Rule>` passed to `Rules::new`.
+//!
+//! Forward `view_created` and `view_properties_updated`.
+
+use {Geometry, ResizeEdge, Size, View, ViewPropertyUpdate, ViewState, ViewType};
+use selector::{Criterion, ViewSelector};
+use states::States;
+
+use regex::Regex;
+
+use std::error::Error as ErrorTrait;
+use std::fmt;
+use std::fs;
+
+/// Properties of a view, that are matched by `Criteria`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Properties {
+    /// Application id, see `View::app_id`
+    pub app_id: String,
+    /// Class, see `View::class`
+    pub class: String,
+    /// Instance, see `View::instance`
+    pub instance: String,
+    /// Title, see `View::title`
+    pub title: String,
+    /// Type of the view
+    pub view_type: ViewType::Flags,
+    /// If the view has a parent
+    pub has_parent: bool,
+    /// File name of the executable of the owning process, if it is known
+    pub executable: Option<String>,
+}
+
+/// File name of the executable of a process
+fn executable(pid: i32) -> Option<String> {
+    if pid <= 0 {
+        return None;
+    }
+    fs::read_link(format!("/proc/{}/exe", pid))
+        .ok()
+        .and_then(|path| path.file_name().map(|name| name.to_string_lossy().into_owned()))
+}
+
+impl Default for Properties {
+    fn default() -> Properties {
+        Properties {
+            app_id: String::new(),
+            class: String::new(),
+            instance: String::new(),
+            title: String::new(),
+            view_type: ViewType::Flags::empty(),
+            has_parent: false,
+            executable: None,
+        }
+    }
+}
+
+impl Properties {
+    /// Current properties of a view
+    pub fn of(view: &View) -> Properties {
+        Properties {
+            app_id: view.app_id().into_owned(),
+            class: view.class().into_owned(),
+            instance: view.instance().into_owned(),
+            title: view.title().into_owned(),
+            view_type: view.view_type(),
+            has_parent: view.parent().is_some(),
+            executable: executable(view.pid()),
+        }
+    }
+}
+
+/// Properties a view has to match
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub struct Criteria {
+    /// Regular expression matching the application id
+    pub app_id: Option<String>,
+    /// Regular expression matching the class
+    pub class: Option<String>,
+    /// Regular expression matching the instance
+    pub instance: Option<String>,
+    /// Regular expression matching the title
+    pub title: Option<String>,
+    /// Type flags, that all need to be set
+    pub view_type: Option<ViewType::Flags>,
+    /// If the view needs to have a parent or not
+    pub has_parent: Option<bool>,
+    /// File name of the executable of the owning process
+    pub executable: Option<String>,
+}
+
+/// Action applied to matching views
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub enum Action {
+    /// Do not tile the view
+    Floating,
+    /// Tile the view
+    Tiled,
+    /// Move the view to the named workspace
+    Workspace(String),
+    /// Move the view to the output with the given name
+    Output(String),
+    /// Set the geometry of the view
+    Geometry(Geometry),
+    /// Set the size of the view, keeping its origin
+    Size(Size),
+    /// Set `ViewState::Fullscreen`
+    Fullscreen,
+    /// Set `ViewState::Maximized`
+    Maximized,
+    /// Do not focus the view, when it is created
+    NoFocus,
+    /// Show the view on all workspaces
+    Sticky,
+}
+
+/// Criteria with actions for matching views
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub struct Rule {
+    /// Properties matching views need to have
+    pub criteria: Criteria,
+    /// Actions applied to matching views
+    pub actions: Vec<Action>,
+    /// Evaluate the rule again, when properties of a view change
+    #[cfg_attr(feature = "serialization", serde(default))]
+    pub reevaluate: bool,
+}
+
+/// Error representing an invalid regular expression of a rule
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RuleError {
+    /// Index of the invalid rule
+    pub rule: usize,
+    /// Name of the invalid criterion, e.g. `"title"`
+    pub field: &'static str,
+    /// Message of the regular expression parser
+    pub message: String,
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid {} of rule {}: {}", self.field, self.rule, self.message)
+    }
+}
+
+impl ErrorTrait for RuleError {
+    fn description(&self) -> &str {
+        "Invalid regular expression in window rule"
+    }
+}
+
+/// Result of all rules matching a view
+///
+/// Actions of later rules override those of earlier ones.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Outcome {
+    /// `Some(true)` if the view should float, `Some(false)` if it should be
+    /// tiled
+    pub floating: Option<bool>,
+    /// Workspace the view should be moved to
+    pub workspace: Option<String>,
+    /// Name of the output the view should be moved to
+    pub output: Option<String>,
+    /// Geometry of the view
+    pub geometry: Option<Geometry>,
+    /// Size of the view
+    pub size: Option<Size>,
+    /// States to set
+    pub state: ViewState::Flags,
+    /// If the view should not be focused
+    pub no_focus: bool,
+    /// If the view should be sticky
+    pub sticky: bool,
+}
+
+impl Default for Outcome {
+    fn default() -> Outcome {
+        Outcome {
+            floating: None,
+            workspace: None,
+            output: None,
+            geometry: None,
+            size: None,
+            state: ViewState::Flags::empty(),
+            no_focus: false,
+            sticky: false,
+        }
+    }
+}
+
+impl Outcome {
+    /// Check if no rule matched
+    pub fn is_empty(&self) -> bool {
+        *self == Outcome::default()
+    }
+
+    fn add(&mut self, action: &Action) {
+        match *action {
+            Action::Floating => self.floating = Some(true),
+            Action::Tiled => self.floating = Some(false),
+            Action::Workspace(ref name) => self.workspace = Some(name.clone()),
+            Action::Output(ref name) => self.output = Some(name.clone()),
+            Action::Geometry(geometry) => {
+                self.geometry = Some(geometry);
+                self.size = None;
+            }
+            Action::Size(size) => self.size = Some(size),
+            Action::Fullscreen => self.state |= ViewState::Fullscreen,
+            Action::Maximized => self.state |= ViewState::Maximized,
+            Action::NoFocus => self.no_focus = true,
+            Action::Sticky => self.sticky = true,
+        }
+    }
+
+    /// Apply the geometry, size and states to the view
+    ///
+    /// `usable_area` is the area of the output not covered by panels, which
+    /// maximised views fill, see `States`.
+    pub fn apply(&self, view: &View, states: &mut States, usable_area: Geometry) {
+        let size = self.size.or_else(|| self.geometry.map(|geometry| geometry.size));
+        if let Some(size) = size {
+            let origin = match self.geometry {
+                Some(geometry) => geometry.origin,
+                None => view.geometry().origin,
+            };
+            view.set_geometry(ResizeEdge::Null,
+                              Geometry {
+                                  origin: origin,
+                                  size: size,
+                              });
+        }
+        if !self.state.is_empty() {
+            states.view_request_state(view, self.state, true, usable_area);
+        }
+    }
+}
+
+struct Compiled {
+    /// Text criteria of the rule
+    selector: ViewSelector,
+    rule: Rule,
+}
+
+impl Compiled {
+    fn new(rule: Rule, index: usize) -> Result<Compiled, RuleError> {
+        let selector = {
+            let patterns: [(&Option<String>, &'static str, fn(Regex) -> Criterion); 4] =
+                [(&rule.criteria.app_id, "app_id", Criterion::AppId),
+                 (&rule.criteria.class, "class", Criterion::Class),
+                 (&rule.criteria.instance, "instance", Criterion::Instance),
+                 (&rule.criteria.title, "title", Criterion::Title)];
+            let mut criteria = Vec::new();
+            for &(pattern, field, criterion) in &patterns {
+                if let Some(ref pattern) = *pattern {
+                    let regex = Regex::new(pattern).map_err(|error| {
+                            RuleError {
+                                rule: index,
+                                field: field,
+                                message: error.to_string(),
+                            }
+                        })?;
+                    criteria.push(criterion(regex));
+                }
+            }
+            ViewSelector::new(criteria)
+        };
+
+        Ok(Compiled {
+               selector: selector,
+               rule: rule,
+           })
+    }
+
+    fn matches(&self, properties: &Properties) -> bool {
+        let criteria = &self.rule.criteria;
+        self.selector.matches_properties(properties) &&
+        criteria.view_type.map(|flags| properties.view_type.contains(flags)).unwrap_or(true) &&
+        criteria.has_parent.map(|parent| parent == properties.has_parent).unwrap_or(true) &&
+        criteria.executable
+            .as_ref()
+            .map(|executable| Some(executable) == properties.executable.as_ref())
+            .unwrap_or(true)
+    }
+}
+
+/// Ordered set of compiled rules
+pub struct Rules {
+    rules: Vec<Compiled>,
+}
+
+impl Rules {
+    /// Compile a set of rules
+    ///
+    /// Fails if any criterion is not a valid regular expression.
+    pub fn new(rules: Vec<Rule>) -> Result<Rules, RuleError> {
+        let mut compiled = Vec::with_capacity(rules.len());
+        for (i, rule) in rules.into_iter().enumerate() {
+            compiled.push(Compiled::new(rule, i)?);
+        }
+        Ok(Rules { rules: compiled })
+    }
+
+    /// The rules of the set in order
+    pub fn rules(&self) -> Vec<&Rule> {
+        self.rules.iter().map(|compiled| &compiled.rule).collect()
+    }
+
+    fn evaluate_filtered<F>(&self, properties: &Properties, filter: F) -> Outcome
+        where F: Fn(&Rule) -> bool
+    {
+        let mut outcome = Outcome::default();
+        for compiled in &self.rules {
+            if filter(&compiled.rule) && compiled.matches(properties) {
+                for action in &compiled.rule.actions {
+                    outcome.add(action);
+                }
+            }
+        }
+        outcome
+    }
+
+    /// Combined outcome of all rules matching the properties
+    pub fn evaluate(&self, properties: &Properties) -> Outcome {
+        self.evaluate_filtered(properties, |_| true)
+    }
+
+    /// Forward of `Callback::view_created`
+    ///
+    /// Applies the geometry, size and states of matching rules and returns
+    /// their outcome, the other actions are left to the caller. See
+    /// `Outcome::apply` for `states` and `usable_area`.
+    pub fn view_created(&self, view: &View, states: &mut States, usable_area: Geometry) -> Outcome {
+        let outcome = self.evaluate(&Properties::of(view));
+        outcome.apply(view, states, usable_area);
+        outcome
+    }
+
+    /// Forward of `Callback::view_properties_updated`
+    ///
+    /// Only rules marked with `Rule::reevaluate` are evaluated again, their
+    /// outcome is applied like in `view_created`.
+    pub fn view_properties_updated(&self, view: &View, _mask: ViewPropertyUpdate::Flags, states: &mut States,
+                                   usable_area: Geometry)
+                                   -> Outcome {
+        let outcome = self.evaluate_filtered(&Properties::of(view), |rule| rule.reevaluate);
+        outcome.apply(view, states, usable_area);
+        outcome
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn firefox() -> Properties {
+        Properties {
+            app_id: String::from("firefox"),
+            title: String::from("Mail - Mozilla Firefox"),
+            executable: Some(String::from("firefox")),
+            ..Properties::default()
+        }
+    }
+
+    fn rule(criteria: Criteria, actions: Vec<Action>) -> Rule {
+        Rule {
+            criteria: criteria,
+            actions: actions,
+            reevaluate: false,
+        }
+    }
+
+    #[test]
+    fn test_matching() {
+        let rules = Rules::new(vec![rule(Criteria {
+                                              app_id: Some(String::from("^firefox$")),
+                                              title: Some(String::from("^Mail")),
+                                              ..Criteria::default()
+                                          },
+                                          vec![Action::Workspace(String::from("mail"))]),
+                                     rule(Criteria {
+                                              has_parent: Some(true),
+                                              ..Criteria::default()
+                                          },
+                                          vec![Action::Floating])])
+            .unwrap();
+
+        let outcome = rules.evaluate(&firefox());
+        assert_eq!(outcome.workspace, Some(String::from("mail")));
+        assert_eq!(outcome.floating, None);
+
+        let mut dialog = firefox();
+        dialog.title = String::from("Open File");
+        dialog.has_parent = true;
+        let outcome = rules.evaluate(&dialog);
+        assert_eq!(outcome.workspace, None);
+        assert_eq!(outcome.floating, Some(true));
+    }
+
+    #[test]
+    fn test_later_rules_override() {
+        let rules = Rules::new(vec![rule(Criteria::default(), vec![Action::Floating, Action::Fullscreen]),
+                                     rule(Criteria {
+                                              executable: Some(String::from("firefox")),
+                                              ..Criteria::default()
+                                          },
+                                          vec![Action::Tiled, Action::Maximized])])
+            .unwrap();
+        let outcome = rules.evaluate(&firefox());
+        assert_eq!(outcome.floating, Some(false));
+        assert_eq!(outcome.state, ViewState::Fullscreen | ViewState::Maximized);
+        assert!(rules.evaluate(&Properties::default()).floating == Some(true));
+    }
+
+    #[test]
+    fn test_invalid_regex() {
+        let error = Rules::new(vec![Rule::default(),
+                                    rule(Criteria {
+                                             class: Some(String::from("(unclosed")),
+                                             ..Criteria::default()
+                                         },
+                                         vec![])])
+            .err()
+            .unwrap();
+        assert_eq!(error.rule, 1);
+        assert_eq!(error.field, "class");
+    }
+}
@@ -0,0 +1,293 @@
+//! Scratchpad views
+//!
+//! Any view may be moved to the scratchpad, where it is hidden by clearing
+//! its `Visibility`. Summoning the scratchpad shows the view centred on an
+//! output as floating overlay on its current workspace, summoning it again
+//! hides it. With multiple views on the scratchpad every summon shows the
+//! next one.
+//!
+//! Applications may be registered with a command by their app id. If no view
+//! of the application is on the scratchpad when it is summoned, a running
+//! view of it is moved to the scratchpad. Otherwise the command is spawned
+//! and the first view of the application created within `SPAWN_TIMEOUT`
+//! seconds is adopted.
+//!
+//! Views on the scratchpad are taken out of `Workspaces` with
+//! `Workspaces::set_managed`, so switching workspaces does not show them.
+//! Spawned commands are reaped, whenever the scratchpad is summoned or a view
+//! is created.
+//!
+//! Forward `view_created` and `view_destroyed`.
+
+use {Geometry, Output, ResizeEdge, View, Visibility, WeakView};
+use placement;
+use workspaces::Workspaces;
+
+use std::collections::HashMap;
+use std::io;
+use std::process::{Child, Command};
+use std::time::{Duration, Instant};
+
+/// Seconds a spawned application has to create its first view, later views
+/// are not adopted
+pub const SPAWN_TIMEOUT: u64 = 10;
+
+/// Move an item to the end of `items`, if it is contained
+fn move_to_back<T, U: ?Sized>(items: &mut Vec<T>, item: &U)
+    where T: PartialEq<U>
+{
+    if let Some(position) = items.iter().position(|other| *other == *item) {
+        let item = items.remove(position);
+        items.push(item);
+    }
+}
+
+/// Forget spawned applications, that did not create a view in time
+fn expire(pending: &mut Vec<(String, Instant)>, now: Instant) {
+    pending.retain(|&(_, spawned)| now.duration_since(spawned) < Duration::from_secs(SPAWN_TIMEOUT));
+}
+
+/// Remove a spawned application, returns `true` if it was spawned in time
+fn adopt(pending: &mut Vec<(String, Instant)>, app_id: &str, now: Instant) -> bool {
+    expire(pending, now);
+    match pending.iter().position(|&(ref other, _)| other == app_id) {
+        Some(position) => {
+            pending.remove(position);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Wait for spawned commands, that exited, keeping the running ones
+fn reap(children: &mut Vec<Child>) {
+    let running = children.drain(..)
+        .filter_map(|mut child| match child.try_wait() {
+                        Ok(None) => Some(child),
+                        Ok(Some(_)) | Err(_) => None,
+                    })
+        .collect();
+    *children = running;
+}
+
+/// Hidden views, that may be summoned on demand
+#[derive(Default)]
+pub struct Scratchpad {
+    /// Views in the order they are summoned
+    views: Vec<WeakView>,
+    shown: Option<WeakView>,
+    commands: HashMap<String, String>,
+    /// App ids of spawned commands, whose view did not appear yet, and the
+    /// time they were spawned
+    pending: Vec<(String, Instant)>,
+    /// Spawned commands, that did not exit yet
+    children: Vec<Child>,
+}
+
+impl Scratchpad {
+    /// Create a new empty scratchpad
+    pub fn new() -> Scratchpad {
+        Scratchpad::default()
+    }
+
+    /// Views on the scratchpad, the next one to be summoned first
+    pub fn views(&self) -> &[WeakView] {
+        &self.views
+    }
+
+    /// Check if a view is on the scratchpad
+    pub fn contains(&self, view: &View) -> bool {
+        self.views.iter().any(|other| *other == *view)
+    }
+
+    /// The currently summoned view
+    pub fn shown(&self) -> Option<WeakView> {
+        self.shown.clone()
+    }
+
+    /// Move a view to the scratchpad and hide it
+    pub fn add(&mut self, view: &View, workspaces: &mut Workspaces) {
+        if !self.contains(view) {
+            self.views.push(view.weak_reference());
+        }
+        workspaces.set_managed(view, false);
+        self.hide(view);
+    }
+
+    /// Remove a view from the scratchpad and show it on the current
+    /// workspace of its output
+    pub fn remove(&mut self, view: &View, workspaces: &mut Workspaces) {
+        self.views.retain(|other| *other != *view);
+        if self.shown.as_ref().map(|shown| *shown == *view).unwrap_or(false) {
+            self.shown = None;
+        }
+        workspaces.set_managed(view, true);
+        let current = workspaces.current(view.output()).map(String::from);
+        match current {
+            Some(current) => workspaces.move_view(view, &current),
+            None => view.set_visibility(view.output().visibility()),
+        }
+    }
+
+    fn hide(&mut self, view: &View) {
+        view.set_visibility(Visibility::Null);
+        if self.shown.as_ref().map(|shown| *shown == *view).unwrap_or(false) {
+            self.shown = None;
+        }
+        // the view is summoned last again
+        move_to_back(&mut self.views, view);
+    }
+
+    fn show(&mut self, view: &View, output: &Output, area: Geometry) {
+        if let Some(shown) = self.shown.take() {
+            shown.run(|shown| self.hide(shown));
+        }
+        if view.output() != output {
+            view.set_output(output);
+        }
+        view.set_visibility(output.visibility());
+        view.set_geometry(ResizeEdge::Null, placement::center(view.geometry().size, area));
+        view.bring_to_front();
+        view.focus();
+        self.shown = Some(view.weak_reference());
+    }
+
+    /// Summon the next view of the scratchpad on the output or hide the
+    /// summoned one
+    ///
+    /// `area` is the usable area of the output, the view is centred on.
+    pub fn toggle(&mut self, output: &Output, area: Geometry) {
+        if let Some(shown) = self.shown.clone() {
+            shown.run(|shown| self.hide(shown));
+            return;
+        }
+        if let Some(next) = self.views.first().cloned() {
+            next.run(|next| self.show(next, output, area));
+        }
+    }
+
+    /// Register a command, that is spawned, when an application is summoned
+    /// without having a view on the scratchpad
+    pub fn set_command<S: Into<String>, C: Into<String>>(&mut self, app_id: S, command: C) {
+        self.commands.insert(app_id.into(), command.into());
+    }
+
+    /// Summon the view of an application, spawning its registered command if
+    /// there is none
+    ///
+    /// A running view of the application, that is not on the scratchpad yet,
+    /// is moved to it. Summoning a shown view hides it. Returns an error if
+    /// the command could not be spawned.
+    pub fn summon(&mut self, app_id: &str, output: &Output, area: Geometry, workspaces: &mut Workspaces)
+                  -> io::Result<()> {
+        reap(&mut self.children);
+        let view = self.views
+            .iter()
+            .find(|view| view.run(|view| view.app_id() == app_id).unwrap_or(false))
+            .cloned();
+        if let Some(view) = view {
+            if self.shown.as_ref() == Some(&view) {
+                view.run(|view| self.hide(view));
+            } else {
+                view.run(|view| self.show(view, output, area));
+            }
+            return Ok(());
+        }
+
+        let running = Output::with_all_outputs(|outputs| {
+            outputs.iter()
+                .flat_map(|output| output.views())
+                .find(|view| view.app_id() == app_id)
+                .map(|view| view.weak_reference())
+        });
+        if let Some(view) = running {
+            view.run(|view| {
+                         self.views.insert(0, view.weak_reference());
+                         workspaces.set_managed(view, false);
+                         self.show(view, output, area);
+                     });
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        expire(&mut self.pending, now);
+        if self.pending.iter().any(|&(ref pending, _)| pending == app_id) {
+            return Ok(());
+        }
+        if let Some(command) = self.commands.get(app_id) {
+            let child = Command::new("sh").arg("-c").arg(command).spawn()?;
+            self.children.push(child);
+            self.pending.push((String::from(app_id), now));
+        }
+        Ok(())
+    }
+
+    /// Forward of `Callback::view_created`
+    ///
+    /// Adopts and summons the first view of a spawned application on the
+    /// output it was created on, if it appeared within `SPAWN_TIMEOUT`.
+    /// Returns `true` if the view was adopted.
+    pub fn view_created(&mut self, view: &View, area: Geometry, workspaces: &mut Workspaces) -> bool {
+        reap(&mut self.children);
+        if !adopt(&mut self.pending, &view.app_id(), Instant::now()) {
+            return false;
+        }
+        self.views.insert(0, view.weak_reference());
+        workspaces.set_managed(view, false);
+        self.show(view, view.output(), area);
+        true
+    }
+
+    /// Forward of `Callback::view_destroyed`
+    pub fn view_destroyed(&mut self, view: &View) {
+        self.views.retain(|other| *other != *view);
+        if self.shown.as_ref().map(|shown| *shown == *view).unwrap_or(false) {
+            self.shown = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cycling_order() {
+        let mut views = vec![1, 2, 3];
+        // the first view is summoned and hidden again
+        move_to_back(&mut views, &1);
+        assert_eq!(views, vec![2, 3, 1]);
+        move_to_back(&mut views, &2);
+        assert_eq!(views, vec![3, 1, 2]);
+        move_to_back(&mut views, &4);
+        assert_eq!(views, vec![3, 1, 2]);
+    }
+
+    #[test]
+    fn test_adopt_pending() {
+        let start = Instant::now();
+        let mut pending = vec![(String::from("foot"), start), (String::from("firefox"), start)];
+        assert!(!adopt(&mut pending, "mpv", start));
+        assert!(adopt(&mut pending, "foot", start + Duration::from_secs(1)));
+        // only the first view is adopted
+        assert!(!adopt(&mut pending, "foot", start + Duration::from_secs(1)));
+        // timed out
+        assert!(!adopt(&mut pending, "firefox", start + Duration::from_secs(SPAWN_TIMEOUT)));
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn test_reap_exited_commands() {
+        let mut exited = Command::new("sh").arg("-c").arg("exit 0").spawn().unwrap();
+        exited.wait().unwrap();
+        let running = Command::new("sh").arg("-c").arg("sleep 10").spawn().unwrap();
+        let mut children = vec![exited, running];
+
+        reap(&mut children);
+        assert_eq!(children.len(), 1);
+        children[0].kill().unwrap();
+        children[0].wait().unwrap();
+        reap(&mut children);
+        assert!(children.is_empty());
+    }
+}
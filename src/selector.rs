@@ -0,0 +1,443 @@
+//! i3-style criteria for selecting views
+//!
+//! A `ViewSelector` is parsed from a list of criteria in brackets, e.g.
+//! `[app_id="firefox" title="^Mail"]`, `[con_mark=x]`, `[workspace=3]` or
+//! `[urgent]`. A view is selected, if it matches all criteria, `[]` selects
+//! every view. Supported criteria are
+//!
+//! - `app_id`, `class`, `instance` and `title`, regular expressions
+//!   matching the respective property of the view
+//! - `con_mark`, a regular expression matching any mark of the view
+//! - `workspace`, the name of a workspace the view is on
+//! - `urgent`, selecting views, that demand attention
+//!
+//! Values may be quoted, backslashes escape quotes and backslashes inside
+//! quotes. Parse errors carry the byte offset of the offending input.
+//!
+//! Marks, workspaces and urgency are not known to wlc, they are provided by
+//! a `Context`, which is implemented for `Workspaces`.
+
+use View;
+use rules::Properties;
+use workspaces::Workspaces;
+
+use regex::Regex;
+
+use std::error::Error as ErrorTrait;
+use std::fmt;
+use std::str::FromStr;
+
+/// Compositor state, that criteria may refer to
+pub trait Context {
+    /// Marks of a view
+    fn marks(&self, _view: &View) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Names of the workspaces a view is on
+    fn workspaces(&self, _view: &View) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Check if a view demands attention
+    fn is_urgent(&self, _view: &View) -> bool {
+        false
+    }
+}
+
+/// Context without marks, workspaces or urgent views
+impl Context for () {}
+
+impl Context for Workspaces {
+    fn workspaces(&self, view: &View) -> Vec<String> {
+        self.tags(view).into_iter().map(String::from).collect()
+    }
+}
+
+/// A single criterion of a `ViewSelector`
+#[derive(Clone, Debug)]
+pub enum Criterion {
+    /// Application id matching the expression
+    AppId(Regex),
+    /// Class matching the expression
+    Class(Regex),
+    /// Instance matching the expression
+    Instance(Regex),
+    /// Title matching the expression
+    Title(Regex),
+    /// Any mark matching the expression
+    ConMark(Regex),
+    /// On the named workspace
+    Workspace(String),
+    /// Demanding attention
+    Urgent,
+}
+
+/// Everything known about a view, that criteria match on
+struct Subject<'a> {
+    properties: &'a Properties,
+    marks: Vec<String>,
+    workspaces: Vec<String>,
+    urgent: bool,
+}
+
+impl Criterion {
+    fn matches(&self, subject: &Subject) -> bool {
+        match *self {
+            Criterion::AppId(ref regex) => regex.is_match(&subject.properties.app_id),
+            Criterion::Class(ref regex) => regex.is_match(&subject.properties.class),
+            Criterion::Instance(ref regex) => regex.is_match(&subject.properties.instance),
+            Criterion::Title(ref regex) => regex.is_match(&subject.properties.title),
+            Criterion::ConMark(ref regex) => subject.marks.iter().any(|mark| regex.is_match(mark)),
+            Criterion::Workspace(ref name) => subject.workspaces.iter().any(|workspace| workspace == name),
+            Criterion::Urgent => subject.urgent,
+        }
+    }
+}
+
+/// Kind of a `ParseError`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// The criteria do not start with `[`
+    ExpectedOpenBracket,
+    /// The criteria are not closed with `]`
+    ExpectedCloseBracket,
+    /// A criterion does not start with a name
+    ExpectedKey,
+    /// The criterion is not known
+    UnknownKey(String),
+    /// The criterion needs a value, e.g. `title=...`
+    ExpectedValue,
+    /// The criterion does not take a value, e.g. `urgent`
+    UnexpectedValue,
+    /// A quoted value is not closed
+    UnterminatedString,
+    /// A value is not a valid regular expression
+    InvalidRegex(String),
+    /// Input follows the closing `]`
+    TrailingCharacters,
+}
+
+/// Error representing invalid criteria
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// Byte offset of the error in the input
+    pub position: usize,
+    /// What went wrong
+    pub kind: ParseErrorKind,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            ParseErrorKind::UnknownKey(ref key) => {
+                write!(f, "Unknown criterion '{}' at position {}", key, self.position)
+            }
+            ParseErrorKind::InvalidRegex(ref message) => {
+                write!(f, "Invalid regular expression at position {}: {}", self.position, message)
+            }
+            _ => write!(f, "{} at position {}", self.description(), self.position),
+        }
+    }
+}
+
+impl ErrorTrait for ParseError {
+    fn description(&self) -> &str {
+        match self.kind {
+            ParseErrorKind::ExpectedOpenBracket => "Expected '['",
+            ParseErrorKind::ExpectedCloseBracket => "Expected ']'",
+            ParseErrorKind::ExpectedKey => "Expected criterion",
+            ParseErrorKind::UnknownKey(_) => "Unknown criterion",
+            ParseErrorKind::ExpectedValue => "Expected '=' and a value",
+            ParseErrorKind::UnexpectedValue => "Criterion does not take a value",
+            ParseErrorKind::UnterminatedString => "Unterminated string",
+            ParseErrorKind::InvalidRegex(_) => "Invalid regular expression",
+            ParseErrorKind::TrailingCharacters => "Unexpected input after ']'",
+        }
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error<T>(&self, position: usize, kind: ParseErrorKind) -> Result<T, ParseError> {
+        Err(ParseError {
+                position: position,
+                kind: kind,
+            })
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.position..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let next = self.peek();
+        if let Some(c) = next {
+            self.position += c.len_utf8();
+        }
+        next
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().map(char::is_whitespace).unwrap_or(false) {
+            self.bump();
+        }
+    }
+
+    fn key(&mut self) -> &'a str {
+        let start = self.position;
+        while self.peek().map(|c| c.is_alphanumeric() || c == '_').unwrap_or(false) {
+            self.bump();
+        }
+        &self.input[start..self.position]
+    }
+
+    fn value(&mut self) -> Result<String, ParseError> {
+        let start = self.position;
+        if self.peek() != Some('"') {
+            while self.peek().map(|c| !c.is_whitespace() && c != ']').unwrap_or(false) {
+                self.bump();
+            }
+            if self.position == start {
+                return self.error(start, ParseErrorKind::ExpectedValue);
+            }
+            return Ok(String::from(&self.input[start..self.position]));
+        }
+
+        self.bump();
+        let mut value = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => return Ok(value),
+                Some('\\') => {
+                    match self.peek() {
+                        Some(c) if c == '"' || c == '\\' => {
+                            self.bump();
+                            value.push(c);
+                        }
+                        _ => value.push('\\'),
+                    }
+                }
+                Some(c) => value.push(c),
+                None => return self.error(start, ParseErrorKind::UnterminatedString),
+            }
+        }
+    }
+
+    fn regex(&self, value: &str, position: usize) -> Result<Regex, ParseError> {
+        match Regex::new(value) {
+            Ok(regex) => Ok(regex),
+            Err(error) => self.error(position, ParseErrorKind::InvalidRegex(error.to_string())),
+        }
+    }
+
+    fn criterion(&mut self) -> Result<Criterion, ParseError> {
+        let start = self.position;
+        let key = self.key();
+        if key.is_empty() {
+            return self.error(start, ParseErrorKind::ExpectedKey);
+        }
+
+        let has_value = self.peek() == Some('=');
+        if key == "urgent" {
+            return if has_value {
+                       self.error(self.position, ParseErrorKind::UnexpectedValue)
+                   } else {
+                       Ok(Criterion::Urgent)
+                   };
+        }
+        let known = ["app_id", "class", "instance", "title", "con_mark", "workspace"];
+        if !known.contains(&key) {
+            return self.error(start, ParseErrorKind::UnknownKey(String::from(key)));
+        }
+        if !has_value {
+            return self.error(self.position, ParseErrorKind::ExpectedValue);
+        }
+        self.bump();
+
+        let value_start = self.position;
+        let value = self.value()?;
+        Ok(match key {
+               "app_id" => Criterion::AppId(self.regex(&value, value_start)?),
+               "class" => Criterion::Class(self.regex(&value, value_start)?),
+               "instance" => Criterion::Instance(self.regex(&value, value_start)?),
+               "title" => Criterion::Title(self.regex(&value, value_start)?),
+               "con_mark" => Criterion::ConMark(self.regex(&value, value_start)?),
+               _ => Criterion::Workspace(value),
+           })
+    }
+
+    fn selector(&mut self) -> Result<ViewSelector, ParseError> {
+        self.skip_whitespace();
+        let start = self.position;
+        if self.bump() != Some('[') {
+            return self.error(start, ParseErrorKind::ExpectedOpenBracket);
+        }
+
+        let mut criteria = Vec::new();
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some(']') => {
+                    self.bump();
+                    break;
+                }
+                None => return self.error(self.position, ParseErrorKind::ExpectedCloseBracket),
+                Some(_) => criteria.push(self.criterion()?),
+            }
+        }
+
+        self.skip_whitespace();
+        if self.position < self.input.len() {
+            return self.error(self.position, ParseErrorKind::TrailingCharacters);
+        }
+        Ok(ViewSelector { criteria: criteria })
+    }
+}
+
+/// Parsed criteria selecting views
+#[derive(Clone, Debug)]
+pub struct ViewSelector {
+    criteria: Vec<Criterion>,
+}
+
+impl FromStr for ViewSelector {
+    type Err = ParseError;
+
+    fn from_str(input: &str) -> Result<ViewSelector, ParseError> {
+        Parser {
+                input: input,
+                position: 0,
+            }
+            .selector()
+    }
+}
+
+impl ViewSelector {
+    /// Create a selector from criteria, that are already compiled
+    pub fn new(criteria: Vec<Criterion>) -> ViewSelector {
+        ViewSelector { criteria: criteria }
+    }
+
+    /// Parse criteria, same as `str::parse`
+    pub fn parse(input: &str) -> Result<ViewSelector, ParseError> {
+        input.parse()
+    }
+
+    /// The parsed criteria
+    pub fn criteria(&self) -> &[Criterion] {
+        &self.criteria
+    }
+
+    fn matches_subject(&self, subject: &Subject) -> bool {
+        self.criteria.iter().all(|criterion| criterion.matches(subject))
+    }
+
+    /// Check if the properties of a view match all criteria
+    ///
+    /// Without a `Context` criteria on marks, workspaces or urgency never
+    /// match.
+    pub fn matches_properties(&self, properties: &Properties) -> bool {
+        self.matches_subject(&Subject {
+                                  properties: properties,
+                                  marks: Vec::new(),
+                                  workspaces: Vec::new(),
+                                  urgent: false,
+                              })
+    }
+
+    /// Check if a view matches all criteria
+    pub fn matches<C: Context + ?Sized>(&self, view: &View, context: &C) -> bool {
+        self.matches_subject(&Subject {
+                                  properties: &Properties::of(view),
+                                  marks: context.marks(view),
+                                  workspaces: context.workspaces(view),
+                                  urgent: context.is_urgent(view),
+                              })
+    }
+
+    /// All views matching the criteria
+    pub fn select<'a, C: Context + ?Sized>(&self, views: &[&'a View], context: &C) -> Vec<&'a View> {
+        views.iter().cloned().filter(|view| self.matches(view, context)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn properties(app_id: &str, title: &str) -> Properties {
+        Properties {
+            app_id: String::from(app_id),
+            title: String::from(title),
+            ..Properties::default()
+        }
+    }
+
+    fn subject(properties: &Properties) -> Subject {
+        Subject {
+            properties: properties,
+            marks: vec![String::from("x")],
+            workspaces: vec![String::from("3")],
+            urgent: false,
+        }
+    }
+
+    fn error(input: &str) -> ParseError {
+        ViewSelector::parse(input).err().unwrap()
+    }
+
+    #[test]
+    fn test_matching() {
+        let selector = ViewSelector::parse(r#"[app_id="firefox" title="^Mail"]"#).unwrap();
+        assert_eq!(selector.criteria().len(), 2);
+        assert!(selector.matches_subject(&subject(&properties("firefox", "Mail - Inbox"))));
+        assert!(!selector.matches_subject(&subject(&properties("firefox", "Re: Mail"))));
+
+        let selector = ViewSelector::parse("[con_mark=x workspace=3]").unwrap();
+        assert!(selector.matches_subject(&subject(&properties("foot", ""))));
+        let selector = ViewSelector::parse("[ workspace=\"4\" ]").unwrap();
+        assert!(!selector.matches_subject(&subject(&properties("foot", ""))));
+        let selector = ViewSelector::parse("[urgent]").unwrap();
+        assert!(!selector.matches_subject(&subject(&properties("foot", ""))));
+        assert!(ViewSelector::parse("[]").unwrap().matches_subject(&subject(&properties("foot", ""))));
+    }
+
+    #[test]
+    fn test_quoting() {
+        let selector = ViewSelector::parse(r#"[title="\"b\" \\d"]"#).unwrap();
+        assert!(selector.matches_subject(&subject(&properties("", r#"a "b" 1"#))));
+        assert!(!selector.matches_subject(&subject(&properties("", r#"a "b" c"#))));
+    }
+
+    #[test]
+    fn test_error_positions() {
+        assert_eq!(error("app_id=x]"),
+                   ParseError {
+                       position: 0,
+                       kind: ParseErrorKind::ExpectedOpenBracket,
+                   });
+        assert_eq!(error("[app_id=x"),
+                   ParseError {
+                       position: 9,
+                       kind: ParseErrorKind::ExpectedCloseBracket,
+                   });
+        assert_eq!(error("[app_id=x foo=y]"),
+                   ParseError {
+                       position: 10,
+                       kind: ParseErrorKind::UnknownKey(String::from("foo")),
+                   });
+        assert_eq!(error("[title]").position, 6);
+        assert_eq!(error("[urgent=1]").kind, ParseErrorKind::UnexpectedValue);
+        assert_eq!(error("[title=\"abc]").position, 7);
+        assert_eq!(error("[=x]").kind, ParseErrorKind::ExpectedKey);
+        assert_eq!(error("[class=(]").position, 7);
+        assert_eq!(error("[] x").position, 3);
+        assert_eq!(error("  \u{e9}]").position, 2);
+        assert_eq!(error("").position, 0);
+    }
+}
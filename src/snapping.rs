@@ -0,0 +1,233 @@
+//! Snapping of moved views
+//!
+//! While a view is moved its edges snap magnetically to the edges of the
+//! output and to the edges of other views, once they come closer than a
+//! threshold. Moving the pointer to an edge or corner of the output selects
+//! a `SnapZone` instead, the view is tiled to the half or quarter of the
+//! output, when the move is finished.
+//!
+//! All computations are pure functions over `Geometry`. Enable them for
+//! interactive moves with `Interactive::set_snapping`.
+
+use {Geometry, Point, Size};
+
+/// Snapping configuration
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub struct Snapping {
+    /// Distance in pixels, below which edges snap, `0` disables it
+    pub threshold: u32,
+    /// Distance of the pointer to the output edges in pixels, below which
+    /// a `SnapZone` is selected, `0` disables it
+    pub zone_threshold: u32,
+}
+
+impl Default for Snapping {
+    fn default() -> Snapping {
+        Snapping {
+            threshold: 16,
+            zone_threshold: 8,
+        }
+    }
+}
+
+/// Region of an output a view may be tiled to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub enum SnapZone {
+    /// Left half
+    Left,
+    /// Right half
+    Right,
+    /// Upper half
+    Top,
+    /// Lower half
+    Bottom,
+    /// Upper left quarter
+    TopLeft,
+    /// Upper right quarter
+    TopRight,
+    /// Lower left quarter
+    BottomLeft,
+    /// Lower right quarter
+    BottomRight,
+}
+
+impl SnapZone {
+    /// Geometry of the zone inside `area`
+    ///
+    /// Also used as preview region during the move.
+    pub fn geometry(&self, area: Geometry) -> Geometry {
+        let left = area.size.w / 2;
+        let top = area.size.h / 2;
+        let (x, w) = match *self {
+            SnapZone::Top | SnapZone::Bottom => (0, area.size.w),
+            SnapZone::Left | SnapZone::TopLeft | SnapZone::BottomLeft => (0, left),
+            SnapZone::Right | SnapZone::TopRight | SnapZone::BottomRight => (left, area.size.w - left),
+        };
+        let (y, h) = match *self {
+            SnapZone::Left | SnapZone::Right => (0, area.size.h),
+            SnapZone::Top | SnapZone::TopLeft | SnapZone::TopRight => (0, top),
+            SnapZone::Bottom | SnapZone::BottomLeft | SnapZone::BottomRight => (top, area.size.h - top),
+        };
+
+        Geometry {
+            origin: Point {
+                x: area.origin.x + x as i32,
+                y: area.origin.y + y as i32,
+            },
+            size: Size { w: w, h: h },
+        }
+    }
+}
+
+/// Zone selected by the pointer, if it is within `threshold` pixels of an
+/// edge of `area`
+///
+/// Corners, where the pointer is close to two edges, select quarters.
+pub fn zone_at(area: Geometry, pointer: Point, threshold: u32) -> Option<SnapZone> {
+    let right = area.origin.x + area.size.w as i32;
+    let bottom = area.origin.y + area.size.h as i32;
+    if threshold == 0 || pointer.x < area.origin.x || pointer.y < area.origin.y || pointer.x >= right ||
+       pointer.y >= bottom {
+        return None;
+    }
+
+    let threshold = threshold as i32;
+    let left = pointer.x < area.origin.x + threshold;
+    let right = pointer.x >= right - threshold;
+    let top = pointer.y < area.origin.y + threshold;
+    let bottom = pointer.y >= bottom - threshold;
+
+    match (left, right, top, bottom) {
+        (true, _, true, _) => Some(SnapZone::TopLeft),
+        (true, _, _, true) => Some(SnapZone::BottomLeft),
+        (_, true, true, _) => Some(SnapZone::TopRight),
+        (_, true, _, true) => Some(SnapZone::BottomRight),
+        (true, _, _, _) => Some(SnapZone::Left),
+        (_, true, _, _) => Some(SnapZone::Right),
+        (_, _, true, _) => Some(SnapZone::Top),
+        (_, _, _, true) => Some(SnapZone::Bottom),
+        _ => None,
+    }
+}
+
+/// Offset, that moves one of the edges `start` and `start + length` onto
+/// the nearest candidate within `threshold`
+fn snap_axis(start: i32, length: u32, candidates: &[i32], threshold: u32) -> i32 {
+    let mut best: Option<i32> = None;
+    for &candidate in candidates {
+        for &edge in &[start, start + length as i32] {
+            let offset = candidate - edge;
+            let closer = best.map(|best| offset.abs() < best.abs()).unwrap_or(true);
+            if offset.abs() <= threshold as i32 && closer {
+                best = Some(offset);
+            }
+        }
+    }
+    best.unwrap_or(0)
+}
+
+/// Check if two ranges overlap or touch
+fn overlaps(a: i32, a_length: u32, b: i32, b_length: u32) -> bool {
+    a <= b + b_length as i32 && b <= a + a_length as i32
+}
+
+/// Snap the edges of `geometry` to the edges of `area` and of `others`
+/// within `threshold` pixels
+///
+/// Edges of other views are only considered, if the views are next to each
+/// other on the other axis. The size is never changed.
+pub fn snap_edges(geometry: Geometry, area: Geometry, others: &[Geometry], threshold: u32) -> Geometry {
+    if threshold == 0 {
+        return geometry;
+    }
+
+    let mut xs = vec![area.origin.x, area.origin.x + area.size.w as i32];
+    let mut ys = vec![area.origin.y, area.origin.y + area.size.h as i32];
+    for other in others {
+        if overlaps(geometry.origin.y, geometry.size.h, other.origin.y, other.size.h) {
+            xs.push(other.origin.x);
+            xs.push(other.origin.x + other.size.w as i32);
+        }
+        if overlaps(geometry.origin.x, geometry.size.w, other.origin.x, other.size.w) {
+            ys.push(other.origin.y);
+            ys.push(other.origin.y + other.size.h as i32);
+        }
+    }
+
+    Geometry {
+        origin: Point {
+            x: geometry.origin.x + snap_axis(geometry.origin.x, geometry.size.w, &xs, threshold),
+            y: geometry.origin.y + snap_axis(geometry.origin.y, geometry.size.h, &ys, threshold),
+        },
+        size: geometry.size,
+    }
+}
+
+/// Geometry restoring the pre-snap `size` of a view dragged out of a snapped
+/// geometry
+///
+/// The pointer keeps its relative horizontal position inside the view and
+/// its distance to the top, as far as the restored size allows.
+pub fn unsnapped(snapped: Geometry, size: Size, pointer: Point) -> Geometry {
+    let fraction = if snapped.size.w == 0 {
+        0.5
+    } else {
+        (pointer.x - snapped.origin.x) as f32 / snapped.size.w as f32
+    };
+    let offset_y = (pointer.y - snapped.origin.y).max(0).min(size.h as i32);
+
+    Geometry {
+        origin: Point {
+            x: pointer.x - (size.w as f32 * fraction) as i32,
+            y: pointer.y - offset_y,
+        },
+        size: size,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_util::geometry;
+
+    #[test]
+    fn test_snap_edges() {
+        let area = geometry(0, 0, 1000, 800);
+        // left edge of the output
+        assert_eq!(snap_edges(geometry(10, 300, 100, 100), area, &[], 16),
+                   geometry(0, 300, 100, 100));
+        // right edge of another view, which wins as it is closer
+        let other = geometry(0, 250, 120, 300);
+        assert_eq!(snap_edges(geometry(130, 300, 100, 100), area, &[other], 16),
+                   geometry(120, 300, 100, 100));
+        // views not next to each other do not attract
+        let other = geometry(0, 600, 120, 100);
+        assert_eq!(snap_edges(geometry(130, 300, 100, 100), area, &[other], 16),
+                   geometry(130, 300, 100, 100));
+        assert_eq!(snap_edges(geometry(10, 300, 100, 100), area, &[], 0),
+                   geometry(10, 300, 100, 100));
+    }
+
+    #[test]
+    fn test_zones() {
+        let area = geometry(0, 0, 1001, 800);
+        assert_eq!(zone_at(area, Point { x: 2, y: 400 }, 8), Some(SnapZone::Left));
+        assert_eq!(zone_at(area, Point { x: 1000, y: 799 }, 8), Some(SnapZone::BottomRight));
+        assert_eq!(zone_at(area, Point { x: 500, y: 400 }, 8), None);
+        assert_eq!(zone_at(area, Point { x: 1001, y: 400 }, 8), None);
+
+        assert_eq!(SnapZone::Left.geometry(area), geometry(0, 0, 500, 800));
+        assert_eq!(SnapZone::BottomRight.geometry(area), geometry(500, 400, 501, 400));
+    }
+
+    #[test]
+    fn test_unsnapped_keeps_pointer_inside() {
+        let snapped = geometry(0, 0, 500, 800);
+        assert_eq!(unsnapped(snapped, Size { w: 200, h: 100 }, Point { x: 250, y: 20 }),
+                   geometry(150, 0, 200, 100));
+        assert_eq!(unsnapped(snapped, Size { w: 200, h: 100 }, Point { x: 250, y: 400 }),
+                   geometry(150, 300, 200, 100));
+    }
+}
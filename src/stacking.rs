@@ -0,0 +1,268 @@
+//! Stacking order enforcing layers
+//!
+//! Every view belongs to a `Layer`, views of higher layers always stay above
+//! views of lower ones. Views with a parent (transients like dialogs) are
+//! kept directly above their parent and share its layer, unless their own
+//! layer is higher. Normal views in the fullscreen state are stacked into
+//! `Layer::Fullscreen`.
+//!
+//! Inside a layer the current order of the output is kept. Raising or
+//! lowering a view moves it inside its layer, then the complete order of the
+//! output is pushed at once via `Output::set_views`.
+//!
+//! Forward `view_created`, `view_destroyed` and `view_request_state`. Use
+//! `Stacking::raise` and `Stacking::lower` instead of `View::bring_to_front`
+//! and `View::send_to_back`.
+
+use {Output, View, ViewState, WeakView};
+
+use std::collections::HashMap;
+use std::error::Error as ErrorTrait;
+use std::fmt;
+
+/// Layer of the stacking order, from bottom to top
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub enum Layer {
+    /// Wallpapers and desktop views
+    Background,
+    /// Views kept below normal ones
+    Below,
+    /// Ordinary views
+    Normal,
+    /// Views kept above normal ones
+    Above,
+    /// Fullscreen views
+    Fullscreen,
+    /// Panels, notifications and lock screens
+    Overlay,
+}
+
+const LAYERS: [Layer; 6] = [Layer::Background,
+                            Layer::Below,
+                            Layer::Normal,
+                            Layer::Above,
+                            Layer::Fullscreen,
+                            Layer::Overlay];
+
+impl Default for Layer {
+    fn default() -> Layer {
+        Layer::Normal
+    }
+}
+
+/// Error representing a rejected stacking order
+///
+/// Wraps the `Err` case of `Output::set_views`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StackingError {
+    /// Name of the output
+    pub output: String,
+    /// The rejected order, from bottom to top
+    pub views: Vec<WeakView>,
+}
+
+impl fmt::Display for StackingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "Could not stack {} views of output {}",
+               self.views.len(),
+               self.output)
+    }
+}
+
+impl ErrorTrait for StackingError {
+    fn description(&self) -> &str {
+        "Stacking order was rejected by the output"
+    }
+}
+
+/// Entry to be stacked: the view, its layer and its parent
+pub type Entry<T> = (T, Layer, Option<T>);
+
+fn push_subtree(i: usize, attached: &[Option<usize>], visited: &mut [bool], order: &mut Vec<usize>) {
+    if visited[i] {
+        return;
+    }
+    visited[i] = true;
+    order.push(i);
+    for j in 0..attached.len() {
+        if attached[j] == Some(i) {
+            push_subtree(j, attached, visited, order);
+        }
+    }
+}
+
+/// Order entries from bottom to top by their layers
+///
+/// Entries are expected in their current order, which is kept inside each
+/// layer. Children follow directly above their parent (and their earlier
+/// siblings) and are lifted into its layer, if it is higher than their own.
+pub fn stack<T: Clone + PartialEq>(entries: &[Entry<T>]) -> Vec<T> {
+    let len = entries.len();
+    let position = |parent: &T| entries.iter().position(|entry| entry.0 == *parent);
+    let parents: Vec<Option<usize>> =
+        entries.iter().map(|&(_, _, ref parent)| parent.as_ref().and_then(&position)).collect();
+
+    let mut layers: Vec<Layer> = entries.iter().map(|entry| entry.1).collect();
+    for _ in 0..len {
+        for i in 0..len {
+            if let Some(parent) = parents[i] {
+                if layers[parent] > layers[i] {
+                    layers[i] = layers[parent];
+                }
+            }
+        }
+    }
+
+    // children of a higher layer are stacked on their own
+    let attached: Vec<Option<usize>> = (0..len)
+        .map(|i| match parents[i] {
+                 Some(parent) if layers[parent] == layers[i] && parent != i => Some(parent),
+                 _ => None,
+             })
+        .collect();
+
+    let mut visited = vec![false; len];
+    let mut order = Vec::with_capacity(len);
+    for layer in &LAYERS {
+        for i in 0..len {
+            if attached[i].is_none() && layers[i] == *layer {
+                push_subtree(i, &attached, &mut visited, &mut order);
+            }
+        }
+    }
+    // parent cycles
+    for i in 0..len {
+        push_subtree(i, &attached, &mut visited, &mut order);
+    }
+
+    order.into_iter().map(|i| entries[i].0.clone()).collect()
+}
+
+/// Keeps the stacking order of all outputs consistent with their layers
+#[derive(Default)]
+pub struct Stacking {
+    layers: HashMap<WeakView, Layer>,
+}
+
+impl Stacking {
+    /// Create a new manager, that puts all views into `Layer::Normal`
+    pub fn new() -> Stacking {
+        Stacking::default()
+    }
+
+    /// Layer a view was put in
+    pub fn layer(&self, view: &View) -> Layer {
+        self.layers.get(&view.weak_reference()).cloned().unwrap_or(Layer::Normal)
+    }
+
+    /// Layer a view is stacked in, considering its fullscreen state
+    fn effective_layer(&self, view: &View) -> Layer {
+        match self.layer(view) {
+            Layer::Normal if view.state().contains(ViewState::Fullscreen) => Layer::Fullscreen,
+            layer => layer,
+        }
+    }
+
+    /// Put a view into a layer and restack its output
+    pub fn set_layer(&mut self, view: &View, layer: Layer) -> Result<(), StackingError> {
+        self.layers.insert(view.weak_reference(), layer);
+        self.restack(view.output())
+    }
+
+    /// Push the order resolved from the layers to the output
+    pub fn restack(&self, output: &Output) -> Result<(), StackingError> {
+        self.restack_with(output, |_| {})
+    }
+
+    fn restack_with<'a, F>(&self, output: &'a Output, change: F) -> Result<(), StackingError>
+        where F: FnOnce(&mut Vec<&'a View>)
+    {
+        let mut views = output.views();
+        change(&mut views);
+        let entries: Vec<Entry<&View>> = views.iter()
+            .map(|view| (*view, self.effective_layer(view), view.parent()))
+            .collect();
+
+        output.set_views(stack(&entries)).map_err(|views| {
+            StackingError {
+                output: output.name().into_owned(),
+                views: views.into_iter().map(View::weak_reference).collect(),
+            }
+        })
+    }
+
+    /// Raise a view to the top of its layer, together with its children
+    pub fn raise(&self, view: &View) -> Result<(), StackingError> {
+        self.restack_with(view.output(), |views| {
+            views.retain(|other| *other != view);
+            views.push(view);
+        })
+    }
+
+    /// Lower a view to the bottom of its layer, together with its children
+    pub fn lower(&self, view: &View) -> Result<(), StackingError> {
+        self.restack_with(view.output(), |views| {
+            views.retain(|other| *other != view);
+            views.insert(0, view);
+        })
+    }
+
+    /// Forward of `Callback::view_created`
+    ///
+    /// Raises the new view.
+    pub fn view_created(&mut self, view: &View) -> Result<(), StackingError> {
+        self.raise(view)
+    }
+
+    /// Forward of `Callback::view_destroyed`
+    pub fn view_destroyed(&mut self, view: &View) {
+        self.layers.remove(&view.weak_reference());
+    }
+
+    /// Forward of `Callback::view_request_state`
+    ///
+    /// Restacks the output, if the fullscreen state changed. Call it after
+    /// the state was applied.
+    pub fn view_request_state(&mut self, view: &View, state: ViewState::Flags, _toggle: bool)
+                              -> Result<(), StackingError> {
+        if state.contains(ViewState::Fullscreen) {
+            self.restack(view.output())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layers() {
+        let entries = [(1, Layer::Overlay, None),
+                       (2, Layer::Normal, None),
+                       (3, Layer::Background, None),
+                       (4, Layer::Normal, None),
+                       (5, Layer::Fullscreen, None)];
+        assert_eq!(stack(&entries), vec![3, 2, 4, 5, 1]);
+    }
+
+    #[test]
+    fn test_children_stay_above_parent() {
+        let entries = [(1, Layer::Normal, None),
+                       (2, Layer::Normal, Some(1)),
+                       (3, Layer::Normal, None),
+                       (4, Layer::Normal, Some(2)),
+                       (5, Layer::Below, Some(3)),
+                       (6, Layer::Overlay, Some(1))];
+        assert_eq!(stack(&entries), vec![1, 2, 4, 3, 5, 6]);
+    }
+
+    #[test]
+    fn test_parent_cycle() {
+        let entries = [(1, Layer::Normal, Some(2)), (2, Layer::Normal, Some(1))];
+        assert_eq!(stack(&entries).len(), 2);
+    }
+}
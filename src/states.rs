@@ -0,0 +1,266 @@
+//! Fullscreen and maximised states
+//!
+//! `States` applies the `ViewState::Fullscreen` and `ViewState::Maximized`
+//! requests of clients. Before a view enters a state its geometry,
+//! visibility and position in the stacking order are saved and restored,
+//! when the state is left again. Maximised views fill the usable area of
+//! their output, fullscreen views either cover the whole output including
+//! panels or only the usable area, depending on the `PanelPolicy`.
+//!
+//! If the output of a view was unplugged in between, the view keeps its
+//! saved size and is moved into the area of its new output.
+//!
+//! Forward `view_request_state` and `view_destroyed`.
+
+use {Geometry, Point, ResizeEdge, View, ViewState, Visibility, WeakOutput, WeakView};
+use placement;
+
+use std::collections::HashMap;
+
+/// What happens to panels while a view is fullscreen
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub enum PanelPolicy {
+    /// Cover the whole output including panels
+    Hide,
+    /// Only cover the usable area, panels stay visible
+    Keep,
+}
+
+/// Geometry and visibility of a view before entering a state
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Snapshot {
+    geometry: Geometry,
+    visibility: Visibility::Flags,
+}
+
+impl Snapshot {
+    /// Snapshot to apply when restoring
+    ///
+    /// Views `moved` to another output in between keep their size, but are
+    /// moved into `area` and take the `visibility` of their new output.
+    fn restored(&self, moved: bool, area: Geometry, visibility: Visibility::Flags) -> Snapshot {
+        if moved {
+            Snapshot {
+                geometry: placement::fit(self.geometry, area),
+                visibility: visibility,
+            }
+        } else {
+            *self
+        }
+    }
+}
+
+/// Item directly above `item` in a list ordered from bottom to top
+fn above<'a, T: PartialEq>(items: &'a [T], item: &T) -> Option<&'a T> {
+    items.iter().position(|other| *other == *item).and_then(|i| items.get(i + 1))
+}
+
+#[derive(Clone)]
+struct Saved {
+    output: WeakOutput,
+    snapshot: Snapshot,
+    /// View directly above in the stacking order
+    above: Option<WeakView>,
+}
+
+impl Saved {
+    fn of(view: &View) -> Saved {
+        let views = view.output().views();
+        Saved {
+            output: view.output().weak_reference(),
+            snapshot: Snapshot {
+                geometry: view.geometry(),
+                visibility: view.visibility(),
+            },
+            above: above(&views, &view).map(|above| above.weak_reference()),
+        }
+    }
+
+    fn restore(&self, view: &View, area: Geometry) {
+        let moved = self.output != *view.output();
+        let snapshot = self.snapshot.restored(moved, area, view.output().visibility());
+        view.set_geometry(ResizeEdge::Null, snapshot.geometry);
+        view.set_visibility(snapshot.visibility);
+        if moved {
+            return;
+        }
+
+        let restacked = self.above
+            .as_ref()
+            .and_then(|above| {
+                above.run(|above| if above.output() == view.output() {
+                              view.send_below(above);
+                              true
+                          } else {
+                              false
+                          })
+            })
+            .unwrap_or(false);
+        if !restacked {
+            view.bring_to_front();
+        }
+    }
+}
+
+/// Applies fullscreen and maximised states and restores views afterwards
+pub struct States {
+    panels: PanelPolicy,
+    fullscreen: HashMap<WeakView, Saved>,
+    maximized: HashMap<WeakView, Saved>,
+}
+
+impl States {
+    /// Create a new helper with the given policy for panels
+    pub fn new(panels: PanelPolicy) -> States {
+        States {
+            panels: panels,
+            fullscreen: HashMap::new(),
+            maximized: HashMap::new(),
+        }
+    }
+
+    /// Policy for panels of fullscreen views
+    pub fn panel_policy(&self) -> PanelPolicy {
+        self.panels
+    }
+
+    /// Change the policy for panels, applies to views entering fullscreen
+    /// afterwards
+    pub fn set_panel_policy(&mut self, panels: PanelPolicy) {
+        self.panels = panels;
+    }
+
+    /// Geometry of the output
+    fn output_area(view: &View) -> Geometry {
+        Geometry {
+            origin: Point { x: 0, y: 0 },
+            size: view.output().virtual_resolution(),
+        }
+    }
+
+    /// Enter or leave fullscreen
+    ///
+    /// `usable_area` is the area of the output not covered by panels, e.g.
+    /// from `LayoutEngine::usable_area`.
+    pub fn set_fullscreen(&mut self, view: &View, fullscreen: bool, usable_area: Geometry) {
+        let weak = view.weak_reference();
+        if fullscreen {
+            if !self.fullscreen.contains_key(&weak) {
+                let saved = self.maximized.get(&weak).cloned().unwrap_or_else(|| Saved::of(view));
+                self.fullscreen.insert(weak, saved);
+            }
+            let geometry = match self.panels {
+                PanelPolicy::Hide => States::output_area(view),
+                PanelPolicy::Keep => usable_area,
+            };
+            view.set_state(ViewState::Fullscreen, true);
+            view.set_geometry(ResizeEdge::Null, geometry);
+            view.bring_to_front();
+        } else if let Some(saved) = self.fullscreen.remove(&weak) {
+            view.set_state(ViewState::Fullscreen, false);
+            if self.maximized.contains_key(&weak) {
+                view.set_geometry(ResizeEdge::Null, usable_area);
+            } else {
+                saved.restore(view, usable_area);
+            }
+        }
+    }
+
+    /// Maximise a view or restore it
+    ///
+    /// `usable_area` is the area of the output not covered by panels, e.g.
+    /// from `LayoutEngine::usable_area`.
+    pub fn set_maximized(&mut self, view: &View, maximized: bool, usable_area: Geometry) {
+        let weak = view.weak_reference();
+        let fullscreen = self.fullscreen.contains_key(&weak);
+        if maximized {
+            if !self.maximized.contains_key(&weak) {
+                let saved = self.fullscreen.get(&weak).cloned().unwrap_or_else(|| Saved::of(view));
+                self.maximized.insert(weak, saved);
+            }
+            view.set_state(ViewState::Maximized, true);
+            // fullscreen takes precedence
+            if !fullscreen {
+                view.set_geometry(ResizeEdge::Null, usable_area);
+            }
+        } else if let Some(saved) = self.maximized.remove(&weak) {
+            view.set_state(ViewState::Maximized, false);
+            if !fullscreen {
+                saved.restore(view, usable_area);
+            }
+        }
+    }
+
+    /// Check if a view is fullscreen by this helper
+    pub fn is_fullscreen(&self, view: &View) -> bool {
+        self.fullscreen.contains_key(&view.weak_reference())
+    }
+
+    /// Check if a view is maximised by this helper
+    pub fn is_maximized(&self, view: &View) -> bool {
+        self.maximized.contains_key(&view.weak_reference())
+    }
+
+    /// Forward of `Callback::view_request_state`
+    ///
+    /// `usable_area` is the area of the output not covered by panels, e.g.
+    /// from `LayoutEngine::usable_area`.
+    pub fn view_request_state(&mut self, view: &View, state: ViewState::Flags, toggle: bool,
+                              usable_area: Geometry) {
+        if state.contains(ViewState::Maximized) {
+            self.set_maximized(view, toggle, usable_area);
+        }
+        if state.contains(ViewState::Fullscreen) {
+            self.set_fullscreen(view, toggle, usable_area);
+        }
+    }
+
+    /// Forward of `Callback::view_destroyed`
+    pub fn view_destroyed(&mut self, view: &View) {
+        let weak = view.weak_reference();
+        self.fullscreen.remove(&weak);
+        self.maximized.remove(&weak);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_util::geometry;
+
+    #[test]
+    fn test_restored_on_the_same_output() {
+        let snapshot = Snapshot {
+            geometry: geometry(1800, 0, 400, 300),
+            visibility: Visibility::Slot2,
+        };
+        let area = geometry(0, 30, 1280, 690);
+        assert_eq!(snapshot.restored(false, area, Visibility::Slot1), snapshot);
+    }
+
+    #[test]
+    fn test_restored_after_unplugging_the_output() {
+        let snapshot = Snapshot {
+            geometry: geometry(1800, 0, 400, 300),
+            visibility: Visibility::Slot2,
+        };
+        // saved on a larger output
+        let area = geometry(0, 30, 1280, 690);
+        assert_eq!(snapshot.restored(true, area, Visibility::Slot1),
+                   Snapshot {
+                       geometry: geometry(880, 30, 400, 300),
+                       visibility: Visibility::Slot1,
+                   });
+    }
+
+    #[test]
+    fn test_above() {
+        let stack = [1, 2, 3];
+        assert_eq!(above(&stack, &1), Some(&2));
+        assert_eq!(above(&stack, &2), Some(&3));
+        // top most view is restored to the front
+        assert_eq!(above(&stack, &3), None);
+        assert_eq!(above(&stack, &4), None);
+    }
+}
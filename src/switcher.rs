@@ -0,0 +1,173 @@
+//! Alt-Tab like cycling through views
+//!
+//! While a modifier is held, every press of the cycle key selects the next
+//! view in most recently used order of the `FocusManager` and raises it. As
+//! soon as the modifier is released the selection is focused, which also
+//! makes it the most recently used view. Escape aborts the cycle and
+//! restores the stacking order of the output. Holding shift cycles
+//! backwards.
+//!
+//! Forward `keyboard_key` and `view_destroyed`.
+
+use {Key, KeyState, Modifier, Modifiers, Output, View, WeakOutput, WeakView};
+use focus::FocusManager;
+use input::keyboard::{self, Keysym, Keysyms};
+
+use std::usize;
+
+/// Index of the next selection of a cycle through `len` views
+fn step(index: usize, len: usize, backwards: bool) -> usize {
+    if len == 0 {
+        0
+    } else if backwards {
+        (index + len - 1) % len
+    } else {
+        (index + 1) % len
+    }
+}
+
+struct Cycle {
+    output: WeakOutput,
+    views: Vec<WeakView>,
+    index: usize,
+    /// Stacking order of the output, when the cycle started
+    stacking: Vec<WeakView>,
+}
+
+/// Cycles through views while a modifier is held
+pub struct Switcher {
+    modifier: Modifier::Flags,
+    key: Keysym,
+    cycle: Option<Cycle>,
+}
+
+impl Switcher {
+    /// Create a new switcher cycling on presses of `key` while `modifier` is
+    /// held, e.g. `Modifier::Alt` and `Keysyms::KEY_Tab`
+    pub fn new(modifier: Modifier::Flags, key: Keysym) -> Switcher {
+        Switcher {
+            modifier: modifier,
+            key: key,
+            cycle: None,
+        }
+    }
+
+    /// Check if a cycle is in progress
+    pub fn is_active(&self) -> bool {
+        self.cycle.is_some()
+    }
+
+    /// Currently selected view of the cycle
+    pub fn selection(&self) -> Option<WeakView> {
+        self.cycle.as_ref().and_then(|cycle| cycle.views.get(cycle.index).cloned())
+    }
+
+    /// Select the next view of the output, starting a cycle if none is in
+    /// progress
+    pub fn next(&mut self, output: &Output, focus: &FocusManager, backwards: bool) {
+        if self.cycle.is_none() {
+            self.cycle = Some(Cycle {
+                                  output: output.weak_reference(),
+                                  views: focus.history(output),
+                                  index: 0,
+                                  stacking: output.views().into_iter().map(View::weak_reference).collect(),
+                              });
+        }
+
+        if let Some(ref mut cycle) = self.cycle {
+            cycle.index = step(cycle.index, cycle.views.len(), backwards);
+            if let Some(view) = cycle.views.get(cycle.index) {
+                view.run(|view| view.bring_to_front());
+            }
+        }
+    }
+
+    /// Focus the selected view and end the cycle
+    pub fn commit(&mut self, focus: &mut FocusManager) {
+        if let Some(view) = self.selection() {
+            view.run(|view| focus.focus(view));
+        }
+        self.cycle = None;
+    }
+
+    /// End the cycle and restore the stacking order
+    pub fn cancel(&mut self) {
+        if let Some(cycle) = self.cycle.take() {
+            cycle.output.run(|output| {
+                let mut views = output.views();
+                // views created during the cycle stay on top
+                let position = |view: &View| cycle.stacking.iter().position(|other| *other == *view);
+                views.sort_by_key(|view| position(*view).unwrap_or(usize::MAX));
+                if output.set_views(views).is_err() {
+                    warn!("Could not restore stacking order of output {}", output.name());
+                }
+            });
+        }
+    }
+
+    /// Forward of `Callback::keyboard_key`
+    ///
+    /// Returns `true` if the event was consumed.
+    pub fn keyboard_key(&mut self, view: Option<&View>, modifiers: Modifiers, key: Key, state: KeyState,
+                        focus: &mut FocusManager)
+                        -> bool {
+        if self.is_active() && !modifiers.mods.contains(self.modifier) {
+            self.commit(focus);
+            return false;
+        }
+
+        let sym = keyboard::keysym_for_key(key, Modifiers::empty());
+        if self.is_active() && sym == Keysyms::KEY_Escape {
+            if state == KeyState::Pressed {
+                self.cancel();
+            }
+            return true;
+        }
+        if sym != self.key || !modifiers.mods.contains(self.modifier) {
+            return false;
+        }
+        if state == KeyState::Released {
+            return self.is_active();
+        }
+
+        let output = match (view, focus.focused()) {
+            (Some(view), _) => Some(view.output().weak_reference()),
+            (None, Some(focused)) => focused.run(|view| view.output().weak_reference()),
+            (None, None) => None,
+        };
+        let backwards = modifiers.mods.contains(Modifier::Shift);
+        match output {
+            Some(output) => {
+                output.run(|output| self.next(output, focus, backwards));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Forward of `Callback::view_destroyed`
+    pub fn view_destroyed(&mut self, view: &View) {
+        if let Some(ref mut cycle) = self.cycle {
+            if let Some(position) = cycle.views.iter().position(|other| *other == *view) {
+                cycle.views.remove(position);
+                if position < cycle.index || cycle.index >= cycle.views.len() {
+                    cycle.index = cycle.index.saturating_sub(1);
+                }
+            }
+            cycle.stacking.retain(|other| *other != *view);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_step_wraps_around() {
+        assert_eq!(step(0, 3, false), 1);
+        assert_eq!(step(2, 3, false), 0);
+        assert_eq!(step(0, 3, true), 2);
+        assert_eq!(step(0, 0, false), 0);
+    }
+}
@@ -0,0 +1,11 @@
+//! Helpers shared by the unit tests
+
+use {Geometry, Point, Size};
+
+/// Geometry from its origin and size
+pub fn geometry(x: i32, y: i32, w: u32, h: u32) -> Geometry {
+    Geometry {
+        origin: Point { x: x, y: y },
+        size: Size { w: w, h: h },
+    }
+}
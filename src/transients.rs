@@ -0,0 +1,172 @@
+//! Dialog placement and focus policy
+//!
+//! Views with a parent, that are not popups, are treated as dialogs of their
+//! parent. Dialogs open centred over their parent on the output of the
+//! parent and follow it: they keep their offset, when the parent moves, are
+//! raised with it and share its visibility. While a dialog of type
+//! `ViewType::Modal` is open, focusing its parent focuses the dialog
+//! instead.
+//!
+//! Forward `view_created`, `view_destroyed` and `view_focus`. Call
+//! `Transients::parent_changed` after changing the geometry or visibility of
+//! a view, `Transients::raise` raises a view together with its parents and
+//! dialogs through `Stacking`.
+
+use {Geometry, Point, ResizeEdge, View, ViewType, WeakView};
+use placement;
+use stacking::{Stacking, StackingError};
+
+/// Check if a view is a dialog of its parent
+pub fn is_dialog(view: &View) -> bool {
+    view.parent().is_some() && !view.view_type().contains(ViewType::Popup) && view.positioner().is_none()
+}
+
+struct Dialog {
+    view: WeakView,
+    parent: WeakView,
+    modal: bool,
+    /// Offset of the origin relative to the parent
+    offset: Point,
+}
+
+/// Keeps dialogs together with their parents
+#[derive(Default)]
+pub struct Transients {
+    dialogs: Vec<Dialog>,
+}
+
+impl Transients {
+    /// Create a new policy without any dialogs
+    pub fn new() -> Transients {
+        Transients::default()
+    }
+
+    /// Dialogs of a view, in the order they were opened
+    pub fn dialogs(&self, parent: &View) -> Vec<WeakView> {
+        self.dialogs
+            .iter()
+            .filter(|dialog| dialog.parent == *parent)
+            .map(|dialog| dialog.view.clone())
+            .collect()
+    }
+
+    /// Most recently opened modal dialog of a view, including modal dialogs
+    /// of its dialogs
+    pub fn modal(&self, parent: &View) -> Option<WeakView> {
+        let mut modal = None;
+        let mut current = parent.weak_reference();
+        loop {
+            let next = self.dialogs
+                .iter()
+                .rev()
+                .find(|dialog| dialog.parent == current && dialog.modal)
+                .map(|dialog| dialog.view.clone());
+            match next {
+                Some(next) => {
+                    modal = Some(next.clone());
+                    current = next;
+                }
+                None => return modal,
+            }
+        }
+    }
+
+    /// Raise a view to the top of its layer
+    ///
+    /// The parents of a dialog are raised first, `Stacking` keeps dialogs
+    /// directly above their parent.
+    pub fn raise(&self, view: &View, stacking: &Stacking) -> Result<(), StackingError> {
+        let mut root = view.weak_reference();
+        loop {
+            let parent = self.dialogs
+                .iter()
+                .find(|dialog| dialog.view == root)
+                .map(|dialog| dialog.parent.clone());
+            match parent {
+                Some(parent) => root = parent,
+                None => break,
+            }
+        }
+
+        if root != *view {
+            root.run(|root| stacking.raise(root)).unwrap_or(Ok(()))?;
+        }
+        stacking.raise(view)
+    }
+
+    /// Move, show or hide the dialogs of a view with it
+    ///
+    /// Call after changing the geometry or visibility of the view.
+    pub fn parent_changed(&self, parent: &View) {
+        let origin = parent.geometry().origin;
+        for dialog in self.dialogs.iter().filter(|dialog| dialog.parent == *parent) {
+            dialog.view.run(|view| {
+                let geometry = Geometry {
+                    origin: Point {
+                        x: origin.x + dialog.offset.x,
+                        y: origin.y + dialog.offset.y,
+                    },
+                    size: view.geometry().size,
+                };
+                view.set_geometry(ResizeEdge::Null, geometry);
+                view.set_visibility(parent.visibility());
+                self.parent_changed(view);
+            });
+        }
+    }
+
+    /// Forward of `Callback::view_created`
+    ///
+    /// Returns `true` if the view is a dialog, which was placed over its
+    /// parent.
+    pub fn view_created(&mut self, view: &View) -> bool {
+        if !is_dialog(view) {
+            return false;
+        }
+        let parent = match view.parent() {
+            Some(parent) => parent,
+            None => return false,
+        };
+
+        if parent.output() != view.output() {
+            view.set_output(parent.output());
+        }
+        view.set_visibility(parent.visibility());
+        let parent_geometry = parent.geometry();
+        let area = Geometry {
+            origin: Point { x: 0, y: 0 },
+            size: view.output().virtual_resolution(),
+        };
+        let geometry = placement::centered(parent_geometry, view.geometry().size, area);
+        view.set_geometry(ResizeEdge::Null, geometry);
+        view.bring_above(parent);
+
+        self.dialogs.push(Dialog {
+                              view: view.weak_reference(),
+                              parent: parent.weak_reference(),
+                              modal: view.view_type().contains(ViewType::Modal),
+                              offset: Point {
+                                  x: geometry.origin.x - parent_geometry.origin.x,
+                                  y: geometry.origin.y - parent_geometry.origin.y,
+                              },
+                          });
+        true
+    }
+
+    /// Forward of `Callback::view_destroyed`
+    pub fn view_destroyed(&mut self, view: &View) {
+        self.dialogs.retain(|dialog| dialog.view != *view && dialog.parent != *view);
+    }
+
+    /// Forward of `Callback::view_focus`
+    ///
+    /// Moves the focus from a view to its modal dialog.
+    pub fn view_focus(&mut self, view: &View, focus: bool) {
+        if !focus {
+            return;
+        }
+        if let Some(modal) = self.modal(view) {
+            modal.run(|modal| modal.focus());
+        }
+    }
+}
@@ -0,0 +1,898 @@
+//! Manual tiling with a container tree
+//!
+//! A `Tree` holds the tiled views of one output and workspace in the manner
+//! of i3. Views are the leaves of nested `Container`s, which either split
+//! their area horizontally or vertically or show only one child at a time as
+//! tabbed or stacked container. Every container remembers its focused child,
+//! the focused view of the tree is found by following them from the root.
+//!
+//! The tree is generic over the type identifying a view. `Tree<WeakView>`
+//! can be applied to the views directly, hiding the inactive children of
+//! tabbed and stacked containers through their `Visibility`. Use `Tree::map`
+//! to convert it into something, that can be serialized (enabled by feature
+//! `serialization`), e.g. app ids for restoring a session.
+//!
+//! `Trees` keeps one tree per output and workspace of `Workspaces`.
+//!
+//! No space is reserved for tab bars or titles, shrink the area passed to
+//! `Tree::arrange` to draw your own.
+
+use {Geometry, Output, Point, ResizeEdge, Size, View, Visibility, WeakOutput, WeakView};
+use workspaces::Workspaces;
+
+use std::collections::HashMap;
+use std::mem;
+
+/// Smallest share of a split a child may be resized to
+const MIN_SHARE: f32 = 0.05;
+
+/// How a container arranges its children
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub enum ContainerLayout {
+    /// Children are placed from left to right
+    Horizontal,
+    /// Children are placed from top to bottom
+    Vertical,
+    /// Only the focused child is visible, switched with left and right
+    Tabbed,
+    /// Only the focused child is visible, switched with up and down
+    Stacked,
+}
+
+impl ContainerLayout {
+    fn is_horizontal(&self) -> bool {
+        match *self {
+            ContainerLayout::Horizontal | ContainerLayout::Tabbed => true,
+            ContainerLayout::Vertical | ContainerLayout::Stacked => false,
+        }
+    }
+}
+
+/// Direction for focus, move and resize operations
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub enum Direction {
+    /// Towards the left border
+    Left,
+    /// Towards the right border
+    Right,
+    /// Towards the top border
+    Up,
+    /// Towards the bottom border
+    Down,
+}
+
+impl Direction {
+    fn is_horizontal(&self) -> bool {
+        *self == Direction::Left || *self == Direction::Right
+    }
+
+    fn is_forward(&self) -> bool {
+        *self == Direction::Right || *self == Direction::Down
+    }
+}
+
+/// Index of the sibling next to `index` in the given direction
+fn neighbour(index: usize, len: usize, direction: Direction) -> Option<usize> {
+    if direction.is_forward() {
+        if index + 1 < len { Some(index + 1) } else { None }
+    } else {
+        index.checked_sub(1)
+    }
+}
+
+/// Split `length` into pieces of `(offset, length)` proportional to
+/// `weights`, the last piece takes the rounding remainder
+fn split_weighted(length: u32, weights: &[f32]) -> Vec<(u32, u32)> {
+    let total: f32 = weights.iter().sum();
+    let mut offset = 0;
+    weights.iter()
+        .enumerate()
+        .map(|(i, weight)| {
+                 let piece = if i + 1 == weights.len() {
+                     length.saturating_sub(offset)
+                 } else {
+                     (length as f32 * weight / total) as u32
+                 };
+                 let result = (offset, piece);
+                 offset += piece;
+                 result
+             })
+        .collect()
+}
+
+/// Node of a `Tree`
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub enum Node<T> {
+    /// A single view
+    View(T),
+    /// A nested container
+    Container(Container<T>),
+}
+
+/// Container of views and other containers
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub struct Container<T> {
+    layout: ContainerLayout,
+    children: Vec<Node<T>>,
+    weights: Vec<f32>,
+    focus: usize,
+}
+
+impl<T> Container<T> {
+    fn new(layout: ContainerLayout) -> Container<T> {
+        Container {
+            layout: layout,
+            children: Vec::new(),
+            weights: Vec::new(),
+            focus: 0,
+        }
+    }
+
+    /// Layout of the container
+    pub fn layout(&self) -> ContainerLayout {
+        self.layout
+    }
+
+    /// Children of the container in order
+    pub fn children(&self) -> &[Node<T>] {
+        &self.children
+    }
+
+    /// Share of the containers area each child gets, in the same order as
+    /// `Container::children`
+    ///
+    /// Only used by horizontal and vertical containers.
+    pub fn shares(&self) -> Vec<f32> {
+        let total: f32 = self.weights.iter().sum();
+        self.weights.iter().map(|weight| weight / total).collect()
+    }
+
+    /// Focused child of the container
+    pub fn focused(&self) -> Option<&Node<T>> {
+        self.children.get(self.focus)
+    }
+
+    /// Insert a child, that gets an equal share of the area
+    fn insert(&mut self, index: usize, node: Node<T>) {
+        let weight = if self.weights.is_empty() {
+            1.0
+        } else {
+            self.weights.iter().sum::<f32>() / self.weights.len() as f32
+        };
+        self.children.insert(index, node);
+        self.weights.insert(index, weight);
+        if index <= self.focus && self.children.len() > 1 {
+            self.focus += 1;
+        }
+    }
+
+    fn remove(&mut self, index: usize) -> Node<T> {
+        self.weights.remove(index);
+        let node = self.children.remove(index);
+        if index < self.focus || self.focus >= self.children.len() {
+            self.focus = self.focus.saturating_sub(1);
+        }
+        node
+    }
+
+    fn map<U, F: FnMut(&T) -> U>(&self, f: &mut F) -> Container<U> {
+        Container {
+            layout: self.layout,
+            children: self.children
+                .iter()
+                .map(|child| match *child {
+                         Node::View(ref view) => Node::View(f(view)),
+                         Node::Container(ref container) => Node::Container(container.map(f)),
+                     })
+                .collect(),
+            weights: self.weights.clone(),
+            focus: self.focus,
+        }
+    }
+
+    fn arrange(&self, area: Geometry, visible: bool, placements: &mut Vec<Placement<T>>)
+        where T: Clone
+    {
+        let cells: Vec<Geometry> = match self.layout {
+            ContainerLayout::Horizontal => {
+                split_weighted(area.size.w, &self.weights)
+                    .into_iter()
+                    .map(|(offset, width)| {
+                             Geometry {
+                                 origin: Point {
+                                     x: area.origin.x + offset as i32,
+                                     y: area.origin.y,
+                                 },
+                                 size: Size {
+                                     w: width,
+                                     h: area.size.h,
+                                 },
+                             }
+                         })
+                    .collect()
+            }
+            ContainerLayout::Vertical => {
+                split_weighted(area.size.h, &self.weights)
+                    .into_iter()
+                    .map(|(offset, height)| {
+                             Geometry {
+                                 origin: Point {
+                                     x: area.origin.x,
+                                     y: area.origin.y + offset as i32,
+                                 },
+                                 size: Size {
+                                     w: area.size.w,
+                                     h: height,
+                                 },
+                             }
+                         })
+                    .collect()
+            }
+            ContainerLayout::Tabbed |
+            ContainerLayout::Stacked => vec![area; self.children.len()],
+        };
+
+        for (i, (child, cell)) in self.children.iter().zip(cells).enumerate() {
+            let visible = visible &&
+                          match self.layout {
+                              ContainerLayout::Tabbed | ContainerLayout::Stacked => i == self.focus,
+                              _ => true,
+                          };
+            match *child {
+                Node::View(ref view) => {
+                    placements.push(Placement {
+                                        view: view.clone(),
+                                        geometry: cell,
+                                        visible: visible,
+                                    })
+                }
+                Node::Container(ref container) => container.arrange(cell, visible, placements),
+            }
+        }
+    }
+}
+
+/// Result of `Tree::arrange` for a single view
+#[derive(Clone, Debug, PartialEq)]
+pub struct Placement<T> {
+    /// The view
+    pub view: T,
+    /// Geometry of the view
+    pub geometry: Geometry,
+    /// `false` for views hidden behind the focused child of a tabbed or
+    /// stacked container
+    pub visible: bool,
+}
+
+/// Container tree of a single output and workspace
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub struct Tree<T> {
+    root: Container<T>,
+}
+
+impl<T: PartialEq> Tree<T> {
+    /// Create an empty tree, whose root container uses the given layout
+    pub fn new(layout: ContainerLayout) -> Tree<T> {
+        Tree { root: Container::new(layout) }
+    }
+
+    /// Root container of the tree
+    pub fn root(&self) -> &Container<T> {
+        &self.root
+    }
+
+    /// Check if the tree contains no views
+    pub fn is_empty(&self) -> bool {
+        self.root.children.is_empty()
+    }
+
+    /// Check if the tree contains the given view
+    pub fn contains(&self, view: &T) -> bool {
+        self.path(view).is_some()
+    }
+
+    /// All views of the tree depth-first
+    pub fn views(&self) -> Vec<&T> {
+        fn collect<'a, T>(container: &'a Container<T>, views: &mut Vec<&'a T>) {
+            for child in &container.children {
+                match *child {
+                    Node::View(ref view) => views.push(view),
+                    Node::Container(ref container) => collect(container, views),
+                }
+            }
+        }
+
+        let mut views = Vec::new();
+        collect(&self.root, &mut views);
+        views
+    }
+
+    /// The focused view
+    pub fn focused(&self) -> Option<&T> {
+        self.focus_path().map(|path| match *self.node(&path) {
+                                  Node::View(ref view) => view,
+                                  Node::Container(_) => unreachable!(),
+                              })
+    }
+
+    /// Focus the given view
+    ///
+    /// Returns `false` if the view is not part of the tree
+    pub fn focus(&mut self, view: &T) -> bool {
+        match self.path(view) {
+            Some(path) => {
+                self.set_focus_path(&path);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Insert a new view next to the focused view and focus it
+    pub fn insert(&mut self, view: T) {
+        let (mut path, index) = match self.focus_path() {
+            Some(mut path) => {
+                let index = path.pop().unwrap() + 1;
+                (path, index)
+            }
+            None => (Vec::new(), 0),
+        };
+        self.container_mut(&path).insert(index, Node::View(view));
+        path.push(index);
+        self.set_focus_path(&path);
+    }
+
+    /// Remove a view, containers left empty are removed as well
+    ///
+    /// Returns `false` if the view is not part of the tree
+    pub fn remove(&mut self, view: &T) -> bool {
+        match self.path(view) {
+            Some(path) => {
+                self.take(&path);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Move the focus to the next view in the given direction
+    ///
+    /// Returns the newly focused view or `None` if there is no view in that
+    /// direction
+    pub fn focus_direction(&mut self, direction: Direction) -> Option<&T> {
+        let path = match self.focus_path() {
+            Some(path) => path,
+            None => return None,
+        };
+
+        for depth in (0..path.len()).rev() {
+            let target = {
+                let container = self.container(&path[..depth]);
+                if container.layout.is_horizontal() != direction.is_horizontal() {
+                    continue;
+                }
+                match neighbour(path[depth], container.children.len(), direction) {
+                    Some(target) => target,
+                    None => continue,
+                }
+            };
+
+            let mut target_path = path[..depth].to_vec();
+            target_path.push(target);
+            let target_path = self.descend_focus(target_path);
+            self.set_focus_path(&target_path);
+            return self.focused();
+        }
+        None
+    }
+
+    /// Move the focused view in the given direction
+    ///
+    /// The view is swapped with a neighbouring view, moved into a
+    /// neighbouring container or, at the border of its container, moved out
+    /// next to it. Returns `false` if the view could not be moved.
+    pub fn move_direction(&mut self, direction: Direction) -> bool {
+        let path = match self.focus_path() {
+            Some(path) => path,
+            None => return false,
+        };
+        let (last, parent_path) = path.split_last().unwrap();
+
+        for depth in (0..path.len()).rev() {
+            let ancestor = &path[..depth];
+            let len = {
+                let container = self.container(ancestor);
+                if container.layout.is_horizontal() != direction.is_horizontal() {
+                    continue;
+                }
+                container.children.len()
+            };
+            let index = path[depth];
+
+            if depth + 1 < path.len() {
+                // leave the nested container to the side of it
+                let at = if direction.is_forward() { index + 1 } else { index };
+                let mut old_parent = parent_path.to_vec();
+                if !direction.is_forward() {
+                    old_parent[depth] += 1;
+                }
+
+                let node = self.container_mut(parent_path).remove(*last);
+                self.container_mut(ancestor).insert(at, node);
+                let before = self.container(ancestor).children.len();
+                self.collapse(&old_parent);
+                let collapsed = self.container(ancestor).children.len() < before;
+
+                let mut new_path = ancestor.to_vec();
+                new_path.push(if collapsed && direction.is_forward() { at - 1 } else { at });
+                self.set_focus_path(&new_path);
+                return true;
+            }
+
+            let target = match neighbour(index, len, direction) {
+                Some(target) => target,
+                None => continue,
+            };
+            let into_container = match self.container(ancestor).children[target] {
+                Node::Container(_) => true,
+                Node::View(_) => false,
+            };
+
+            if into_container {
+                let node = self.container_mut(ancestor).remove(index);
+                let mut new_path = ancestor.to_vec();
+                new_path.push(if target > index { target - 1 } else { target });
+                let at = {
+                    let container = self.container_mut(&new_path);
+                    let at = if direction.is_forward() { 0 } else { container.children.len() };
+                    container.insert(at, node);
+                    at
+                };
+                new_path.push(at);
+                self.set_focus_path(&new_path);
+            } else {
+                let container = self.container_mut(ancestor);
+                container.children.swap(index, target);
+                container.weights.swap(index, target);
+                container.focus = target;
+            }
+            return true;
+        }
+        false
+    }
+
+    /// Move a view next to another view, possibly into another container
+    ///
+    /// The moved view gets focused. Returns `false` if one of the views is
+    /// not part of the tree.
+    pub fn move_to(&mut self, view: &T, target: &T) -> bool {
+        if view == target || !self.contains(target) {
+            return false;
+        }
+        let node = match self.path(view) {
+            Some(path) => self.take(&path),
+            None => return false,
+        };
+
+        let mut path = self.path(target).unwrap();
+        let index = path.pop().unwrap() + 1;
+        self.container_mut(&path).insert(index, node);
+        path.push(index);
+        self.set_focus_path(&path);
+        true
+    }
+
+    /// Split the focused view, so that views inserted next to it are
+    /// arranged with the given layout
+    ///
+    /// If the view is the only child of its container, the layout of the
+    /// container is changed instead.
+    pub fn split(&mut self, layout: ContainerLayout) {
+        let mut path = match self.focus_path() {
+            Some(path) => path,
+            None => {
+                self.root.layout = layout;
+                return;
+            }
+        };
+        let index = path.pop().unwrap();
+
+        let container = self.container_mut(&path);
+        if container.children.len() == 1 {
+            container.layout = layout;
+            return;
+        }
+        let node = mem::replace(&mut container.children[index], Node::Container(Container::new(layout)));
+        if let Node::Container(ref mut split) = container.children[index] {
+            split.insert(0, node);
+        }
+    }
+
+    /// Change the layout of the container holding the focused view
+    pub fn set_layout(&mut self, layout: ContainerLayout) {
+        let mut path = self.focus_path().unwrap_or_else(Vec::new);
+        path.pop();
+        self.container_mut(&path).layout = layout;
+    }
+
+    /// Grow the focused view (or the split containing it) in the given
+    /// direction by `amount` of the split, shrinking its neighbour
+    ///
+    /// Negative amounts shrink the view instead. No child is resized below
+    /// 5% of its split. Returns `false`, if there is no neighbour in that
+    /// direction.
+    pub fn resize(&mut self, direction: Direction, amount: f32) -> bool {
+        let path = match self.focus_path() {
+            Some(path) => path,
+            None => return false,
+        };
+
+        for depth in (0..path.len()).rev() {
+            let container = self.container_mut(&path[..depth]);
+            let matches = match container.layout {
+                ContainerLayout::Horizontal => direction.is_horizontal(),
+                ContainerLayout::Vertical => !direction.is_horizontal(),
+                ContainerLayout::Tabbed | ContainerLayout::Stacked => false,
+            };
+            if !matches {
+                continue;
+            }
+            let index = path[depth];
+            let target = match neighbour(index, container.children.len(), direction) {
+                Some(target) => target,
+                None => continue,
+            };
+
+            let total: f32 = container.weights.iter().sum();
+            let min = total * MIN_SHARE;
+            let delta = (amount * total)
+                .min(container.weights[target] - min)
+                .max(min - container.weights[index]);
+            container.weights[index] += delta;
+            container.weights[target] -= delta;
+            return true;
+        }
+        false
+    }
+
+    /// Convert the views of the tree, keeping its structure
+    pub fn map<U, F: FnMut(&T) -> U>(&self, mut f: F) -> Tree<U> {
+        Tree { root: self.root.map(&mut f) }
+    }
+
+    /// Compute the geometry and visibility of all views inside `area`
+    pub fn arrange(&self, area: Geometry) -> Vec<Placement<T>>
+        where T: Clone
+    {
+        let mut placements = Vec::new();
+        self.root.arrange(area, true, &mut placements);
+        placements
+    }
+
+    fn path(&self, view: &T) -> Option<Vec<usize>> {
+        fn find<T: PartialEq>(container: &Container<T>, view: &T, path: &mut Vec<usize>) -> bool {
+            for (i, child) in container.children.iter().enumerate() {
+                path.push(i);
+                let found = match *child {
+                    Node::View(ref other) => other == view,
+                    Node::Container(ref container) => find(container, view, path),
+                };
+                if found {
+                    return true;
+                }
+                path.pop();
+            }
+            false
+        }
+
+        let mut path = Vec::new();
+        if find(&self.root, view, &mut path) {
+            Some(path)
+        } else {
+            None
+        }
+    }
+
+    fn focus_path(&self) -> Option<Vec<usize>> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(self.descend_focus(Vec::new()))
+        }
+    }
+
+    /// Extend a path by following the focused children down to a view
+    fn descend_focus(&self, mut path: Vec<usize>) -> Vec<usize> {
+        loop {
+            let next = {
+                let container = if path.is_empty() {
+                    &self.root
+                } else {
+                    match *self.node(&path) {
+                        Node::Container(ref container) => container,
+                        Node::View(_) => break,
+                    }
+                };
+                container.focus
+            };
+            path.push(next);
+        }
+        path
+    }
+
+    fn set_focus_path(&mut self, path: &[usize]) {
+        for depth in 0..path.len() {
+            self.container_mut(&path[..depth]).focus = path[depth];
+        }
+    }
+
+    fn node(&self, path: &[usize]) -> &Node<T> {
+        let (last, parent) = path.split_last().unwrap();
+        &self.container(parent).children[*last]
+    }
+
+    fn container(&self, path: &[usize]) -> &Container<T> {
+        let mut container = &self.root;
+        for &index in path {
+            container = match container.children[index] {
+                Node::Container(ref child) => child,
+                Node::View(_) => panic!("path does not lead to a container"),
+            };
+        }
+        container
+    }
+
+    fn container_mut(&mut self, path: &[usize]) -> &mut Container<T> {
+        let mut container = &mut self.root;
+        for &index in path {
+            let current = container;
+            container = match current.children[index] {
+                Node::Container(ref mut child) => child,
+                Node::View(_) => panic!("path does not lead to a container"),
+            };
+        }
+        container
+    }
+
+    /// Remove a node and all containers left empty by it
+    fn take(&mut self, path: &[usize]) -> Node<T> {
+        let (last, parent) = path.split_last().unwrap();
+        let node = self.container_mut(parent).remove(*last);
+        self.collapse(parent);
+        node
+    }
+
+    /// Remove the container at `path`, if it is empty, and its ancestors,
+    /// that become empty by this (except the root)
+    fn collapse(&mut self, path: &[usize]) {
+        let mut path = path;
+        while let Some((last, parent)) = path.split_last() {
+            if !self.container(path).children.is_empty() {
+                break;
+            }
+            self.container_mut(parent).remove(*last);
+            path = parent;
+        }
+    }
+}
+
+impl Tree<WeakView> {
+    /// Arrange the tree inside `area` and apply the result to its views
+    ///
+    /// Hidden views get an empty `Visibility`, visible ones `visibility`, the
+    /// visibility of the tree's workspace.
+    pub fn apply(&self, visibility: Visibility::Flags, area: Geometry) {
+        for placement in self.arrange(area) {
+            placement.view.run(|view| if placement.visible {
+                                   view.set_visibility(visibility);
+                                   view.set_geometry(ResizeEdge::Null, placement.geometry);
+                               } else {
+                                   view.set_visibility(Visibility::Null);
+                               });
+        }
+    }
+}
+
+/// Container trees of all outputs and their workspaces
+///
+/// Trees are identified by an output and the name of a workspace of
+/// `Workspaces`, `Trees::apply` arranges the tree of the workspace shown on
+/// an output. Views are not tracked on their own, insert created views into
+/// the tree of their workspace, e.g. via `Trees::current_mut`, and move them
+/// to another tree, when they are moved to another workspace.
+pub struct Trees {
+    layout: ContainerLayout,
+    trees: HashMap<(WeakOutput, String), Tree<WeakView>>,
+}
+
+impl Trees {
+    /// Create a new collection, whose trees start with the given root layout
+    pub fn new(layout: ContainerLayout) -> Trees {
+        Trees {
+            layout: layout,
+            trees: HashMap::new(),
+        }
+    }
+
+    /// Tree of the workspace on the given output, if it was used before
+    pub fn tree(&self, output: &Output, workspace: &str) -> Option<&Tree<WeakView>> {
+        self.trees.get(&(output.weak_reference(), String::from(workspace)))
+    }
+
+    /// Tree of the workspace on the given output, created if necessary
+    pub fn tree_mut(&mut self, output: &Output, workspace: &str) -> &mut Tree<WeakView> {
+        let layout = self.layout;
+        self.trees
+            .entry((output.weak_reference(), String::from(workspace)))
+            .or_insert_with(|| Tree::new(layout))
+    }
+
+    /// Tree of the workspace shown on the output, if it was used before
+    pub fn current(&self, output: &Output, workspaces: &Workspaces) -> Option<&Tree<WeakView>> {
+        workspaces.current(output).and_then(|workspace| self.tree(output, workspace))
+    }
+
+    /// Tree of the workspace shown on the output, created if necessary
+    ///
+    /// Returns `None`, if the output does not show a workspace yet.
+    pub fn current_mut(&mut self, output: &Output, workspaces: &Workspaces) -> Option<&mut Tree<WeakView>> {
+        match workspaces.current(output) {
+            Some(workspace) => Some(self.tree_mut(output, workspace)),
+            None => None,
+        }
+    }
+
+    /// Find the tree containing the view
+    pub fn find(&self, view: &View) -> Option<(WeakOutput, String)> {
+        let weak = view.weak_reference();
+        self.trees
+            .iter()
+            .find(|&(_, tree)| tree.contains(&weak))
+            .map(|(&(ref output, ref workspace), _)| (output.clone(), workspace.clone()))
+    }
+
+    /// Apply the tree of the workspace shown on the output
+    ///
+    /// Unlike `Tree::apply` views hidden behind the focused child of a
+    /// tabbed or stacked container are marked with `Workspaces::set_hidden`,
+    /// so they stay hidden when switching workspaces.
+    pub fn apply(&self, output: &Output, workspaces: &mut Workspaces, area: Geometry) {
+        let tree = match self.current(output, workspaces) {
+            Some(tree) => tree,
+            None => return,
+        };
+        for placement in tree.arrange(area) {
+            placement.view.run(|view| {
+                                   workspaces.set_hidden(view, !placement.visible);
+                                   if placement.visible {
+                                       view.set_geometry(ResizeEdge::Null, placement.geometry);
+                                   }
+                               });
+        }
+    }
+
+    /// Forward of `Callback::output_destroyed`
+    pub fn output_destroyed(&mut self, output: &Output) {
+        self.trees.retain(|&(ref other, _), _| *other != *output);
+    }
+
+    /// Forward of `Callback::view_destroyed`
+    ///
+    /// Returns the tree the view was removed from, apply it again to fill
+    /// the gap
+    pub fn view_destroyed(&mut self, view: &View) -> Option<(WeakOutput, String)> {
+        let location = self.find(view);
+        if let Some(ref location) = location {
+            if let Some(tree) = self.trees.get_mut(location) {
+                tree.remove(&view.weak_reference());
+            }
+        }
+        location
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_util::geometry;
+
+    /// Tree of `1 | (2 / 3)` with 3 focused
+    fn nested() -> Tree<u32> {
+        let mut tree = Tree::new(ContainerLayout::Horizontal);
+        tree.insert(1);
+        tree.insert(2);
+        tree.split(ContainerLayout::Vertical);
+        tree.insert(3);
+        tree
+    }
+
+    #[test]
+    fn test_insert_and_remove() {
+        let mut tree = nested();
+        assert_eq!(tree.views(), vec![&1, &2, &3]);
+        assert_eq!(tree.focused(), Some(&3));
+
+        assert!(tree.remove(&3));
+        assert_eq!(tree.focused(), Some(&2));
+        assert!(tree.remove(&2));
+        // the empty vertical container is gone
+        assert_eq!(tree.root().children(), &[Node::View(1)]);
+        assert!(!tree.remove(&2));
+    }
+
+    #[test]
+    fn test_focus_direction() {
+        let mut tree = nested();
+        assert_eq!(tree.focus_direction(Direction::Up), Some(&2));
+        assert_eq!(tree.focus_direction(Direction::Up), None);
+        assert_eq!(tree.focus_direction(Direction::Left), Some(&1));
+        // the vertical container remembers its focused child
+        assert_eq!(tree.focus_direction(Direction::Right), Some(&2));
+    }
+
+    #[test]
+    fn test_move_direction() {
+        let mut tree = nested();
+        // out of the vertical container to its left
+        assert!(tree.move_direction(Direction::Left));
+        assert_eq!(tree.views(), vec![&1, &3, &2]);
+        assert_eq!(tree.root().children().len(), 3);
+        assert_eq!(tree.focused(), Some(&3));
+
+        // swap with a view
+        assert!(tree.move_direction(Direction::Left));
+        assert_eq!(tree.views(), vec![&3, &1, &2]);
+        assert!(!tree.move_direction(Direction::Left));
+
+        // into a container
+        let mut tree = nested();
+        tree.focus(&1);
+        assert!(tree.move_direction(Direction::Right));
+        assert_eq!(tree.root().children().len(), 1);
+        assert_eq!(tree.views(), vec![&1, &2, &3]);
+        assert_eq!(tree.focused(), Some(&1));
+    }
+
+    #[test]
+    fn test_arrange_with_tabs_and_resize() {
+        let mut tree = nested();
+        tree.set_layout(ContainerLayout::Tabbed);
+        tree.focus(&1);
+        assert!(tree.resize(Direction::Right, 0.25));
+        assert_eq!(tree.arrange(geometry(0, 0, 1000, 500)),
+                   vec![Placement {
+                            view: 1,
+                            geometry: geometry(0, 0, 750, 500),
+                            visible: true,
+                        },
+                        Placement {
+                            view: 2,
+                            geometry: geometry(750, 0, 250, 500),
+                            visible: false,
+                        },
+                        Placement {
+                            view: 3,
+                            geometry: geometry(750, 0, 250, 500),
+                            visible: true,
+                        }]);
+        assert!(!tree.resize(Direction::Left, 0.25));
+    }
+
+    #[test]
+    fn test_map_keeps_structure() {
+        let tree = nested();
+        let mapped = tree.map(|view| view * 10);
+        assert_eq!(mapped.views(), vec![&10, &20, &30]);
+        assert_eq!(mapped.focused(), Some(&30));
+        assert_eq!(mapped.root().shares(), vec![0.5, 0.5]);
+        match mapped.root().children()[1] {
+            Node::Container(ref container) => assert_eq!(container.layout(), ContainerLayout::Vertical),
+            Node::View(_) => panic!("expected a container"),
+        }
+    }
+}
@@ -0,0 +1,296 @@
+//! Per-client tracking and security policy
+//!
+//! The `ClientRegistry` records the credentials of every connected client,
+//! when it connected and which views it owns. It also decides through a
+//! `SecurityPolicy`, which clients get to see the privileged globals of this
+//! crate (`Screencopy`, `GammaControl` and `OutputManagement`). Other clients
+//! will not find them in their registry and are unable to bind them.
+//!
+//! Create the registry before any client connects and forward
+//! `view_created` and `view_destroyed`.
+
+use {View, WeakView};
+
+use super::Display;
+use wayland::raw;
+
+use libc::{self, c_void};
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::SystemTime;
+
+use wayland_server::Client;
+
+/// Globals, that are only advertised to clients allowed by the
+/// `SecurityPolicy`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PrivilegedGlobal {
+    /// `wayland::screencopy::Screencopy`
+    Screencopy,
+    /// `wayland::gamma_control::GammaControl`
+    GammaControl,
+    /// `wayland::output_management::OutputManagement`
+    OutputManagement,
+}
+
+impl PrivilegedGlobal {
+    fn from_interface_name(name: &[u8]) -> Option<PrivilegedGlobal> {
+        match name {
+            b"zwlr_screencopy_manager_v1" => Some(PrivilegedGlobal::Screencopy),
+            b"zwlr_gamma_control_manager_v1" => Some(PrivilegedGlobal::GammaControl),
+            b"zwlr_output_manager_v1" => Some(PrivilegedGlobal::OutputManagement),
+            _ => None,
+        }
+    }
+}
+
+/// Information about a connected client
+#[derive(Clone, Debug)]
+pub struct ClientInfo {
+    /// Process id of the client
+    pub pid: libc::pid_t,
+    /// User id of the client
+    pub uid: libc::uid_t,
+    /// Group id of the client
+    pub gid: libc::gid_t,
+    /// Executable of the client process at the time it connected
+    pub executable: Option<PathBuf>,
+    /// Time the client connected
+    pub connected: SystemTime,
+    /// Views owned by the client
+    pub views: Vec<WeakView>,
+}
+
+impl ClientInfo {
+    unsafe fn from_client(client: *mut raw::wl_client) -> ClientInfo {
+        let mut pid = 0;
+        let mut uid = 0;
+        let mut gid = 0;
+        raw::wl_client_get_credentials(client, &mut pid, &mut uid, &mut gid);
+
+        ClientInfo {
+            pid: pid,
+            uid: uid,
+            gid: gid,
+            executable: fs::read_link(format!("/proc/{}/exe", pid)).ok(),
+            connected: SystemTime::now(),
+            views: Vec::new(),
+        }
+    }
+}
+
+/// Decides which clients may bind a `PrivilegedGlobal`
+///
+/// An Implementation for `FnMut` is provided, so you may use a (anonymous)
+/// function instead
+pub trait SecurityPolicy {
+    /// Return `true` to advertise the global to the client
+    fn allow(&mut self, client: &ClientInfo, global: PrivilegedGlobal) -> bool;
+}
+
+impl<F> SecurityPolicy for F
+    where F: FnMut(&ClientInfo, PrivilegedGlobal) -> bool
+{
+    fn allow(&mut self, client: &ClientInfo, global: PrivilegedGlobal) -> bool {
+        self(client, global)
+    }
+}
+
+/// `SecurityPolicy` matching clients by their executable
+///
+/// Clients with an executable, that was not permitted any global, are
+/// denied access.
+#[derive(Clone, Debug, Default)]
+pub struct ExecutablePolicy {
+    permitted: HashMap<PathBuf, Vec<PrivilegedGlobal>>,
+}
+
+impl ExecutablePolicy {
+    /// Create a new policy denying access to all clients
+    pub fn new() -> ExecutablePolicy {
+        ExecutablePolicy::default()
+    }
+
+    /// Permit clients running the given executable to bind a global
+    pub fn permit<P: Into<PathBuf>>(&mut self, executable: P, global: PrivilegedGlobal) {
+        let globals = self.permitted.entry(executable.into()).or_insert_with(Vec::new);
+        if !globals.contains(&global) {
+            globals.push(global);
+        }
+    }
+
+    /// Check if the executable may bind the given global
+    pub fn is_permitted(&self, executable: &Path, global: PrivilegedGlobal) -> bool {
+        self.permitted.get(executable).map(|globals| globals.contains(&global)).unwrap_or(false)
+    }
+}
+
+impl SecurityPolicy for ExecutablePolicy {
+    fn allow(&mut self, client: &ClientInfo, global: PrivilegedGlobal) -> bool {
+        match client.executable {
+            Some(ref executable) => self.is_permitted(executable, global),
+            None => false,
+        }
+    }
+}
+
+struct State {
+    clients: HashMap<*mut raw::wl_client, ClientInfo>,
+    policy: Box<SecurityPolicy>,
+}
+
+impl State {
+    /// Get the information of a client, clients that connected before the
+    /// registry was created are added on first use
+    unsafe fn client(this: &Rc<RefCell<State>>, client: *mut raw::wl_client) -> ClientInfo {
+        if let Some(info) = this.borrow().clients.get(&client) {
+            return info.clone();
+        }
+
+        let info = ClientInfo::from_client(client);
+        let weak = Rc::downgrade(this);
+        raw::on_client_destroyed(client, move |client| if let Some(state) = weak.upgrade() {
+            state.borrow_mut().clients.remove(&(client as *mut raw::wl_client));
+        });
+        this.borrow_mut().clients.insert(client, info.clone());
+        info
+    }
+}
+
+unsafe extern "C" fn global_filter(client: *const raw::wl_client, global: *const raw::wl_global,
+                                   data: *mut c_void)
+                                   -> bool {
+    let interface = raw::wl_global_get_interface(global);
+    let global = match PrivilegedGlobal::from_interface_name(CStr::from_ptr((*interface).name).to_bytes()) {
+        Some(global) => global,
+        None => return true,
+    };
+    let state = match raw::from_user_data::<State>(data) {
+        Some(state) => state,
+        None => return false,
+    };
+
+    let info = State::client(&state, client as *mut _);
+    let mut state = state.borrow_mut();
+    state.policy.allow(&info, global)
+}
+
+/// Registry of all connected clients
+///
+/// Dropping this stops tracking clients and advertises privileged globals to
+/// all clients again.
+pub struct ClientRegistry {
+    state: Rc<RefCell<State>>,
+    display: Display,
+    filter_data: *mut c_void,
+    _listener: raw::Listener,
+}
+
+impl ClientRegistry {
+    /// Start tracking clients connecting to the given `Display`
+    pub fn new<P: SecurityPolicy + 'static>(display: &Display, policy: P) -> ClientRegistry {
+        let state = Rc::new(RefCell::new(State {
+                                              clients: HashMap::new(),
+                                              policy: Box::new(policy),
+                                          }));
+
+        let weak = Rc::downgrade(&state);
+        let listener = unsafe {
+            raw::on_client_created(display.ptr(), move |client| if let Some(state) = weak.upgrade() {
+                State::client(&state, client as *mut _);
+            })
+        };
+        let filter_data = raw::into_user_data(&state);
+        unsafe { raw::wl_display_set_global_filter(display.ptr(), Some(global_filter), filter_data) };
+
+        ClientRegistry {
+            state: state,
+            display: display.clone(),
+            filter_data: filter_data,
+            _listener: listener,
+        }
+    }
+
+    /// Get the information of a connected client
+    pub fn client(&self, client: &Client) -> ClientInfo {
+        unsafe { State::client(&self.state, client.ptr() as *mut _) }
+    }
+
+    /// Get the information of all connected clients
+    pub fn clients(&self) -> Vec<ClientInfo> {
+        self.state.borrow().clients.values().cloned().collect()
+    }
+
+    /// Get the client owning the view
+    pub fn view_owner(&self, view: &View) -> ClientInfo {
+        self.client(&view.wl_client())
+    }
+
+    /// Check if the policy allows the client to bind the global
+    pub fn is_allowed(&self, client: &Client, global: PrivilegedGlobal) -> bool {
+        let info = self.client(client);
+        self.state.borrow_mut().policy.allow(&info, global)
+    }
+
+    /// Disconnect a client, destroying all its views and resources
+    pub fn kill_client(&mut self, client: &Client) {
+        unsafe { raw::wl_client_destroy(client.ptr() as *mut _) }
+    }
+
+    /// Forward of `Callback::view_created`
+    pub fn view_created(&mut self, view: &View) {
+        let client = view.wl_client().ptr() as *mut raw::wl_client;
+        unsafe { State::client(&self.state, client) };
+        if let Some(info) = self.state.borrow_mut().clients.get_mut(&client) {
+            info.views.push(view.weak_reference());
+        }
+    }
+
+    /// Forward of `Callback::view_destroyed`
+    pub fn view_destroyed(&mut self, view: &View) {
+        for info in self.state.borrow_mut().clients.values_mut() {
+            info.views.retain(|other| *other != *view);
+        }
+    }
+}
+
+impl Drop for ClientRegistry {
+    fn drop(&mut self) {
+        unsafe {
+            raw::wl_display_set_global_filter(self.display.ptr(), None, 0 as *mut _);
+            raw::free_user_data::<State>(self.filter_data);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(executable: Option<&str>) -> ClientInfo {
+        ClientInfo {
+            pid: 1,
+            uid: 1000,
+            gid: 1000,
+            executable: executable.map(PathBuf::from),
+            connected: SystemTime::now(),
+            views: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_executable_policy() {
+        let mut policy = ExecutablePolicy::new();
+        policy.permit("/usr/bin/grim", PrivilegedGlobal::Screencopy);
+
+        assert!(policy.allow(&client(Some("/usr/bin/grim")), PrivilegedGlobal::Screencopy));
+        assert!(!policy.allow(&client(Some("/usr/bin/grim")), PrivilegedGlobal::GammaControl));
+        assert!(!policy.allow(&client(Some("/usr/bin/evil")), PrivilegedGlobal::Screencopy));
+        assert!(!policy.allow(&client(None), PrivilegedGlobal::Screencopy));
+    }
+}
@@ -0,0 +1,378 @@
+//! Gamma control protocol for redshift-like clients
+//!
+//! Implements the `zwlr_gamma_control_manager_v1` protocol (version 1).
+//! A client permitted by the `GammaPolicy` may take control over the gamma
+//! ramps of an `Output`, only one client may control an output at a time.
+//!
+//! wlc is not able to read back gamma ramps, so set the ramps of the
+//! compositor through `GammaControl::set_gamma`. These are restored once
+//! the client releases the output or disconnects, a linear ramp is used if
+//! the compositor never set any. Forward `Callback::output_destroyed` to
+//! restore the ramps before the output is gone.
+
+use {Output, WeakOutput};
+
+use super::{Display, output_from_wl_surface};
+use wayland::raw;
+
+use libc::{self, c_void};
+
+use std::cell::RefCell;
+use std::cmp;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::mem;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::rc::Rc;
+use std::slice;
+
+use wayland_server::{Client, Resource};
+use wayland_server::protocol::wl_output::WlOutput;
+
+const VERSION: u32 = 1;
+
+const CONTROL_EVENT_GAMMA_SIZE: u32 = 0;
+const CONTROL_EVENT_FAILED: u32 = 1;
+
+const CONTROL_ERROR_INVALID_GAMMA: u32 = 1;
+
+/// Decides which clients may control the gamma of an `Output`
+///
+/// An Implementation for `FnMut` is provided, so you may use a (anonymous)
+/// function instead
+pub trait GammaPolicy {
+    /// Return `true` to let the client control the gamma ramps of the output
+    fn allow(&mut self, client: &Client, output: &Output) -> bool;
+}
+
+impl<F> GammaPolicy for F
+    where F: FnMut(&Client, &Output) -> bool
+{
+    fn allow(&mut self, client: &Client, output: &Output) -> bool {
+        self(client, output)
+    }
+}
+
+/// Linear gamma ramp of the given size, as used by outputs without any
+/// color correction
+pub fn linear_ramp(size: u16) -> Vec<u16> {
+    if size <= 1 {
+        return vec![u16::max_value(); size as usize];
+    }
+    (0..size as u32).map(|i| (i * u16::max_value() as u32 / (size as u32 - 1)) as u16).collect()
+}
+
+struct Ramps {
+    red: Vec<u16>,
+    green: Vec<u16>,
+    blue: Vec<u16>,
+}
+
+impl Ramps {
+    fn linear(size: u16) -> Ramps {
+        Ramps {
+            red: linear_ramp(size),
+            green: linear_ramp(size),
+            blue: linear_ramp(size),
+        }
+    }
+
+    /// Split a buffer of the red, green and blue ramps as sent by the client
+    fn from_buffer(mut buffer: Vec<u16>, size: u16) -> Option<Ramps> {
+        if size == 0 || buffer.len() != size as usize * 3 {
+            return None;
+        }
+        let blue = buffer.split_off(size as usize * 2);
+        let green = buffer.split_off(size as usize);
+        Some(Ramps {
+                 red: buffer,
+                 green: green,
+                 blue: blue,
+             })
+    }
+
+    fn apply(&mut self, output: &Output) {
+        output.set_gamma(&mut self.red, &mut self.green, &mut self.blue)
+    }
+}
+
+struct Control {
+    resource: *mut raw::wl_resource,
+    output: WeakOutput,
+}
+
+struct State {
+    controls: Vec<Control>,
+    originals: HashMap<WeakOutput, Ramps>,
+    policy: Box<GammaPolicy>,
+}
+
+impl State {
+    fn is_controlled(&self, output: &Output) -> bool {
+        self.controls.iter().any(|control| control.output == *output)
+    }
+
+    fn restore(&mut self, output: &Output) {
+        match self.originals.get_mut(&output.weak_reference()) {
+            Some(ramps) => ramps.apply(output),
+            None => Ramps::linear(output.gamma_size()).apply(output),
+        }
+    }
+}
+
+/// Gamma control global
+///
+/// Dropping this removes the global, ramps set by clients stay active.
+pub struct GammaControl {
+    state: Rc<RefCell<State>>,
+    _global: raw::Global<State>,
+}
+
+impl GammaControl {
+    /// Advertise the gamma control global on the given `Display`
+    pub fn new<P: GammaPolicy + 'static>(display: &Display, policy: P) -> GammaControl {
+        let state = Rc::new(RefCell::new(State {
+                                              controls: Vec::new(),
+                                              originals: HashMap::new(),
+                                              policy: Box::new(policy),
+                                          }));
+
+        let global = unsafe {
+            raw::Global::new(display.ptr(),
+                             &MANAGER_INTERFACE,
+                             VERSION,
+                             &state,
+                             bind_gamma_control_manager)
+        };
+
+        GammaControl {
+            state: state,
+            _global: global,
+        }
+    }
+
+    /// Check if a client currently controls the gamma of the output
+    pub fn is_controlled(&self, output: &Output) -> bool {
+        self.state.borrow().is_controlled(output)
+    }
+
+    /// Set the gamma ramps of the compositor for the output
+    ///
+    /// If a client controls the output, the ramps are applied once it
+    /// releases the output.
+    ///
+    /// # Panic
+    /// Panics if `r`, `g`, `b` have different sizes
+    pub fn set_gamma(&mut self, output: &Output, r: &[u16], g: &[u16], b: &[u16]) {
+        if r.len() != g.len() || r.len() != b.len() {
+            panic!("Color ramps do not have the same size");
+        }
+
+        let mut state = self.state.borrow_mut();
+        let mut ramps = Ramps {
+            red: r.to_vec(),
+            green: g.to_vec(),
+            blue: b.to_vec(),
+        };
+        if !state.is_controlled(output) {
+            ramps.apply(output);
+        }
+        state.originals.insert(output.weak_reference(), ramps);
+    }
+
+    /// Forward of `Callback::output_destroyed`
+    pub fn output_destroyed(&mut self, output: &Output) {
+        let mut state = self.state.borrow_mut();
+        if state.is_controlled(output) {
+            for control in state.controls.iter().filter(|control| control.output == *output) {
+                unsafe { raw::wl_resource_post_event(control.resource, CONTROL_EVENT_FAILED) };
+            }
+            state.controls.retain(|control| control.output != *output);
+            state.restore(output);
+        }
+        state.originals.remove(&output.weak_reference());
+    }
+}
+
+unsafe extern "C" fn bind_gamma_control_manager(client: *mut raw::wl_client, data: *mut c_void, version: u32,
+                                                id: u32) {
+    if let Some(state) = raw::from_user_data::<State>(data) {
+        raw::create_resource(client,
+                             &MANAGER_INTERFACE,
+                             cmp::min(version, VERSION),
+                             id,
+                             &MANAGER_IMPLEMENTATION,
+                             &state);
+    }
+}
+
+unsafe extern "C" fn get_gamma_control(client: *mut raw::wl_client, resource: *mut raw::wl_resource, id: u32,
+                                       output: *mut raw::wl_resource) {
+    let state = match raw::state::<State>(resource) {
+        Some(state) => state,
+        None => return,
+    };
+    let version = raw::wl_resource_get_version(resource) as u32;
+    let control = match raw::create_resource(client,
+                                             &CONTROL_INTERFACE,
+                                             version,
+                                             id,
+                                             &CONTROL_IMPLEMENTATION,
+                                             &state) {
+        Some(control) => control,
+        None => return,
+    };
+    raw::on_resource_destroyed(control, |control| if let Some(state) = raw::state::<State>(control) {
+        let mut state = state.borrow_mut();
+        let output = state.controls
+            .iter()
+            .find(|other| other.resource == control)
+            .map(|other| other.output.clone());
+        if let Some(output) = output {
+            state.controls.retain(|other| other.resource != control);
+            output.run(|output| state.restore(output));
+        }
+    });
+
+    let output = output_from_wl_surface(&WlOutput::from_ptr_initialized(output as *mut _));
+
+    let mut state = state.borrow_mut();
+    let gamma_size = output.run(|output| {
+            let allowed = output.gamma_size() > 0 && !state.is_controlled(output) &&
+                          state.policy.allow(&Client::from_ptr(client as *mut _), output);
+            if allowed { output.gamma_size() } else { 0 }
+        })
+        .unwrap_or(0);
+    if gamma_size > 0 {
+        state.controls.push(Control {
+                                resource: control,
+                                output: output,
+                            });
+        raw::wl_resource_post_event(control, CONTROL_EVENT_GAMMA_SIZE, gamma_size as u32);
+    } else {
+        raw::wl_resource_post_event(control, CONTROL_EVENT_FAILED);
+    }
+}
+
+unsafe fn read_ramps(mut file: File, size: u16) -> Option<Ramps> {
+    // never block the compositor on a client not writing the ramps
+    let fd = file.as_raw_fd();
+    let flags = libc::fcntl(fd, libc::F_GETFL);
+    if flags == -1 || libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) == -1 {
+        return None;
+    }
+
+    let mut buffer = vec![0u16; size as usize * 3];
+    {
+        let bytes = slice::from_raw_parts_mut(buffer.as_mut_ptr() as *mut u8,
+                                              buffer.len() * mem::size_of::<u16>());
+        if file.read_exact(bytes).is_err() {
+            return None;
+        }
+    }
+    Ramps::from_buffer(buffer, size)
+}
+
+unsafe extern "C" fn set_gamma(_client: *mut raw::wl_client, resource: *mut raw::wl_resource, fd: i32) {
+    // closed on every path, once dropped
+    let file = File::from_raw_fd(fd);
+    let state = match raw::state::<State>(resource) {
+        Some(state) => state,
+        None => return,
+    };
+
+    let output = state.borrow()
+        .controls
+        .iter()
+        .find(|control| control.resource == resource)
+        .map(|control| control.output.clone());
+    let output = match output {
+        Some(output) => output,
+        // the control already failed
+        None => return,
+    };
+
+    let applied = output.run(|output| match read_ramps(file, output.gamma_size()) {
+                                 Some(mut ramps) => ramps.apply(output),
+                                 None => {
+                                     raw::wl_resource_post_error(resource,
+                                                                 CONTROL_ERROR_INVALID_GAMMA,
+                                                                 cstr!("invalid gamma ramps"))
+                                 }
+                             });
+    if applied.is_none() {
+        // the output is gone, but `output_destroyed` was not forwarded yet
+        state.borrow_mut().controls.retain(|control| control.resource != resource);
+        raw::wl_resource_post_event(resource, CONTROL_EVENT_FAILED);
+    }
+}
+
+unsafe extern "C" fn destroy(_client: *mut raw::wl_client, resource: *mut raw::wl_resource) {
+    raw::wl_resource_destroy(resource);
+}
+
+#[repr(C)]
+struct ManagerImplementation {
+    get_gamma_control: unsafe extern "C" fn(*mut raw::wl_client,
+                                            *mut raw::wl_resource,
+                                            u32,
+                                            *mut raw::wl_resource),
+    destroy: unsafe extern "C" fn(*mut raw::wl_client, *mut raw::wl_resource),
+}
+
+#[repr(C)]
+struct ControlImplementation {
+    set_gamma: unsafe extern "C" fn(*mut raw::wl_client, *mut raw::wl_resource, i32),
+    destroy: unsafe extern "C" fn(*mut raw::wl_client, *mut raw::wl_resource),
+}
+
+static MANAGER_IMPLEMENTATION: ManagerImplementation = ManagerImplementation {
+    get_gamma_control: get_gamma_control,
+    destroy: destroy,
+};
+
+static CONTROL_IMPLEMENTATION: ControlImplementation = ControlImplementation {
+    set_gamma: set_gamma,
+    destroy: destroy,
+};
+
+static GET_GAMMA_CONTROL_TYPES: raw::Types<[*const raw::wl_interface; 2]> =
+    raw::Types([&CONTROL_INTERFACE as *const _, unsafe { &raw::wl_output_interface as *const _ }]);
+
+static MANAGER_REQUESTS: [raw::wl_message; 2] =
+    [message!("get_gamma_control", "no", &GET_GAMMA_CONTROL_TYPES),
+     message!("destroy", "", &raw::NULL_TYPES)];
+static MANAGER_EVENTS: [raw::wl_message; 0] = [];
+
+static MANAGER_INTERFACE: raw::wl_interface =
+    interface!("zwlr_gamma_control_manager_v1", 1, MANAGER_REQUESTS, MANAGER_EVENTS);
+
+static CONTROL_REQUESTS: [raw::wl_message; 2] = [message!("set_gamma", "h", &raw::NULL_TYPES),
+                                                 message!("destroy", "", &raw::NULL_TYPES)];
+static CONTROL_EVENTS: [raw::wl_message; 2] = [message!("gamma_size", "u", &raw::NULL_TYPES),
+                                               message!("failed", "", &raw::NULL_TYPES)];
+
+static CONTROL_INTERFACE: raw::wl_interface =
+    interface!("zwlr_gamma_control_v1", 1, CONTROL_REQUESTS, CONTROL_EVENTS);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_linear_ramp() {
+        assert_eq!(linear_ramp(0), Vec::<u16>::new());
+        assert_eq!(linear_ramp(1), vec![u16::max_value()]);
+        assert_eq!(linear_ramp(3), vec![0, 32767, 65535]);
+    }
+
+    #[test]
+    fn test_ramps_from_buffer() {
+        let ramps = Ramps::from_buffer(vec![1, 2, 3, 4, 5, 6], 2).unwrap();
+        assert_eq!(ramps.red, vec![1, 2]);
+        assert_eq!(ramps.green, vec![3, 4]);
+        assert_eq!(ramps.blue, vec![5, 6]);
+        assert!(Ramps::from_buffer(vec![1, 2, 3, 4, 5], 2).is_none());
+    }
+}
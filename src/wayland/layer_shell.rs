@@ -0,0 +1,846 @@
+//! Layer shell protocol for panels, docks and wallpapers
+//!
+//! Implements the `zwlr_layer_shell_v1` protocol (version 1).
+//! Clients may place their surfaces on one of four `Layer`s of an `Output`,
+//! anchor them to its edges and reserve an exclusive zone other views should
+//! not cover (e.g. the height of a status bar).
+//!
+//! Create a `LayerShell` once the compositor is ready (e.g. in
+//! `Callback::compositor_ready`) and forward `view_created`,
+//! `view_destroyed`, `output_resolution` and `output_destroyed` to it.
+//! Layer surfaces are ordinary `View`s to wlc, check `LayerShell::view_created`
+//! before managing a new view yourself and use `LayerShell::usable_area`
+//! instead of the output's resolution for your layout.
+
+use {Geometry, Output, Point, ResizeEdge, Size, View, Visibility, WeakOutput, WeakView};
+use event_loop::{Timer, event_loop_add_timer};
+
+use super::{Display, RoleError, RoleInterface, WlcSurface};
+use wayland::raw;
+
+use ffi;
+use libc::{c_char, c_void};
+use num_traits::FromPrimitive;
+
+use std::cell::RefCell;
+use std::cmp;
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::rc::Rc;
+use std::time::Duration;
+
+use wayland_server::Client;
+
+const VERSION: u32 = 1;
+
+const SHELL_ERROR_ROLE: u32 = 0;
+const SHELL_ERROR_INVALID_LAYER: u32 = 1;
+const SHELL_ERROR_ALREADY_CONSTRUCTED: u32 = 2;
+const SURFACE_ERROR_INVALID_SIZE: u32 = 1;
+const SURFACE_ERROR_INVALID_ANCHOR: u32 = 2;
+
+const SURFACE_EVENT_CONFIGURE: u32 = 0;
+const SURFACE_EVENT_CLOSED: u32 = 1;
+
+enum_from_primitive! {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    #[repr(u32)]
+    /// Layers a surface may be placed on, from bottom to top
+    pub enum Layer {
+        /// Below everything, e.g. wallpapers
+        Background = 0,
+        /// Below normal views, e.g. desktop widgets
+        Bottom = 1,
+        /// Above normal views, e.g. panels and docks
+        Top = 2,
+        /// Above everything including fullscreen views, e.g. lock screens
+        Overlay = 3,
+    }
+}
+
+#[cfg_attr(rustfmt, rustfmt_skip)]
+enum_serde!(Layer {
+    Background,
+    Bottom,
+    Top,
+    Overlay,
+});
+
+/// Edges of an `Output` a layer surface may be anchored to
+#[allow(non_snake_case)]
+pub mod LayerAnchor {
+    bitflags! {
+        /// Bitmap that may represent multiple edges
+        pub flags Flags: u32 {
+            /// Top edge
+            const Top       = 1,
+            /// Bottom edge
+            const Bottom    = 2,
+            /// Left edge
+            const Left      = 4,
+            /// Right edge
+            const Right     = 8,
+        }
+    }
+}
+
+#[cfg_attr(rustfmt, rustfmt_skip)]
+bitflags_serde!(LayerAnchor {
+    Top,
+    Bottom,
+    Left,
+    Right,
+});
+
+/// Distance of a layer surface to the anchored edges
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub struct Margin {
+    /// Distance to the top edge
+    pub top: i32,
+    /// Distance to the right edge
+    pub right: i32,
+    /// Distance to the bottom edge
+    pub bottom: i32,
+    /// Distance to the left edge
+    pub left: i32,
+}
+
+/// Placement of a layer surface as requested by its client
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub struct LayerSurfaceState {
+    /// Layer of the surface
+    pub layer: Layer,
+    /// Anchored edges
+    pub anchor: LayerAnchor::Flags,
+    /// Exclusive zone
+    ///
+    /// A positive value reserves space at the anchored edge, `0` lets the
+    /// surface be moved to avoid other exclusive zones and `-1` ignores them.
+    pub exclusive_zone: i32,
+    /// Margin to the anchored edges
+    pub margin: Margin,
+    /// Requested size, `0` in a dimension stretches the surface between the
+    /// opposite anchors
+    pub size: Size,
+    /// Whether the surface wants keyboard focus
+    pub keyboard_interactivity: bool,
+}
+
+impl LayerSurfaceState {
+    /// Initial state of a surface on the given layer
+    pub fn new(layer: Layer) -> LayerSurfaceState {
+        LayerSurfaceState {
+            layer: layer,
+            anchor: LayerAnchor::Flags::empty(),
+            exclusive_zone: 0,
+            margin: Margin::default(),
+            size: Size { w: 0, h: 0 },
+            keyboard_interactivity: false,
+        }
+    }
+
+    /// Edge the exclusive zone of the surface applies to, if any
+    ///
+    /// The surface needs to be anchored to exactly one edge or to one edge
+    /// and both perpendicular ones.
+    pub fn exclusive_edge(&self) -> Option<LayerAnchor::Flags> {
+        if self.exclusive_zone <= 0 {
+            return None;
+        }
+
+        let horizontal = LayerAnchor::Left | LayerAnchor::Right;
+        let vertical = LayerAnchor::Top | LayerAnchor::Bottom;
+        [LayerAnchor::Top, LayerAnchor::Bottom, LayerAnchor::Left, LayerAnchor::Right]
+            .iter()
+            .cloned()
+            .find(|&edge| {
+                let perpendicular = if vertical.contains(edge) { horizontal } else { vertical };
+                self.anchor == edge || self.anchor == edge | perpendicular
+            })
+    }
+
+    /// Check if the requested size may be used with the anchored edges
+    ///
+    /// A dimension of `0` requires the surface to be anchored to both
+    /// opposite edges of that dimension.
+    pub fn is_size_valid(&self) -> bool {
+        (self.size.w != 0 || self.anchor.contains(LayerAnchor::Left | LayerAnchor::Right)) &&
+        (self.size.h != 0 || self.anchor.contains(LayerAnchor::Top | LayerAnchor::Bottom))
+    }
+}
+
+/// Compute the geometry of a layer surface inside the given bounds
+pub fn surface_geometry(state: &LayerSurfaceState, bounds: Geometry) -> Geometry {
+    let (x, w) = axis(state.anchor.contains(LayerAnchor::Left),
+                      state.anchor.contains(LayerAnchor::Right),
+                      bounds.origin.x,
+                      bounds.size.w as i32,
+                      state.size.w as i32,
+                      state.margin.left,
+                      state.margin.right);
+    let (y, h) = axis(state.anchor.contains(LayerAnchor::Top),
+                      state.anchor.contains(LayerAnchor::Bottom),
+                      bounds.origin.y,
+                      bounds.size.h as i32,
+                      state.size.h as i32,
+                      state.margin.top,
+                      state.margin.bottom);
+
+    Geometry {
+        origin: Point { x: x, y: y },
+        size: Size {
+            w: cmp::max(w, 0) as u32,
+            h: cmp::max(h, 0) as u32,
+        },
+    }
+}
+
+fn axis(start: bool, end: bool, origin: i32, available: i32, requested: i32, margin_start: i32,
+        margin_end: i32)
+        -> (i32, i32) {
+    match (start, end) {
+        (true, true) if requested == 0 => (origin + margin_start, available - margin_start - margin_end),
+        (true, false) => (origin + margin_start, requested),
+        (false, true) => (origin + available - requested - margin_end, requested),
+        _ => (origin + (available - requested) / 2, requested),
+    }
+}
+
+/// Reduce the usable area of an `Output` by the exclusive zone of a layer
+/// surface
+pub fn apply_exclusive_zone(state: &LayerSurfaceState, usable: &mut Geometry) {
+    let edge = match state.exclusive_edge() {
+        Some(edge) => edge,
+        None => return,
+    };
+
+    // a negative margin may exceed the zone, which never grows the area
+    let clamped = |margin: i32| cmp::max(state.exclusive_zone + margin, 0);
+    if edge == LayerAnchor::Top {
+        let zone = clamped(state.margin.top);
+        usable.origin.y += zone;
+        usable.size.h = usable.size.h.saturating_sub(zone as u32);
+    } else if edge == LayerAnchor::Bottom {
+        let zone = clamped(state.margin.bottom);
+        usable.size.h = usable.size.h.saturating_sub(zone as u32);
+    } else if edge == LayerAnchor::Left {
+        let zone = clamped(state.margin.left);
+        usable.origin.x += zone;
+        usable.size.w = usable.size.w.saturating_sub(zone as u32);
+    } else if edge == LayerAnchor::Right {
+        let zone = clamped(state.margin.right);
+        usable.size.w = usable.size.w.saturating_sub(zone as u32);
+    }
+}
+
+/// Arrange all layer surfaces of an `Output`
+///
+/// Surfaces with an exclusive zone are placed first, from the top most layer
+/// to the bottom most one, each reducing the usable area. Afterwards all other
+/// surfaces are placed inside the remaining area.
+///
+/// Returns the geometry of every surface in the given order and the usable
+/// area that is left for normal views.
+pub fn arrange(output: Geometry, surfaces: &[LayerSurfaceState]) -> (Vec<Geometry>, Geometry) {
+    let mut usable = output;
+    let mut geometries = vec![output; surfaces.len()];
+
+    for &exclusive in &[true, false] {
+        for &layer in &[Layer::Overlay, Layer::Top, Layer::Bottom, Layer::Background] {
+            for (i, state) in surfaces.iter().enumerate() {
+                if state.layer != layer || (state.exclusive_zone > 0) != exclusive {
+                    continue;
+                }
+
+                let bounds = if state.exclusive_zone == -1 { output } else { usable };
+                geometries[i] = surface_geometry(state, bounds);
+                apply_exclusive_zone(state, &mut usable);
+            }
+        }
+    }
+
+    (geometries, usable)
+}
+
+struct LayerSurface {
+    resource: *mut raw::wl_resource,
+    view: WeakView,
+    // `None` once closed
+    output: Option<WeakOutput>,
+    namespace: String,
+    state: LayerSurfaceState,
+    geometry: Option<Geometry>,
+    configured: Option<Size>,
+}
+
+struct State {
+    display: *mut raw::wl_display,
+    surfaces: Vec<LayerSurface>,
+    usable_areas: HashMap<WeakOutput, Geometry>,
+    timer: Option<Timer>,
+}
+
+impl State {
+    fn surface_mut(&mut self, resource: *mut raw::wl_resource) -> Option<&mut LayerSurface> {
+        self.surfaces.iter_mut().find(|surface| surface.resource == resource)
+    }
+
+    fn schedule_configure(&mut self) {
+        if let Some(ref mut timer) = self.timer {
+            timer.update(&Duration::from_millis(1));
+        }
+    }
+
+    fn arrange(&mut self) {
+        let mut outputs: Vec<WeakOutput> = Vec::new();
+        for output in self.surfaces.iter().filter_map(|surface| surface.output.as_ref()) {
+            if !outputs.contains(output) {
+                outputs.push(output.clone());
+            }
+        }
+        self.usable_areas.retain(|output, _| output.run(|_| ()).is_some());
+
+        for weak_output in outputs {
+            let output = match weak_output.run(output_geometry) {
+                Some(geometry) => geometry,
+                None => continue,
+            };
+
+            let indices: Vec<usize> = (0..self.surfaces.len())
+                .filter(|&i| self.surfaces[i].output.as_ref() == Some(&weak_output))
+                .collect();
+            let states: Vec<LayerSurfaceState> = indices.iter().map(|&i| self.surfaces[i].state).collect();
+            let (geometries, usable) = arrange(output, &states);
+
+            for (&i, geometry) in indices.iter().zip(geometries) {
+                let surface = &mut self.surfaces[i];
+                if surface.configured != Some(geometry.size) {
+                    unsafe {
+                        raw::wl_resource_post_event(surface.resource,
+                                                    SURFACE_EVENT_CONFIGURE,
+                                                    raw::wl_display_next_serial(self.display),
+                                                    geometry.size.w,
+                                                    geometry.size.h);
+                    }
+                    surface.configured = Some(geometry.size);
+                }
+                surface.geometry = Some(geometry);
+                surface.view.run(|view| view.set_geometry(ResizeEdge::Null, geometry));
+            }
+            self.usable_areas.insert(weak_output, usable);
+        }
+    }
+}
+
+fn output_geometry(output: &Output) -> Geometry {
+    Geometry {
+        origin: Point { x: 0, y: 0 },
+        size: output.virtual_resolution(),
+    }
+}
+
+/// Layer shell global
+///
+/// Dropping this removes the global. Existing surfaces stay `View`s, but are
+/// no longer arranged.
+pub struct LayerShell {
+    state: Rc<RefCell<State>>,
+    _global: raw::Global<State>,
+}
+
+impl LayerShell {
+    /// Advertise the layer shell global on the given `Display`
+    pub fn new(display: &Display) -> LayerShell {
+        let state = Rc::new(RefCell::new(State {
+                                              display: display.ptr(),
+                                              surfaces: Vec::new(),
+                                              usable_areas: HashMap::new(),
+                                              timer: None,
+                                          }));
+
+        let weak = Rc::downgrade(&state);
+        state.borrow_mut().timer = Some(event_loop_add_timer(move || if let Some(state) = weak.upgrade() {
+                                                                 state.borrow_mut().arrange();
+                                                             }));
+
+        let global = unsafe {
+            raw::Global::new(display.ptr(),
+                             &LAYER_SHELL_INTERFACE,
+                             VERSION,
+                             &state,
+                             bind_layer_shell)
+        };
+
+        LayerShell {
+            state: state,
+            _global: global,
+        }
+    }
+
+    /// Check if the view is a layer surface
+    pub fn is_layer_surface(&self, view: &View) -> bool {
+        self.state.borrow().surfaces.iter().any(|surface| surface.view == *view)
+    }
+
+    /// Get the requested placement of a layer surface
+    pub fn surface_state(&self, view: &View) -> Option<LayerSurfaceState> {
+        self.state.borrow().surfaces.iter().find(|surface| surface.view == *view).map(|surface| surface.state)
+    }
+
+    /// Get the namespace a layer surface was created with (e.g. "panel")
+    pub fn namespace(&self, view: &View) -> Option<String> {
+        self.state
+            .borrow()
+            .surfaces
+            .iter()
+            .find(|surface| surface.view == *view)
+            .map(|surface| surface.namespace.clone())
+    }
+
+    /// Area of the `Output` not covered by any exclusive zone
+    ///
+    /// Use this instead of `Output::virtual_resolution` to layout normal
+    /// views.
+    pub fn usable_area(&self, output: &Output) -> Geometry {
+        self.state
+            .borrow()
+            .usable_areas
+            .get(&output.weak_reference())
+            .cloned()
+            .unwrap_or_else(|| output_geometry(output))
+    }
+
+    /// Sort views of an `Output` by their layer
+    ///
+    /// Background and bottom surfaces are moved below, top and overlay
+    /// surfaces above all other views. The order inside each layer is kept.
+    pub fn sort<'a>(&self, mut views: Vec<&'a View>) -> Vec<&'a View> {
+        let state = self.state.borrow();
+        views.sort_by_key(|view| match state.surfaces.iter().find(|surface| surface.view == **view) {
+                              Some(surface) if surface.state.layer <= Layer::Bottom => {
+                                  surface.state.layer as u32
+                              }
+                              Some(surface) => surface.state.layer as u32 + 1,
+                              None => Layer::Top as u32,
+                          });
+        views
+    }
+
+    /// Restack the views of an `Output` by their layer using
+    /// `Output::set_views`
+    pub fn restack<'a>(&self, output: &'a Output) -> Result<(), Vec<&'a View>> {
+        output.set_views(self.sort(output.views()))
+    }
+
+    /// Arrange all layer surfaces now instead of with the next event loop
+    /// iteration
+    pub fn arrange(&mut self) {
+        self.state.borrow_mut().arrange();
+    }
+
+    /// Forward of `Callback::view_created`
+    ///
+    /// Returns `true` if the view is a layer surface, which was placed on its
+    /// layer. In that case you should not manage the view yourself.
+    pub fn view_created(&mut self, view: &View) -> bool {
+        let (output, geometry, focus) = {
+            let state = self.state.borrow();
+            match state.surfaces.iter().find(|surface| surface.view == *view) {
+                Some(surface) => {
+                    (surface.output.clone(),
+                     surface.geometry,
+                     surface.state.keyboard_interactivity && surface.state.layer >= Layer::Top)
+                }
+                None => return false,
+            }
+        };
+
+        view.set_visibility(Visibility::Flags::all());
+        if let Some(geometry) = geometry {
+            view.set_geometry(ResizeEdge::Null, geometry);
+        }
+        if let Some(output) = output {
+            output.run(|output| {
+                view.set_output(output);
+                self.restack(output).is_ok()
+            });
+        }
+        if focus {
+            view.focus();
+        }
+
+        true
+    }
+
+    /// Forward of `Callback::view_destroyed`
+    ///
+    /// Returns `true` if the view was a layer surface. The remaining
+    /// surfaces get rearranged, as the usable area may have changed.
+    pub fn view_destroyed(&mut self, view: &View) -> bool {
+        let mut state = self.state.borrow_mut();
+        match state.surfaces.iter().position(|surface| surface.view == *view) {
+            Some(index) => {
+                state.surfaces.remove(index);
+                state.schedule_configure();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Forward of `Callback::output_resolution`
+    pub fn output_resolution(&mut self, _output: &Output) {
+        self.state.borrow_mut().arrange();
+    }
+
+    /// Forward of `Callback::output_destroyed`
+    ///
+    /// Closes all layer surfaces of the output.
+    pub fn output_destroyed(&mut self, output: &Output) {
+        let mut state = self.state.borrow_mut();
+        state.usable_areas.remove(&output.weak_reference());
+
+        for surface in &mut state.surfaces {
+            if surface.output.as_ref().map_or(false, |weak| weak == output) {
+                surface.output = None;
+                unsafe { raw::wl_resource_post_event(surface.resource, SURFACE_EVENT_CLOSED) };
+            }
+        }
+    }
+}
+
+unsafe fn focused_output() -> Option<WeakOutput> {
+    match ffi::wlc_get_focused_output() {
+        0 => None,
+        handle => Some((&*(handle as *const Output)).weak_reference()),
+    }
+}
+
+unsafe extern "C" fn bind_layer_shell(client: *mut raw::wl_client, data: *mut c_void, version: u32, id: u32) {
+    if let Some(state) = raw::from_user_data::<State>(data) {
+        raw::create_resource(client,
+                             &LAYER_SHELL_INTERFACE,
+                             cmp::min(version, VERSION),
+                             id,
+                             &LAYER_SHELL_IMPLEMENTATION,
+                             &state);
+    }
+}
+
+unsafe extern "C" fn get_layer_surface(client: *mut raw::wl_client, resource: *mut raw::wl_resource, id: u32,
+                                       surface: *mut raw::wl_resource, output: *mut raw::wl_resource,
+                                       layer: u32, namespace: *const c_char) {
+    let state = match raw::state::<State>(resource) {
+        Some(state) => state,
+        None => return,
+    };
+
+    let layer = match Layer::from_u32(layer) {
+        Some(layer) => layer,
+        None => {
+            return raw::wl_resource_post_error(resource,
+                                               SHELL_ERROR_INVALID_LAYER,
+                                               cstr!("invalid layer %u"),
+                                               layer)
+        }
+    };
+
+    let output = if output.is_null() {
+        focused_output()
+    } else {
+        match ffi::wlc_handle_from_wl_output_resource(output as *mut _) {
+            0 => None,
+            handle => Some((&*(handle as *const Output)).weak_reference()),
+        }
+    };
+
+    let wlc_surface = &*(ffi::wlc_resource_from_wl_surface_resource(surface as *mut _) as *const WlcSurface);
+    let role = RoleInterface::from_raw(&LAYER_SURFACE_INTERFACE as *const _ as *const c_void,
+                                       "zwlr_layer_surface_v1",
+                                       raw::wl_resource_get_version(resource) as u32,
+                                       VERSION);
+    let data = raw::into_user_data(&state);
+
+    match View::from_wl_surface(wlc_surface,
+                                &Client::from_ptr(client as *mut _),
+                                role,
+                                id,
+                                &LAYER_SURFACE_IMPLEMENTATION as *const _ as *const c_void,
+                                data) {
+        Ok(view) => {
+            let role = match view.run(|view| view.role().map(|role| role.ptr())) {
+                Some(Some(role)) => role,
+                _ => {
+                    raw::free_user_data::<State>(data);
+                    return;
+                }
+            };
+            raw::on_resource_destroyed(role, move |_| {
+                if let Some(state) = raw::from_user_data::<State>(data) {
+                    state.borrow_mut().surfaces.retain(|surface| surface.resource != role);
+                }
+                raw::free_user_data::<State>(data);
+            });
+            if output.is_none() {
+                raw::wl_resource_post_event(role, SURFACE_EVENT_CLOSED);
+            }
+
+            let mut state = state.borrow_mut();
+            state.surfaces.push(LayerSurface {
+                                    resource: role,
+                                    view: view,
+                                    output: output,
+                                    namespace: CStr::from_ptr(namespace).to_string_lossy().into_owned(),
+                                    state: LayerSurfaceState::new(layer),
+                                    geometry: None,
+                                    configured: None,
+                                });
+            state.schedule_configure();
+        }
+        Err(RoleError::InternalError) => {
+            raw::free_user_data::<State>(data);
+            raw::wl_client_post_no_memory(client);
+        }
+        Err(RoleError::AlreadyHasRole) => {
+            raw::free_user_data::<State>(data);
+            raw::wl_resource_post_error(resource,
+                                        SHELL_ERROR_ALREADY_CONSTRUCTED,
+                                        cstr!("wl_surface already has a role"));
+        }
+        Err(_) => {
+            raw::free_user_data::<State>(data);
+            raw::wl_resource_post_error(resource, SHELL_ERROR_ROLE, cstr!("invalid wl_surface"));
+        }
+    }
+}
+
+unsafe fn with_surface<F>(resource: *mut raw::wl_resource, func: F)
+    where F: FnOnce(&mut LayerSurfaceState)
+{
+    if let Some(state) = raw::state::<State>(resource) {
+        let mut state = state.borrow_mut();
+        if let Some(surface) = state.surface_mut(resource) {
+            func(&mut surface.state);
+        }
+        state.schedule_configure();
+    }
+}
+
+unsafe extern "C" fn set_size(_client: *mut raw::wl_client, resource: *mut raw::wl_resource, width: u32,
+                              height: u32) {
+    let mut valid = true;
+    with_surface(resource, |state| {
+        state.size = Size { w: width, h: height };
+        valid = state.is_size_valid();
+    });
+    if !valid {
+        raw::wl_resource_post_error(resource,
+                                    SURFACE_ERROR_INVALID_SIZE,
+                                    cstr!("invalid size %ux%u"),
+                                    width,
+                                    height);
+    }
+}
+
+unsafe extern "C" fn set_anchor(_client: *mut raw::wl_client, resource: *mut raw::wl_resource, anchor: u32) {
+    match LayerAnchor::Flags::from_bits(anchor) {
+        Some(anchor) => with_surface(resource, |state| state.anchor = anchor),
+        None => {
+            raw::wl_resource_post_error(resource,
+                                        SURFACE_ERROR_INVALID_ANCHOR,
+                                        cstr!("invalid anchor %u"),
+                                        anchor)
+        }
+    }
+}
+
+unsafe extern "C" fn set_exclusive_zone(_client: *mut raw::wl_client, resource: *mut raw::wl_resource,
+                                        zone: i32) {
+    with_surface(resource, |state| state.exclusive_zone = zone);
+}
+
+unsafe extern "C" fn set_margin(_client: *mut raw::wl_client, resource: *mut raw::wl_resource, top: i32,
+                                right: i32, bottom: i32, left: i32) {
+    with_surface(resource, |state| {
+        state.margin = Margin {
+            top: top,
+            right: right,
+            bottom: bottom,
+            left: left,
+        }
+    });
+}
+
+unsafe extern "C" fn set_keyboard_interactivity(_client: *mut raw::wl_client, resource: *mut raw::wl_resource,
+                                                interactivity: u32) {
+    with_surface(resource, |state| state.keyboard_interactivity = interactivity != 0);
+}
+
+unsafe extern "C" fn get_popup(_client: *mut raw::wl_client, _resource: *mut raw::wl_resource,
+                               _popup: *mut raw::wl_resource) {
+    // wlc assigns the parent of xdg popups itself
+}
+
+unsafe extern "C" fn ack_configure(_client: *mut raw::wl_client, _resource: *mut raw::wl_resource,
+                                   _serial: u32) {
+    // geometry is applied on arrange, there is no pending state to commit
+}
+
+unsafe extern "C" fn destroy(_client: *mut raw::wl_client, resource: *mut raw::wl_resource) {
+    let state = raw::state::<State>(resource);
+    // the destroy listener cleans up our state
+    raw::wl_resource_destroy(resource);
+    if let Some(state) = state {
+        state.borrow_mut().schedule_configure();
+    }
+}
+
+#[repr(C)]
+struct LayerShellImplementation {
+    get_layer_surface: unsafe extern "C" fn(*mut raw::wl_client,
+                                            *mut raw::wl_resource,
+                                            u32,
+                                            *mut raw::wl_resource,
+                                            *mut raw::wl_resource,
+                                            u32,
+                                            *const c_char),
+}
+
+#[repr(C)]
+struct LayerSurfaceImplementation {
+    set_size: unsafe extern "C" fn(*mut raw::wl_client, *mut raw::wl_resource, u32, u32),
+    set_anchor: unsafe extern "C" fn(*mut raw::wl_client, *mut raw::wl_resource, u32),
+    set_exclusive_zone: unsafe extern "C" fn(*mut raw::wl_client, *mut raw::wl_resource, i32),
+    set_margin: unsafe extern "C" fn(*mut raw::wl_client, *mut raw::wl_resource, i32, i32, i32, i32),
+    set_keyboard_interactivity: unsafe extern "C" fn(*mut raw::wl_client, *mut raw::wl_resource, u32),
+    get_popup: unsafe extern "C" fn(*mut raw::wl_client, *mut raw::wl_resource, *mut raw::wl_resource),
+    ack_configure: unsafe extern "C" fn(*mut raw::wl_client, *mut raw::wl_resource, u32),
+    destroy: unsafe extern "C" fn(*mut raw::wl_client, *mut raw::wl_resource),
+}
+
+static LAYER_SHELL_IMPLEMENTATION: LayerShellImplementation =
+    LayerShellImplementation { get_layer_surface: get_layer_surface };
+
+static LAYER_SURFACE_IMPLEMENTATION: LayerSurfaceImplementation = LayerSurfaceImplementation {
+    set_size: set_size,
+    set_anchor: set_anchor,
+    set_exclusive_zone: set_exclusive_zone,
+    set_margin: set_margin,
+    set_keyboard_interactivity: set_keyboard_interactivity,
+    get_popup: get_popup,
+    ack_configure: ack_configure,
+    destroy: destroy,
+};
+
+static GET_LAYER_SURFACE_TYPES: raw::Types<[*const raw::wl_interface; 5]> =
+    raw::Types([&LAYER_SURFACE_INTERFACE as *const _,
+                unsafe { &raw::wl_surface_interface as *const _ },
+                unsafe { &raw::wl_output_interface as *const _ },
+                0 as *const _,
+                0 as *const _]);
+
+static LAYER_SHELL_REQUESTS: [raw::wl_message; 1] =
+    [message!("get_layer_surface", "no?ous", &GET_LAYER_SURFACE_TYPES)];
+static LAYER_SHELL_EVENTS: [raw::wl_message; 0] = [];
+
+static LAYER_SHELL_INTERFACE: raw::wl_interface =
+    interface!("zwlr_layer_shell_v1", 1, LAYER_SHELL_REQUESTS, LAYER_SHELL_EVENTS);
+
+static LAYER_SURFACE_REQUESTS: [raw::wl_message; 8] = [message!("set_size", "uu", &raw::NULL_TYPES),
+                                                       message!("set_anchor", "u", &raw::NULL_TYPES),
+                                                       message!("set_exclusive_zone", "i", &raw::NULL_TYPES),
+                                                       message!("set_margin", "iiii", &raw::NULL_TYPES),
+                                                       message!("set_keyboard_interactivity",
+                                                                "u",
+                                                                &raw::NULL_TYPES),
+                                                       message!("get_popup", "o", &raw::NULL_TYPES),
+                                                       message!("ack_configure", "u", &raw::NULL_TYPES),
+                                                       message!("destroy", "", &raw::NULL_TYPES)];
+static LAYER_SURFACE_EVENTS: [raw::wl_message; 2] = [message!("configure", "uuu", &raw::NULL_TYPES),
+                                                     message!("closed", "", &raw::NULL_TYPES)];
+
+static LAYER_SURFACE_INTERFACE: raw::wl_interface =
+    interface!("zwlr_layer_surface_v1", 1, LAYER_SURFACE_REQUESTS, LAYER_SURFACE_EVENTS);
+
+#[cfg(test)]
+use test_util::geometry;
+
+#[cfg(test)]
+fn output_1080p() -> Geometry {
+    geometry(0, 0, 1920, 1080)
+}
+
+#[test]
+fn test_arrange_exclusive_bar() {
+    let mut bar = LayerSurfaceState::new(Layer::Top);
+    bar.anchor = LayerAnchor::Top | LayerAnchor::Left | LayerAnchor::Right;
+    bar.size = Size { w: 0, h: 30 };
+    bar.exclusive_zone = 30;
+
+    let (geometries, usable) = arrange(output_1080p(), &[bar]);
+    assert_eq!(geometries[0], geometry(0, 0, 1920, 30));
+    assert_eq!(usable, geometry(0, 30, 1920, 1050));
+}
+
+#[test]
+fn test_arrange_wallpaper_ignores_exclusive_zones() {
+    let mut bar = LayerSurfaceState::new(Layer::Top);
+    bar.anchor = LayerAnchor::Bottom;
+    bar.size = Size { w: 400, h: 40 };
+    bar.exclusive_zone = 40;
+    bar.margin.bottom = 10;
+
+    let mut wallpaper = LayerSurfaceState::new(Layer::Background);
+    wallpaper.anchor = LayerAnchor::all();
+    wallpaper.exclusive_zone = -1;
+
+    let mut widget = LayerSurfaceState::new(Layer::Bottom);
+    widget.size = Size { w: 100, h: 100 };
+
+    let (geometries, usable) = arrange(output_1080p(), &[wallpaper, bar, widget]);
+    assert_eq!(geometries[0], output_1080p());
+    assert_eq!(geometries[1], geometry(760, 1030, 400, 40));
+    assert_eq!(geometries[2], geometry(910, 465, 100, 100));
+    assert_eq!(usable.size, Size { w: 1920, h: 1030 });
+}
+
+#[test]
+fn test_exclusive_edge() {
+    let mut state = LayerSurfaceState::new(Layer::Top);
+    state.exclusive_zone = 10;
+
+    state.anchor = LayerAnchor::Left | LayerAnchor::Top | LayerAnchor::Bottom;
+    assert_eq!(state.exclusive_edge(), Some(LayerAnchor::Left));
+
+    state.anchor = LayerAnchor::Left | LayerAnchor::Top;
+    assert_eq!(state.exclusive_edge(), None);
+
+    state.anchor = LayerAnchor::all();
+    assert_eq!(state.exclusive_edge(), None);
+}
+
+#[test]
+fn test_exclusive_zone_negative_margin() {
+    let mut bar = LayerSurfaceState::new(Layer::Top);
+    bar.anchor = LayerAnchor::Top;
+    bar.size = Size { w: 400, h: 30 };
+    bar.exclusive_zone = 30;
+    bar.margin.top = -50;
+
+    let mut usable = output_1080p();
+    apply_exclusive_zone(&bar, &mut usable);
+    assert_eq!(usable, output_1080p());
+}
+
+#[test]
+fn test_size_valid() {
+    let mut state = LayerSurfaceState::new(Layer::Top);
+    state.size = Size { w: 0, h: 30 };
+    assert!(!state.is_size_valid());
+    state.anchor = LayerAnchor::Left | LayerAnchor::Right;
+    assert!(state.is_size_valid());
+    state.size = Size { w: 0, h: 0 };
+    assert!(!state.is_size_valid());
+}
@@ -0,0 +1,382 @@
+//! wlc's Wayland extensions
+//!
+//! Enabled by feature `wayland`
+//!
+//! Some of the original function may be directly added as functions to View
+//! and Output,
+//! when this feature is enabled.
+//!
+//! `Display`, `View::role` and `View::from_wl_surface` provide the building
+//! blocks to implement your own protocols on top of wlc.
+
+use {Geometry, Output, Size, View, WeakOutput, WeakView};
+
+use ffi;
+use libc::c_void;
+
+use std::borrow::Cow;
+use std::error::Error as ErrorTrait;
+use std::ffi::CStr;
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem;
+use std::slice;
+
+use wayland_server::{Client, Resource};
+use wayland_server::protocol::wl_output::WlOutput;
+use wayland_server::protocol::wl_surface::WlSurface;
+
+#[macro_use]
+mod raw;
+
+pub mod clients;
+pub mod gamma_control;
+pub mod layer_shell;
+pub mod output_management;
+#[cfg(feature = "render")]
+pub mod screencopy;
+pub mod surface_tree;
+pub mod toplevel_management;
+
+#[allow(missing_docs)]
+/// The raw wlc functions wrapped by `Display`, `View::role` and
+/// `View::from_wl_surface`.
+///
+/// Prefer the safe variants, these are only kept for compatibility.
+pub mod sys {
+    pub use ffi::wlc_get_wl_display;
+    pub use ffi::wlc_view_from_surface;
+    pub use ffi::wlc_view_get_role;
+}
+
+/// Handle to the `wl_display` wlc runs its clients on
+///
+/// Can be optained by `Display::get` once the compositor is initialized.
+#[derive(Clone)]
+pub struct Display(*mut raw::wl_display);
+
+#[cfg(not(feature = "unsafe-stable"))]
+impl !Sync for Display {}
+#[cfg(not(feature = "unsafe-stable"))]
+impl !Send for Display {}
+
+impl fmt::Debug for Display {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Display {{ ptr: {:?} }}", self.0)
+    }
+}
+
+impl Display {
+    /// Get the display of the running compositor
+    ///
+    /// Returns `None` if wlc is not initialized (yet).
+    ///
+    /// # Safety
+    /// Dont call this function on another thread, then the main thread
+    pub fn get() -> Option<Display> {
+        let ptr = unsafe { ffi::wlc_get_wl_display() };
+        if ptr.is_null() { None } else { Some(Display(ptr as *mut _)) }
+    }
+
+    /// Raw `wl_display` pointer for use with other wayland libraries
+    pub fn ptr(&self) -> *mut c_void {
+        self.0
+    }
+
+    /// Get the last serial send to clients
+    pub fn serial(&self) -> u32 {
+        unsafe { raw::wl_display_get_serial(self.0) }
+    }
+
+    /// Get a new serial, e.g. for configure events
+    pub fn next_serial(&self) -> u32 {
+        unsafe { raw::wl_display_next_serial(self.0) }
+    }
+}
+
+/// Role resource of a `View`
+///
+/// The role is the shell specific resource (e.g. `xdg_surface` or a custom
+/// one created by `View::from_wl_surface`) that gave the `wl_surface` its
+/// meaning as a `View`.
+/// Can be optained by `view.role()`.
+pub struct Role<'a>(*mut raw::wl_resource, PhantomData<&'a View>);
+
+impl<'a> fmt::Debug for Role<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Role {{ interface: {}, id: {} }}", self.interface_name(), self.id())
+    }
+}
+
+impl<'a> Role<'a> {
+    /// Name of the interface of the role resource, e.g. `xdg_toplevel`
+    pub fn interface_name(&self) -> Cow<str> {
+        unsafe { CStr::from_ptr(raw::wl_resource_get_class(self.0)).to_string_lossy() }
+    }
+
+    /// Protocol id of the role resource
+    pub fn id(&self) -> u32 {
+        unsafe { raw::wl_resource_get_id(self.0) }
+    }
+
+    /// Version of the role resource
+    pub fn version(&self) -> u32 {
+        unsafe { raw::wl_resource_get_version(self.0) as u32 }
+    }
+
+    /// Check if the role is of the given `wayland-server` resource type
+    pub fn is<R: Resource>(&self) -> bool {
+        self.interface_name() == R::interface_name()
+    }
+
+    /// Get the role as a `wayland-server` resource, if it is of the given type
+    ///
+    /// # Safety
+    /// The returned resource needs to already be initialized by
+    /// `wayland-server`, which is not the case for the roles wlc creates
+    /// itself. Only use this for roles created through your own protocol
+    /// implementation.
+    pub unsafe fn downcast<R: Resource>(&self) -> Option<R> {
+        if self.is::<R>() {
+            Some(R::from_ptr_initialized(self.0 as *mut _))
+        } else {
+            None
+        }
+    }
+
+    /// Raw `wl_resource` pointer of the role
+    pub fn ptr(&self) -> *mut c_void {
+        self.0
+    }
+}
+
+/// Interface and version of a custom role created by `View::from_wl_surface`
+#[derive(Clone, Copy, Debug)]
+pub struct RoleInterface {
+    interface: *const c_void,
+    name: &'static str,
+    version: u32,
+    supported_version: u32,
+}
+
+impl RoleInterface {
+    /// Describe a role by a `wayland-server` resource type and the version the
+    /// client requested
+    pub fn new<R: Resource>(version: u32) -> RoleInterface {
+        RoleInterface {
+            interface: R::interface_ptr() as *const _,
+            name: R::interface_name(),
+            version: version,
+            supported_version: R::supported_version(),
+        }
+    }
+
+    /// Describe a role by a raw `wl_interface`
+    ///
+    /// # Safety
+    /// `interface` needs to point to a valid `wl_interface` that lives as
+    /// long as the resources created from it.
+    pub unsafe fn from_raw(interface: *const c_void, name: &'static str, version: u32,
+                           supported_version: u32)
+                           -> RoleInterface {
+        RoleInterface {
+            interface: interface,
+            name: name,
+            version: version,
+            supported_version: supported_version,
+        }
+    }
+
+    /// Name of the interface
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Requested version of the interface
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+}
+
+/// Error representing the failure to create a `View` from a `WlcSurface`
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum RoleError {
+    /// The surface is already a `View`
+    AlreadyHasRole,
+    /// The surface belongs to a different client
+    ClientMismatch,
+    /// The requested version is not supported by the interface
+    UnsupportedVersion(u32),
+    /// wlc failed to create the view (e.g. failure to allocate resources).
+    /// Take a look at the log.
+    InternalError,
+}
+
+impl fmt::Display for RoleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RoleError::UnsupportedVersion(version) => {
+                write!(f, "Error creating View: Unsupported role version {}", version)
+            }
+            _ => write!(f, "Error creating View: {}", self.description()),
+        }
+    }
+}
+
+impl ErrorTrait for RoleError {
+    fn description(&self) -> &str {
+        match *self {
+            RoleError::AlreadyHasRole => "Surface already has a role",
+            RoleError::ClientMismatch => "Surface does not belong to the client",
+            RoleError::UnsupportedVersion(_) => "Unsupported role version",
+            RoleError::InternalError => "Wlc encountered an internal Error",
+        }
+    }
+}
+
+impl View {
+    /// Get the role resource of the view
+    ///
+    /// Returns `None` for views without a wayland role (e.g. xwayland)
+    pub fn role(&self) -> Option<Role> {
+        let ptr = unsafe { ffi::wlc_view_get_role(::view::handle(self)) };
+        if ptr.is_null() { None } else { Some(Role(ptr as *mut _, PhantomData)) }
+    }
+
+    /// Create a new `View` for a surface with a custom role
+    ///
+    /// Use this to implement your own shell protocols. The role resource with
+    /// the given `id` is created by wlc for the `client` and is the
+    /// `View::role` of the new view.
+    ///
+    /// Checks that the surface belongs to `client`, does not already have a
+    /// view and that the requested version is supported by the role
+    /// interface.
+    ///
+    /// # Safety
+    /// `implementation` needs to point to a valid implementation of the role
+    /// interface (as expected by `wl_resource_set_implementation`), that
+    /// lives as long as the resource. `user_data` is passed to the resource
+    /// as is.
+    pub unsafe fn from_wl_surface(surface: &WlcSurface, client: &Client, role: RoleInterface, id: u32,
+                                  implementation: *const c_void, user_data: *mut c_void)
+                                  -> Result<WeakView, RoleError> {
+        if role.version == 0 || role.version > role.supported_version {
+            return Err(RoleError::UnsupportedVersion(role.version));
+        }
+
+        let wl_surface = ffi::wlc_surface_get_wl_resource(handle(surface));
+        if raw::wl_resource_get_client(wl_surface as *mut _) != client.ptr() as *mut _ {
+            return Err(RoleError::ClientMismatch);
+        }
+        if ffi::wlc_handle_from_wl_surface_resource(wl_surface) != 0 {
+            return Err(RoleError::AlreadyHasRole);
+        }
+
+        match ffi::wlc_view_from_surface(handle(surface),
+                                         client.ptr() as *mut _,
+                                         role.interface,
+                                         implementation,
+                                         role.version,
+                                         id,
+                                         user_data) {
+            0 => Err(RoleError::InternalError),
+            view => Ok((&*(view as *const View)).weak_reference()),
+        }
+    }
+}
+
+/// Returns a weak view handle from `WlSurface` resource
+pub fn view_from_wl_surface(surface: &WlSurface) -> WeakView {
+    let view: &View =
+        unsafe { &*(ffi::wlc_handle_from_wl_surface_resource(surface.ptr() as *mut _) as *const View) };
+
+    view.weak_reference()
+}
+
+/// Returns a weak output handle from `WlOutput` resource
+pub fn output_from_wl_surface(output: &WlOutput) -> WeakOutput {
+    let output: &Output =
+        unsafe { &*(ffi::wlc_handle_from_wl_output_resource(output.ptr() as *mut _) as *const Output) };
+
+    output.weak_reference()
+}
+
+/// Internal Surface Struct
+#[repr(C)]
+#[derive(Clone, Debug)]
+pub struct WlcSurface;
+#[cfg(not(feature = "unsafe-stable"))]
+impl !Sync for WlcSurface {}
+#[cfg(not(feature = "unsafe-stable"))]
+impl !Send for WlcSurface {}
+
+/// Returns internal wlc surface from `WlSurface` resource
+pub fn wlc_resource_from_wl_surface(surface: &WlSurface) -> &WlcSurface {
+    unsafe { &*(ffi::wlc_resource_from_wl_surface_resource(surface.ptr() as *mut _) as *const WlcSurface) }
+}
+
+impl WlcSurface {
+    /// Get surface size
+    pub fn size(&self) -> Size {
+        unsafe { Size::from_ffi(&*ffi::wlc_surface_get_size(handle(self))) }
+    }
+
+    /// Return wl_surface resource from internal wlc surface
+    pub fn wl_surface(&self) -> WlSurface {
+        unsafe { WlSurface::from_ptr_new(ffi::wlc_surface_get_wl_resource(handle(self)) as *mut _) }
+    }
+
+    /// Returns a list of the subsurfaces of the given surface
+    pub fn sub_surfaces(&self) -> &[&WlcSubSurface] {
+        unsafe {
+            let mut size = 0;
+            let ptr = ffi::wlc_surface_get_subsurfaces(handle(self), &mut size as *mut _) as *const _;
+            slice::from_raw_parts(ptr, size)
+        }
+    }
+
+    /// Returns a list of the subsurfaces of the given surface, that may be
+    /// modified
+    pub fn sub_surfaces_mut(&mut self) -> &mut [&mut WlcSubSurface] {
+        unsafe {
+            let mut size = 0;
+            let ptr = ffi::wlc_surface_get_subsurfaces(handle(self), &mut size as *mut _) as *mut _;
+            slice::from_raw_parts_mut(ptr, size)
+        }
+    }
+
+    /// Adds frame callbacks of the given surface for the next output frame.
+    /// It applies recursively to all subsurfaces.
+    ///
+    /// Useful when the compositor creates custom animations which require
+    /// disabling internal rendering,
+    /// but still need to update the surface textures (for ex. video players).
+    #[cfg(feature = "render")]
+    pub fn flush_frame_callbacks(&self) {
+        unsafe { ffi::wlc_surface_flush_frame_callbacks(handle(self)) }
+    }
+}
+
+fn handle(surface: &WlcSurface) -> ffi::wlc_resource {
+    unsafe { mem::transmute(surface) }
+}
+
+/// Subsurface of an `WlcSurface`
+#[repr(C)]
+pub struct WlcSubSurface;
+#[cfg(not(feature = "unsafe-stable"))]
+impl !Sync for WlcSubSurface {}
+#[cfg(not(feature = "unsafe-stable"))]
+impl !Send for WlcSubSurface {}
+
+impl WlcSubSurface {
+    /// Returns the size of a subsurface and its position relative to parent
+    pub fn geometry(&self) -> Geometry {
+        unsafe {
+            let mut geo: ffi::wlc_geometry = mem::uninitialized();
+            ffi::wlc_get_subsurface_geometry(mem::transmute(self), &mut geo as *mut _);
+            Geometry::from_ffi(&geo)
+        }
+    }
+}
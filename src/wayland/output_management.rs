@@ -0,0 +1,908 @@
+//! Output management protocol for display configuration tools
+//!
+//! Implements the `zwlr_output_manager_v1` protocol (version 1).
+//! Every `Output` forwarded to the `OutputManagement` is advertised as a head
+//! with its name, resolution, scale and sleep state. The virtual resolution
+//! is part of the head's description. wlc has no list of modes, so every
+//! head only has a single mode matching its current resolution, clients may
+//! request any other resolution as custom mode. Fractional scales are
+//! rejected, wlc only supports integer scales.
+//!
+//! Configurations applied by clients change all outputs at once via
+//! `Output::set_resolution` and `Output::set_sleeping`. If any output ends up
+//! with a different resolution, than the one requested, all outputs are
+//! reverted and the configuration fails. wlc neither arranges nor transforms
+//! outputs, every head is advertised at 0,0 with the normal transform and
+//! configurations requesting anything else fail.
+//!
+//! Create the global before any output is created and forward
+//! `output_created`, `output_destroyed` and `output_resolution`.
+
+use {Output, Point, Size, WeakOutput};
+
+use super::Display;
+use wayland::raw;
+
+use libc::c_void;
+
+use std::cell::RefCell;
+use std::cmp;
+use std::ffi::CString;
+use std::rc::Rc;
+
+const VERSION: u32 = 1;
+
+const MANAGER_EVENT_HEAD: u32 = 0;
+const MANAGER_EVENT_DONE: u32 = 1;
+const MANAGER_EVENT_FINISHED: u32 = 2;
+
+const HEAD_EVENT_NAME: u32 = 0;
+const HEAD_EVENT_DESCRIPTION: u32 = 1;
+const HEAD_EVENT_MODE: u32 = 3;
+const HEAD_EVENT_ENABLED: u32 = 4;
+const HEAD_EVENT_CURRENT_MODE: u32 = 5;
+const HEAD_EVENT_POSITION: u32 = 6;
+const HEAD_EVENT_TRANSFORM: u32 = 7;
+const HEAD_EVENT_SCALE: u32 = 8;
+const HEAD_EVENT_FINISHED: u32 = 9;
+
+const MODE_EVENT_SIZE: u32 = 0;
+const MODE_EVENT_REFRESH: u32 = 1;
+const MODE_EVENT_FINISHED: u32 = 3;
+
+const CONFIGURATION_EVENT_SUCCEEDED: u32 = 0;
+const CONFIGURATION_EVENT_FAILED: u32 = 1;
+const CONFIGURATION_EVENT_CANCELLED: u32 = 2;
+
+const CONFIGURATION_ERROR_ALREADY_CONFIGURED_HEAD: u32 = 1;
+const CONFIGURATION_ERROR_UNCONFIGURED_HEAD: u32 = 2;
+const CONFIGURATION_ERROR_ALREADY_USED: u32 = 3;
+
+const CONFIGURATION_HEAD_ERROR_ALREADY_SET: u32 = 1;
+const CONFIGURATION_HEAD_ERROR_INVALID_MODE: u32 = 2;
+const CONFIGURATION_HEAD_ERROR_INVALID_CUSTOM_MODE: u32 = 3;
+const CONFIGURATION_HEAD_ERROR_INVALID_TRANSFORM: u32 = 4;
+const CONFIGURATION_HEAD_ERROR_INVALID_SCALE: u32 = 5;
+
+/// Convert a `wl_fixed_t` scale into the integer scale used by wlc
+///
+/// wlc only supports integer scales, fractional scales and scales below 1
+/// are invalid.
+fn scale_from_fixed(fixed: i32) -> Option<u32> {
+    if fixed < 256 || fixed % 256 != 0 {
+        None
+    } else {
+        Some((fixed / 256) as u32)
+    }
+}
+
+/// `wl_output.transform` of an untransformed output
+const TRANSFORM_NORMAL: i32 = 0;
+/// Largest valid `wl_output.transform`
+const TRANSFORM_FLIPPED_270: i32 = 7;
+
+/// Check if a requested position and transform match the ones of every
+/// output, unset ones are kept
+fn is_unchanged(position: Option<Point>, transform: Option<i32>) -> bool {
+    position.map(|position| position == Point { x: 0, y: 0 }).unwrap_or(true) &&
+    transform.map(|transform| transform == TRANSFORM_NORMAL).unwrap_or(true)
+}
+
+/// State of an output as advertised to and requested by clients
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Config {
+    enabled: bool,
+    resolution: Size,
+    scale: u32,
+}
+
+impl Config {
+    fn current(output: &Output) -> Config {
+        Config {
+            enabled: !output.is_sleeping(),
+            resolution: output.resolution(),
+            scale: output.scale(),
+        }
+    }
+
+    fn apply(&self, output: &Output) {
+        if output.resolution() != self.resolution || output.scale() != self.scale {
+            output.set_resolution(self.resolution, self.scale);
+        }
+        if output.is_sleeping() == self.enabled {
+            output.set_sleeping(!self.enabled);
+        }
+    }
+}
+
+struct Head {
+    resource: *mut raw::wl_resource,
+    manager: *mut raw::wl_resource,
+    output: WeakOutput,
+    mode: *mut raw::wl_resource,
+    mode_size: Size,
+}
+
+struct ConfigurationHead {
+    resource: *mut raw::wl_resource,
+    output: WeakOutput,
+    enabled: bool,
+    resolution: Option<Size>,
+    scale: Option<u32>,
+    position: Option<Point>,
+    transform: Option<i32>,
+}
+
+struct Configuration {
+    resource: *mut raw::wl_resource,
+    serial: u32,
+    heads: Vec<ConfigurationHead>,
+    used: bool,
+}
+
+struct State {
+    display: Display,
+    serial: u32,
+    outputs: Vec<WeakOutput>,
+    managers: Vec<*mut raw::wl_resource>,
+    heads: Vec<Head>,
+    configurations: Vec<Configuration>,
+    /// Resolutions expected while applying a configuration
+    applying: Vec<(WeakOutput, Size)>,
+    mismatch: bool,
+}
+
+impl State {
+    fn configuration(&mut self, resource: *mut raw::wl_resource) -> Option<&mut Configuration> {
+        self.configurations.iter_mut().find(|configuration| configuration.resource == resource)
+    }
+
+    fn configuration_head(&mut self, resource: *mut raw::wl_resource) -> Option<&mut ConfigurationHead> {
+        self.configurations
+            .iter_mut()
+            .flat_map(|configuration| configuration.heads.iter_mut())
+            .find(|head| head.resource == resource)
+    }
+
+    fn head_output(&self, resource: *mut raw::wl_resource) -> Option<WeakOutput> {
+        self.heads.iter().find(|head| head.resource == resource).map(|head| head.output.clone())
+    }
+
+    /// Size of a mode, if it belongs to a head of the output
+    fn mode_size(&self, output: &WeakOutput, resource: *mut raw::wl_resource) -> Option<Size> {
+        self.heads
+            .iter()
+            .find(|head| head.mode == resource && head.output == *output)
+            .map(|head| head.mode_size)
+    }
+
+    /// Send the current state of all outputs to all managers
+    fn update(&mut self) {
+        self.serial = self.display.next_serial();
+        for head in &mut self.heads {
+            let output = head.output.clone();
+            output.run(|output| unsafe { send_head_state(head, output) });
+        }
+        for manager in &self.managers {
+            unsafe { raw::wl_resource_post_event(*manager, MANAGER_EVENT_DONE, self.serial) };
+        }
+    }
+}
+
+unsafe fn send_string(resource: *mut raw::wl_resource, event: u32, string: &str) {
+    if let Ok(string) = CString::new(string) {
+        raw::wl_resource_post_event(resource, event, string.as_ptr());
+    }
+}
+
+unsafe fn send_head_state(head: &mut Head, output: &Output) {
+    let resolution = output.resolution();
+    if head.mode.is_null() || head.mode_size != resolution {
+        if !head.mode.is_null() {
+            raw::wl_resource_post_event(head.mode, MODE_EVENT_FINISHED);
+        }
+        head.mode = create_mode(head.resource, resolution).unwrap_or(0 as *mut _);
+        head.mode_size = resolution;
+    }
+
+    let virtual_resolution = output.virtual_resolution();
+    send_string(head.resource,
+                HEAD_EVENT_DESCRIPTION,
+                &format!("{} {}x{} (virtual {}x{})",
+                         output.name(),
+                         resolution.w,
+                         resolution.h,
+                         virtual_resolution.w,
+                         virtual_resolution.h));
+    raw::wl_resource_post_event(head.resource, HEAD_EVENT_ENABLED, !output.is_sleeping() as i32);
+    if !head.mode.is_null() {
+        raw::wl_resource_post_event(head.resource, HEAD_EVENT_CURRENT_MODE, head.mode);
+    }
+    raw::wl_resource_post_event(head.resource, HEAD_EVENT_POSITION, 0i32, 0i32);
+    raw::wl_resource_post_event(head.resource, HEAD_EVENT_TRANSFORM, TRANSFORM_NORMAL);
+    raw::wl_resource_post_event(head.resource, HEAD_EVENT_SCALE, (output.scale() * 256) as i32);
+}
+
+unsafe fn create_mode(head: *mut raw::wl_resource, size: Size) -> Option<*mut raw::wl_resource> {
+    let state = match raw::state::<State>(head) {
+        Some(state) => state,
+        None => return None,
+    };
+    let client = raw::wl_resource_get_client(head);
+    let version = raw::wl_resource_get_version(head) as u32;
+    let mode = match raw::create_resource(client, &MODE_INTERFACE, version, 0, &NO_IMPLEMENTATION, &state) {
+        Some(mode) => mode,
+        None => return None,
+    };
+    raw::on_resource_destroyed(mode, |mode| if let Some(state) = raw::state::<State>(mode) {
+        let mut state = state.borrow_mut();
+        for head in state.heads.iter_mut().filter(|head| head.mode == mode) {
+            head.mode = 0 as *mut _;
+        }
+    });
+
+    raw::wl_resource_post_event(head, HEAD_EVENT_MODE, mode);
+    raw::wl_resource_post_event(mode, MODE_EVENT_SIZE, size.w as i32, size.h as i32);
+    // wlc does not expose the refresh rate
+    raw::wl_resource_post_event(mode, MODE_EVENT_REFRESH, 0i32);
+    Some(mode)
+}
+
+unsafe fn create_head(state: &Rc<RefCell<State>>, manager: *mut raw::wl_resource, output: &Output) {
+    let client = raw::wl_resource_get_client(manager);
+    let version = raw::wl_resource_get_version(manager) as u32;
+    let resource = match raw::create_resource(client,
+                                              &HEAD_INTERFACE,
+                                              version,
+                                              0,
+                                              &NO_IMPLEMENTATION,
+                                              state) {
+        Some(resource) => resource,
+        None => return,
+    };
+    raw::on_resource_destroyed(resource, |resource| if let Some(state) = raw::state::<State>(resource) {
+        state.borrow_mut().heads.retain(|head| head.resource != resource);
+    });
+
+    raw::wl_resource_post_event(manager, MANAGER_EVENT_HEAD, resource);
+    send_string(resource, HEAD_EVENT_NAME, &output.name());
+
+    let mut head = Head {
+        resource: resource,
+        manager: manager,
+        output: output.weak_reference(),
+        mode: 0 as *mut _,
+        mode_size: output.resolution(),
+    };
+    send_head_state(&mut head, output);
+    state.borrow_mut().heads.push(head);
+}
+
+unsafe fn finish_head(head: &Head) {
+    if !head.mode.is_null() {
+        raw::wl_resource_post_event(head.mode, MODE_EVENT_FINISHED);
+    }
+    raw::wl_resource_post_event(head.resource, HEAD_EVENT_FINISHED);
+}
+
+/// Output management global
+///
+/// Dropping this removes the global, existing clients are no longer updated.
+pub struct OutputManagement {
+    state: Rc<RefCell<State>>,
+    _global: raw::Global<State>,
+}
+
+impl OutputManagement {
+    /// Advertise the output management global on the given `Display`
+    pub fn new(display: &Display) -> OutputManagement {
+        let state = Rc::new(RefCell::new(State {
+                                              display: display.clone(),
+                                              serial: display.next_serial(),
+                                              outputs: Vec::new(),
+                                              managers: Vec::new(),
+                                              heads: Vec::new(),
+                                              configurations: Vec::new(),
+                                              applying: Vec::new(),
+                                              mismatch: false,
+                                          }));
+
+        let global = unsafe {
+            raw::Global::new(display.ptr(),
+                             &MANAGER_INTERFACE,
+                             VERSION,
+                             &state,
+                             bind_output_manager)
+        };
+
+        OutputManagement {
+            state: state,
+            _global: global,
+        }
+    }
+
+    /// Send the current state of all outputs to the clients
+    ///
+    /// Call this after changing outputs from the compositor, e.g. with
+    /// `Output::set_sleeping`.
+    pub fn update(&mut self) {
+        self.state.borrow_mut().update();
+    }
+
+    /// Forward of `Callback::output_created`
+    pub fn output_created(&mut self, output: &Output) {
+        let managers = {
+            let mut state = self.state.borrow_mut();
+            if state.outputs.iter().any(|other| *other == *output) {
+                return;
+            }
+            state.outputs.push(output.weak_reference());
+            state.managers.clone()
+        };
+
+        for manager in managers {
+            unsafe { create_head(&self.state, manager, output) };
+        }
+        self.state.borrow_mut().update();
+    }
+
+    /// Forward of `Callback::output_destroyed`
+    pub fn output_destroyed(&mut self, output: &Output) {
+        let mut state = self.state.borrow_mut();
+        for head in state.heads.iter().filter(|head| head.output == *output) {
+            unsafe { finish_head(head) };
+        }
+        state.heads.retain(|head| head.output != *output);
+        state.outputs.retain(|other| *other != *output);
+        state.update();
+    }
+
+    /// Forward of `Callback::output_resolution`
+    pub fn output_resolution(&mut self, output: &Output, _from: Size, to: Size) {
+        let mut state = self.state.borrow_mut();
+        if state.applying.is_empty() {
+            state.update();
+        } else if state.applying.iter().any(|&(ref other, size)| *other == *output && size != to) {
+            state.mismatch = true;
+        }
+    }
+}
+
+unsafe extern "C" fn bind_output_manager(client: *mut raw::wl_client, data: *mut c_void, version: u32,
+                                         id: u32) {
+    let state = match raw::from_user_data::<State>(data) {
+        Some(state) => state,
+        None => return,
+    };
+    let manager = match raw::create_resource(client,
+                                             &MANAGER_INTERFACE,
+                                             cmp::min(version, VERSION),
+                                             id,
+                                             &MANAGER_IMPLEMENTATION,
+                                             &state) {
+        Some(manager) => manager,
+        None => return,
+    };
+    raw::on_resource_destroyed(manager, |manager| if let Some(state) = raw::state::<State>(manager) {
+        state.borrow_mut().managers.retain(|other| *other != manager);
+    });
+
+    let outputs = {
+        let mut state = state.borrow_mut();
+        state.managers.push(manager);
+        state.outputs.clone()
+    };
+    for output in outputs {
+        output.run(|output| create_head(&state, manager, output));
+    }
+
+    let serial = state.borrow().serial;
+    raw::wl_resource_post_event(manager, MANAGER_EVENT_DONE, serial);
+}
+
+unsafe extern "C" fn create_configuration(client: *mut raw::wl_client, resource: *mut raw::wl_resource,
+                                          id: u32, serial: u32) {
+    let state = match raw::state::<State>(resource) {
+        Some(state) => state,
+        None => return,
+    };
+    let version = raw::wl_resource_get_version(resource) as u32;
+    let configuration = match raw::create_resource(client,
+                                                   &CONFIGURATION_INTERFACE,
+                                                   version,
+                                                   id,
+                                                   &CONFIGURATION_IMPLEMENTATION,
+                                                   &state) {
+        Some(configuration) => configuration,
+        None => return,
+    };
+    raw::on_resource_destroyed(configuration,
+                               |configuration| if let Some(state) = raw::state::<State>(configuration) {
+        state.borrow_mut().configurations.retain(|other| other.resource != configuration);
+    });
+
+    state.borrow_mut().configurations.push(Configuration {
+                                               resource: configuration,
+                                               serial: serial,
+                                               heads: Vec::new(),
+                                               used: false,
+                                           });
+}
+
+unsafe extern "C" fn stop(_client: *mut raw::wl_client, resource: *mut raw::wl_resource) {
+    if let Some(state) = raw::state::<State>(resource) {
+        let mut state = state.borrow_mut();
+        state.managers.retain(|manager| *manager != resource);
+        for head in state.heads.iter().filter(|head| head.manager == resource) {
+            finish_head(head);
+        }
+        state.heads.retain(|head| head.manager != resource);
+    }
+    raw::wl_resource_post_event(resource, MANAGER_EVENT_FINISHED);
+    raw::wl_resource_destroy(resource);
+}
+
+unsafe fn configure_head(client: *mut raw::wl_client, resource: *mut raw::wl_resource, id: Option<u32>,
+                         head: *mut raw::wl_resource) {
+    let state = match raw::state::<State>(resource) {
+        Some(state) => state,
+        None => return,
+    };
+
+    let configuration_head = match id {
+        Some(id) => {
+            let version = raw::wl_resource_get_version(resource) as u32;
+            match raw::create_resource(client,
+                                       &CONFIGURATION_HEAD_INTERFACE,
+                                       version,
+                                       id,
+                                       &CONFIGURATION_HEAD_IMPLEMENTATION,
+                                       &state) {
+                Some(configuration_head) => {
+                    raw::on_resource_destroyed(configuration_head, |configuration_head| {
+                        if let Some(state) = raw::state::<State>(configuration_head) {
+                            let mut state = state.borrow_mut();
+                            if let Some(head) = state.configuration_head(configuration_head) {
+                                head.resource = 0 as *mut _;
+                            }
+                        }
+                    });
+                    configuration_head
+                }
+                None => return,
+            }
+        }
+        None => 0 as *mut _,
+    };
+
+    let mut state = state.borrow_mut();
+    // the head was already removed, the configuration will be cancelled
+    let output = match state.head_output(head) {
+        Some(output) => output,
+        None => return,
+    };
+    if let Some(configuration) = state.configuration(resource) {
+        if configuration.heads.iter().any(|other| other.output == output) {
+            return raw::wl_resource_post_error(resource,
+                                               CONFIGURATION_ERROR_ALREADY_CONFIGURED_HEAD,
+                                               cstr!("head was already configured"));
+        }
+        configuration.heads.push(ConfigurationHead {
+                                     resource: configuration_head,
+                                     output: output,
+                                     enabled: id.is_some(),
+                                     resolution: None,
+                                     scale: None,
+                                     position: None,
+                                     transform: None,
+                                 });
+    }
+}
+
+unsafe extern "C" fn enable_head(client: *mut raw::wl_client, resource: *mut raw::wl_resource, id: u32,
+                                 head: *mut raw::wl_resource) {
+    configure_head(client, resource, Some(id), head);
+}
+
+unsafe extern "C" fn disable_head(client: *mut raw::wl_client, resource: *mut raw::wl_resource,
+                                  head: *mut raw::wl_resource) {
+    configure_head(client, resource, None, head);
+}
+
+enum Outcome {
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+/// Check a configuration and return the requested state of every output
+unsafe fn prepare(state: &mut State, resource: *mut raw::wl_resource)
+                  -> Option<Result<Vec<(WeakOutput, Config)>, Outcome>> {
+    let serial = state.serial;
+    let outputs = state.outputs.clone();
+    let configuration = match state.configuration(resource) {
+        Some(configuration) => configuration,
+        None => return None,
+    };
+
+    if configuration.used {
+        raw::wl_resource_post_error(resource,
+                                    CONFIGURATION_ERROR_ALREADY_USED,
+                                    cstr!("configuration was already used"));
+        return None;
+    }
+    configuration.used = true;
+
+    if configuration.serial != serial {
+        return Some(Err(Outcome::Cancelled));
+    }
+    if outputs.iter().any(|output| !configuration.heads.iter().any(|head| head.output == *output)) {
+        raw::wl_resource_post_error(resource,
+                                    CONFIGURATION_ERROR_UNCONFIGURED_HEAD,
+                                    cstr!("not all heads were configured"));
+        return None;
+    }
+
+    if configuration.heads.iter().any(|head| !is_unchanged(head.position, head.transform)) {
+        return Some(Err(Outcome::Failed));
+    }
+
+    let mut configs = Vec::new();
+    for head in &configuration.heads {
+        let config = match head.output.run(|output| Config::current(output)) {
+            Some(current) => {
+                Config {
+                    enabled: head.enabled,
+                    resolution: head.resolution.unwrap_or(current.resolution),
+                    scale: head.scale.unwrap_or(current.scale),
+                }
+            }
+            None => return Some(Err(Outcome::Cancelled)),
+        };
+        configs.push((head.output.clone(), config));
+    }
+    Some(Ok(configs))
+}
+
+unsafe fn apply_configuration(resource: *mut raw::wl_resource, test_only: bool) {
+    let state = match raw::state::<State>(resource) {
+        Some(state) => state,
+        None => return,
+    };
+
+    let configs = match prepare(&mut state.borrow_mut(), resource) {
+        Some(Ok(configs)) => configs,
+        Some(Err(outcome)) => return send_outcome(resource, outcome),
+        None => return,
+    };
+    if test_only {
+        return send_outcome(resource, Outcome::Succeeded);
+    }
+
+    let previous: Vec<(WeakOutput, Config)> = configs.iter()
+        .filter_map(|&(ref output, _)| {
+                        output.run(|output| (output.weak_reference(), Config::current(output)))
+                    })
+        .collect();
+
+    {
+        let mut state = state.borrow_mut();
+        state.applying = configs.iter()
+            .map(|&(ref output, config)| (output.clone(), config.resolution))
+            .collect();
+        state.mismatch = false;
+    }
+
+    // setting the resolution will trigger `output_resolution`, so the state
+    // may not be borrowed
+    for &(ref output, config) in &configs {
+        output.run(|output| config.apply(output));
+    }
+
+    let mismatch = {
+        let mut state = state.borrow_mut();
+        state.applying.clear();
+        state.mismatch ||
+        configs.iter()
+            .any(|&(ref output, config)| {
+                     output.run(|output| output.resolution() != config.resolution) == Some(true)
+                 })
+    };
+
+    if mismatch {
+        for &(ref output, config) in &previous {
+            output.run(|output| config.apply(output));
+        }
+        send_outcome(resource, Outcome::Failed);
+    } else {
+        send_outcome(resource, Outcome::Succeeded);
+    }
+    state.borrow_mut().update();
+}
+
+unsafe fn send_outcome(resource: *mut raw::wl_resource, outcome: Outcome) {
+    let event = match outcome {
+        Outcome::Succeeded => CONFIGURATION_EVENT_SUCCEEDED,
+        Outcome::Failed => CONFIGURATION_EVENT_FAILED,
+        Outcome::Cancelled => CONFIGURATION_EVENT_CANCELLED,
+    };
+    raw::wl_resource_post_event(resource, event);
+}
+
+unsafe extern "C" fn apply(_client: *mut raw::wl_client, resource: *mut raw::wl_resource) {
+    apply_configuration(resource, false);
+}
+
+unsafe extern "C" fn test(_client: *mut raw::wl_client, resource: *mut raw::wl_resource) {
+    apply_configuration(resource, true);
+}
+
+unsafe extern "C" fn destroy(_client: *mut raw::wl_client, resource: *mut raw::wl_resource) {
+    raw::wl_resource_destroy(resource);
+}
+
+unsafe fn set_resolution(resource: *mut raw::wl_resource, size: Option<Size>, error: u32) {
+    let state = match raw::state::<State>(resource) {
+        Some(state) => state,
+        None => return,
+    };
+    let mut state = state.borrow_mut();
+    let head = match state.configuration_head(resource) {
+        Some(head) => head,
+        None => return,
+    };
+
+    match size {
+        Some(_) if head.resolution.is_some() => {
+            raw::wl_resource_post_error(resource,
+                                        CONFIGURATION_HEAD_ERROR_ALREADY_SET,
+                                        cstr!("mode was already set"))
+        }
+        Some(size) => head.resolution = Some(size),
+        None => raw::wl_resource_post_error(resource, error, cstr!("invalid mode")),
+    }
+}
+
+unsafe extern "C" fn set_mode(_client: *mut raw::wl_client, resource: *mut raw::wl_resource,
+                              mode: *mut raw::wl_resource) {
+    let size = match raw::state::<State>(resource) {
+        Some(state) => {
+            let mut state = state.borrow_mut();
+            let output = match state.configuration_head(resource) {
+                Some(head) => head.output.clone(),
+                None => return,
+            };
+            // modes of other heads are invalid
+            let size = state.mode_size(&output, mode);
+            size
+        }
+        None => return,
+    };
+    set_resolution(resource, size, CONFIGURATION_HEAD_ERROR_INVALID_MODE);
+}
+
+unsafe extern "C" fn set_custom_mode(_client: *mut raw::wl_client, resource: *mut raw::wl_resource,
+                                     width: i32, height: i32, _refresh: i32) {
+    let size = if width > 0 && height > 0 {
+        Some(Size {
+                 w: width as u32,
+                 h: height as u32,
+             })
+    } else {
+        None
+    };
+    set_resolution(resource, size, CONFIGURATION_HEAD_ERROR_INVALID_CUSTOM_MODE);
+}
+
+unsafe extern "C" fn set_position(_client: *mut raw::wl_client, resource: *mut raw::wl_resource, x: i32,
+                                  y: i32) {
+    let state = match raw::state::<State>(resource) {
+        Some(state) => state,
+        None => return,
+    };
+    let mut state = state.borrow_mut();
+    let head = match state.configuration_head(resource) {
+        Some(head) => head,
+        None => return,
+    };
+
+    // checked when the configuration is applied
+    if head.position.is_some() {
+        raw::wl_resource_post_error(resource,
+                                    CONFIGURATION_HEAD_ERROR_ALREADY_SET,
+                                    cstr!("position was already set"))
+    } else {
+        head.position = Some(Point { x: x, y: y });
+    }
+}
+
+unsafe extern "C" fn set_transform(_client: *mut raw::wl_client, resource: *mut raw::wl_resource,
+                                   transform: i32) {
+    let state = match raw::state::<State>(resource) {
+        Some(state) => state,
+        None => return,
+    };
+    let mut state = state.borrow_mut();
+    let head = match state.configuration_head(resource) {
+        Some(head) => head,
+        None => return,
+    };
+
+    if transform < TRANSFORM_NORMAL || transform > TRANSFORM_FLIPPED_270 {
+        raw::wl_resource_post_error(resource,
+                                    CONFIGURATION_HEAD_ERROR_INVALID_TRANSFORM,
+                                    cstr!("invalid transform"))
+    } else if head.transform.is_some() {
+        raw::wl_resource_post_error(resource,
+                                    CONFIGURATION_HEAD_ERROR_ALREADY_SET,
+                                    cstr!("transform was already set"))
+    } else {
+        head.transform = Some(transform);
+    }
+}
+
+unsafe extern "C" fn set_scale(_client: *mut raw::wl_client, resource: *mut raw::wl_resource, scale: i32) {
+    let state = match raw::state::<State>(resource) {
+        Some(state) => state,
+        None => return,
+    };
+    let mut state = state.borrow_mut();
+    let head = match state.configuration_head(resource) {
+        Some(head) => head,
+        None => return,
+    };
+
+    match scale_from_fixed(scale) {
+        Some(_) if head.scale.is_some() => {
+            raw::wl_resource_post_error(resource,
+                                        CONFIGURATION_HEAD_ERROR_ALREADY_SET,
+                                        cstr!("scale was already set"))
+        }
+        Some(scale) => head.scale = Some(scale),
+        None => {
+            raw::wl_resource_post_error(resource,
+                                        CONFIGURATION_HEAD_ERROR_INVALID_SCALE,
+                                        cstr!("invalid scale"))
+        }
+    }
+}
+
+#[repr(C)]
+struct ManagerImplementation {
+    create_configuration: unsafe extern "C" fn(*mut raw::wl_client, *mut raw::wl_resource, u32, u32),
+    stop: unsafe extern "C" fn(*mut raw::wl_client, *mut raw::wl_resource),
+}
+
+#[repr(C)]
+struct ConfigurationImplementation {
+    enable_head: unsafe extern "C" fn(*mut raw::wl_client, *mut raw::wl_resource, u32, *mut raw::wl_resource),
+    disable_head: unsafe extern "C" fn(*mut raw::wl_client, *mut raw::wl_resource, *mut raw::wl_resource),
+    apply: unsafe extern "C" fn(*mut raw::wl_client, *mut raw::wl_resource),
+    test: unsafe extern "C" fn(*mut raw::wl_client, *mut raw::wl_resource),
+    destroy: unsafe extern "C" fn(*mut raw::wl_client, *mut raw::wl_resource),
+}
+
+#[repr(C)]
+struct ConfigurationHeadImplementation {
+    set_mode: unsafe extern "C" fn(*mut raw::wl_client, *mut raw::wl_resource, *mut raw::wl_resource),
+    set_custom_mode: unsafe extern "C" fn(*mut raw::wl_client, *mut raw::wl_resource, i32, i32, i32),
+    set_position: unsafe extern "C" fn(*mut raw::wl_client, *mut raw::wl_resource, i32, i32),
+    set_transform: unsafe extern "C" fn(*mut raw::wl_client, *mut raw::wl_resource, i32),
+    set_scale: unsafe extern "C" fn(*mut raw::wl_client, *mut raw::wl_resource, i32),
+}
+
+/// Heads and modes have no requests in version 1
+#[repr(C)]
+struct NoImplementation;
+
+static MANAGER_IMPLEMENTATION: ManagerImplementation = ManagerImplementation {
+    create_configuration: create_configuration,
+    stop: stop,
+};
+
+static CONFIGURATION_IMPLEMENTATION: ConfigurationImplementation = ConfigurationImplementation {
+    enable_head: enable_head,
+    disable_head: disable_head,
+    apply: apply,
+    test: test,
+    destroy: destroy,
+};
+
+static CONFIGURATION_HEAD_IMPLEMENTATION: ConfigurationHeadImplementation = ConfigurationHeadImplementation {
+    set_mode: set_mode,
+    set_custom_mode: set_custom_mode,
+    set_position: set_position,
+    set_transform: set_transform,
+    set_scale: set_scale,
+};
+
+static NO_IMPLEMENTATION: NoImplementation = NoImplementation;
+
+static CREATE_CONFIGURATION_TYPES: raw::Types<[*const raw::wl_interface; 2]> =
+    raw::Types([&CONFIGURATION_INTERFACE as *const _, 0 as *const _]);
+static HEAD_TYPES: raw::Types<[*const raw::wl_interface; 1]> = raw::Types([&HEAD_INTERFACE as *const _]);
+static MODE_TYPES: raw::Types<[*const raw::wl_interface; 1]> = raw::Types([&MODE_INTERFACE as *const _]);
+static ENABLE_HEAD_TYPES: raw::Types<[*const raw::wl_interface; 2]> =
+    raw::Types([&CONFIGURATION_HEAD_INTERFACE as *const _, &HEAD_INTERFACE as *const _]);
+
+static MANAGER_REQUESTS: [raw::wl_message; 2] =
+    [message!("create_configuration", "nu", &CREATE_CONFIGURATION_TYPES),
+     message!("stop", "", &raw::NULL_TYPES)];
+static MANAGER_EVENTS: [raw::wl_message; 3] = [message!("head", "n", &HEAD_TYPES),
+                                               message!("done", "u", &raw::NULL_TYPES),
+                                               message!("finished", "", &raw::NULL_TYPES)];
+
+static MANAGER_INTERFACE: raw::wl_interface =
+    interface!("zwlr_output_manager_v1", 1, MANAGER_REQUESTS, MANAGER_EVENTS);
+
+static HEAD_REQUESTS: [raw::wl_message; 0] = [];
+static HEAD_EVENTS: [raw::wl_message; 10] = [message!("name", "s", &raw::NULL_TYPES),
+                                             message!("description", "s", &raw::NULL_TYPES),
+                                             message!("physical_size", "ii", &raw::NULL_TYPES),
+                                             message!("mode", "n", &MODE_TYPES),
+                                             message!("enabled", "i", &raw::NULL_TYPES),
+                                             message!("current_mode", "o", &MODE_TYPES),
+                                             message!("position", "ii", &raw::NULL_TYPES),
+                                             message!("transform", "i", &raw::NULL_TYPES),
+                                             message!("scale", "f", &raw::NULL_TYPES),
+                                             message!("finished", "", &raw::NULL_TYPES)];
+
+static HEAD_INTERFACE: raw::wl_interface = interface!("zwlr_output_head_v1", 1, HEAD_REQUESTS, HEAD_EVENTS);
+
+static MODE_REQUESTS: [raw::wl_message; 0] = [];
+static MODE_EVENTS: [raw::wl_message; 4] = [message!("size", "ii", &raw::NULL_TYPES),
+                                            message!("refresh", "i", &raw::NULL_TYPES),
+                                            message!("preferred", "", &raw::NULL_TYPES),
+                                            message!("finished", "", &raw::NULL_TYPES)];
+
+static MODE_INTERFACE: raw::wl_interface = interface!("zwlr_output_mode_v1", 1, MODE_REQUESTS, MODE_EVENTS);
+
+static CONFIGURATION_REQUESTS: [raw::wl_message; 5] = [message!("enable_head", "no", &ENABLE_HEAD_TYPES),
+                                                       message!("disable_head", "o", &HEAD_TYPES),
+                                                       message!("apply", "", &raw::NULL_TYPES),
+                                                       message!("test", "", &raw::NULL_TYPES),
+                                                       message!("destroy", "", &raw::NULL_TYPES)];
+static CONFIGURATION_EVENTS: [raw::wl_message; 3] = [message!("succeeded", "", &raw::NULL_TYPES),
+                                                     message!("failed", "", &raw::NULL_TYPES),
+                                                     message!("cancelled", "", &raw::NULL_TYPES)];
+
+static CONFIGURATION_INTERFACE: raw::wl_interface =
+    interface!("zwlr_output_configuration_v1", 1, CONFIGURATION_REQUESTS, CONFIGURATION_EVENTS);
+
+static CONFIGURATION_HEAD_REQUESTS: [raw::wl_message; 5] =
+    [message!("set_mode", "o", &MODE_TYPES),
+     message!("set_custom_mode", "iii", &raw::NULL_TYPES),
+     message!("set_position", "ii", &raw::NULL_TYPES),
+     message!("set_transform", "i", &raw::NULL_TYPES),
+     message!("set_scale", "f", &raw::NULL_TYPES)];
+static CONFIGURATION_HEAD_EVENTS: [raw::wl_message; 0] = [];
+
+static CONFIGURATION_HEAD_INTERFACE: raw::wl_interface = interface!("zwlr_output_configuration_head_v1",
+                                                                    1,
+                                                                    CONFIGURATION_HEAD_REQUESTS,
+                                                                    CONFIGURATION_HEAD_EVENTS);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scale_from_fixed() {
+        assert_eq!(scale_from_fixed(256), Some(1));
+        assert_eq!(scale_from_fixed(2 * 256), Some(2));
+        // fractional scales are not supported by wlc
+        assert_eq!(scale_from_fixed(384), None);
+        assert_eq!(scale_from_fixed(2 * 256 + 1), None);
+        assert_eq!(scale_from_fixed(64), None);
+        assert_eq!(scale_from_fixed(0), None);
+        assert_eq!(scale_from_fixed(-256), None);
+    }
+
+    #[test]
+    fn test_position_and_transform_are_fixed() {
+        assert!(is_unchanged(None, None));
+        assert!(is_unchanged(Some(Point { x: 0, y: 0 }), Some(TRANSFORM_NORMAL)));
+        assert!(!is_unchanged(Some(Point { x: 1920, y: 0 }), None));
+        assert!(!is_unchanged(None, Some(1)));
+    }
+}
@@ -0,0 +1,368 @@
+//! Raw libwayland-server functions used by the safe wrappers
+//!
+//! wlc only exposes the `wl_display` and some resources, everything else
+//! needs to go through libwayland directly. The protocols implemented by this
+//! crate are described by hand written `wl_interface`s and implemented by C
+//! style implementation structs, just like wayland-scanner would generate
+//! them for a C compositor.
+
+#![allow(non_camel_case_types, non_upper_case_globals)]
+
+use libc::{c_char, c_int, c_void, gid_t, pid_t, uid_t};
+
+use std::cell::RefCell;
+use std::ffi::CStr;
+use std::marker::PhantomData;
+use std::mem;
+use std::ptr;
+use std::rc::{Rc, Weak};
+
+pub type wl_client = c_void;
+pub type wl_display = c_void;
+pub type wl_global = c_void;
+pub type wl_resource = c_void;
+pub type wl_shm_buffer = c_void;
+
+pub type wl_global_bind_func_t = unsafe extern "C" fn(client: *mut wl_client, data: *mut c_void, version: u32,
+                                                      id: u32);
+pub type wl_resource_destroy_func_t = unsafe extern "C" fn(resource: *mut wl_resource);
+pub type wl_notify_func_t = unsafe extern "C" fn(listener: *mut wl_listener, data: *mut c_void);
+pub type wl_display_global_filter_func_t = unsafe extern "C" fn(client: *const wl_client,
+                                                                global: *const wl_global,
+                                                                data: *mut c_void)
+                                                                -> bool;
+
+#[repr(C)]
+pub struct wl_list {
+    pub prev: *mut wl_list,
+    pub next: *mut wl_list,
+}
+
+#[repr(C)]
+pub struct wl_listener {
+    pub link: wl_list,
+    pub notify: wl_notify_func_t,
+}
+
+#[repr(C)]
+pub struct wl_array {
+    pub size: usize,
+    pub alloc: usize,
+    pub data: *mut c_void,
+}
+
+impl wl_array {
+    /// Borrow a slice as `wl_array` argument of an event
+    pub fn from_slice<T>(slice: &[T]) -> wl_array {
+        wl_array {
+            size: slice.len() * mem::size_of::<T>(),
+            alloc: slice.len() * mem::size_of::<T>(),
+            data: slice.as_ptr() as *mut c_void,
+        }
+    }
+}
+
+pub type wl_client_for_each_resource_iterator_func_t = unsafe extern "C" fn(resource: *mut wl_resource,
+                                                                            user_data: *mut c_void)
+                                                                            -> c_int;
+const WL_ITERATOR_CONTINUE: c_int = 1;
+
+#[repr(C)]
+pub struct wl_message {
+    pub name: *const c_char,
+    pub signature: *const c_char,
+    pub types: *const *const wl_interface,
+}
+unsafe impl Sync for wl_message {}
+
+#[repr(C)]
+pub struct wl_interface {
+    pub name: *const c_char,
+    pub version: c_int,
+    pub request_count: c_int,
+    pub requests: *const wl_message,
+    pub event_count: c_int,
+    pub events: *const wl_message,
+}
+unsafe impl Sync for wl_interface {}
+
+/// Wrapper to store the `types` arrays of `wl_message`s in statics
+#[repr(C)]
+pub struct Types<T>(pub T);
+unsafe impl<T> Sync for Types<T> {}
+
+/// Null-terminated C string literal
+macro_rules! cstr {
+    ($s:expr) => { concat!($s, "\0") as *const str as *const ::libc::c_char }
+}
+
+/// `wl_message` for use in the request and event arrays of a `wl_interface`
+macro_rules! message {
+    ($name:expr, $signature:expr, $types:expr) => {
+        raw::wl_message {
+            name: cstr!($name),
+            signature: cstr!($signature),
+            types: $types as *const _ as *const *const raw::wl_interface,
+        }
+    }
+}
+
+/// `wl_interface` from static request and event arrays
+macro_rules! interface {
+    ($name:expr, $version:expr, $requests:expr, $events:expr) => {
+        raw::wl_interface {
+            name: cstr!($name),
+            version: $version,
+            request_count: $requests.len() as ::libc::c_int,
+            requests: &$requests as *const _ as *const raw::wl_message,
+            event_count: $events.len() as ::libc::c_int,
+            events: &$events as *const _ as *const raw::wl_message,
+        }
+    }
+}
+
+pub static NULL_TYPES: Types<[*const wl_interface; 8]> = Types([0 as *const wl_interface; 8]);
+
+#[link(name = "wayland-server")]
+extern "C" {
+    pub static wl_buffer_interface: wl_interface;
+    pub static wl_output_interface: wl_interface;
+    pub static wl_surface_interface: wl_interface;
+
+    pub fn wl_display_get_serial(display: *mut wl_display) -> u32;
+    pub fn wl_display_next_serial(display: *mut wl_display) -> u32;
+    pub fn wl_display_add_client_created_listener(display: *mut wl_display, listener: *mut wl_listener);
+    pub fn wl_display_set_global_filter(display: *mut wl_display,
+                                        filter: Option<wl_display_global_filter_func_t>,
+                                        data: *mut c_void);
+
+    pub fn wl_global_create(display: *mut wl_display, interface: *const wl_interface, version: c_int,
+                            data: *mut c_void, bind: wl_global_bind_func_t)
+                            -> *mut wl_global;
+    pub fn wl_global_destroy(global: *mut wl_global);
+    pub fn wl_global_get_interface(global: *const wl_global) -> *const wl_interface;
+
+    pub fn wl_client_post_no_memory(client: *mut wl_client);
+    pub fn wl_client_destroy(client: *mut wl_client);
+    pub fn wl_client_get_credentials(client: *mut wl_client, pid: *mut pid_t, uid: *mut uid_t,
+                                     gid: *mut gid_t);
+    pub fn wl_client_add_destroy_listener(client: *mut wl_client, listener: *mut wl_listener);
+    pub fn wl_client_for_each_resource(client: *mut wl_client,
+                                       iterator: wl_client_for_each_resource_iterator_func_t,
+                                       user_data: *mut c_void);
+
+    pub fn wl_resource_create(client: *mut wl_client, interface: *const wl_interface, version: c_int, id: u32)
+                              -> *mut wl_resource;
+    pub fn wl_resource_set_implementation(resource: *mut wl_resource, implementation: *const c_void,
+                                          data: *mut c_void,
+                                          destroy: Option<wl_resource_destroy_func_t>);
+    pub fn wl_resource_destroy(resource: *mut wl_resource);
+    pub fn wl_resource_get_user_data(resource: *mut wl_resource) -> *mut c_void;
+    pub fn wl_resource_get_class(resource: *mut wl_resource) -> *const c_char;
+    pub fn wl_resource_get_client(resource: *mut wl_resource) -> *mut wl_client;
+    pub fn wl_resource_get_id(resource: *mut wl_resource) -> u32;
+    pub fn wl_resource_get_version(resource: *mut wl_resource) -> c_int;
+    pub fn wl_resource_post_event(resource: *mut wl_resource, opcode: u32, ...);
+    pub fn wl_resource_post_error(resource: *mut wl_resource, code: u32, msg: *const c_char, ...);
+    pub fn wl_resource_add_destroy_listener(resource: *mut wl_resource, listener: *mut wl_listener);
+
+    pub fn wl_list_remove(elm: *mut wl_list);
+
+    pub fn wl_shm_buffer_get(resource: *mut wl_resource) -> *mut wl_shm_buffer;
+    pub fn wl_shm_buffer_begin_access(buffer: *mut wl_shm_buffer);
+    pub fn wl_shm_buffer_end_access(buffer: *mut wl_shm_buffer);
+    pub fn wl_shm_buffer_get_data(buffer: *mut wl_shm_buffer) -> *mut c_void;
+    pub fn wl_shm_buffer_get_stride(buffer: *mut wl_shm_buffer) -> i32;
+    pub fn wl_shm_buffer_get_format(buffer: *mut wl_shm_buffer) -> u32;
+    pub fn wl_shm_buffer_get_width(buffer: *mut wl_shm_buffer) -> i32;
+    pub fn wl_shm_buffer_get_height(buffer: *mut wl_shm_buffer) -> i32;
+}
+
+#[repr(C)]
+struct DestroyListener<F> {
+    listener: wl_listener,
+    callback: F,
+}
+
+unsafe extern "C" fn destroy_notify<F: FnOnce(*mut c_void)>(listener: *mut wl_listener, data: *mut c_void) {
+    wl_list_remove(&mut (*listener).link);
+    let boxed: Box<DestroyListener<F>> = Box::from_raw(listener as *mut _);
+    (boxed.callback)(data)
+}
+
+fn destroy_listener<F: FnOnce(*mut c_void)>(callback: F) -> *mut wl_listener {
+    Box::into_raw(Box::new(DestroyListener {
+                               listener: wl_listener {
+                                   link: wl_list {
+                                       prev: ptr::null_mut(),
+                                       next: ptr::null_mut(),
+                                   },
+                                   notify: destroy_notify::<F>,
+                               },
+                               callback: callback,
+                           })) as *mut wl_listener
+}
+
+/// Call a function once the given resource is destroyed
+///
+/// Other then the destructor set by `wl_resource_set_implementation` this
+/// also works for resources created by wlc.
+pub unsafe fn on_resource_destroyed<F: FnOnce(*mut c_void)>(resource: *mut wl_resource, callback: F) {
+    wl_resource_add_destroy_listener(resource, destroy_listener(callback));
+}
+
+/// Call a function once the given client is destroyed
+pub unsafe fn on_client_destroyed<F: FnOnce(*mut c_void)>(client: *mut wl_client, callback: F) {
+    wl_client_add_destroy_listener(client, destroy_listener(callback));
+}
+
+#[repr(C)]
+struct CallbackListener<F> {
+    listener: wl_listener,
+    callback: F,
+}
+
+unsafe extern "C" fn callback_notify<F: FnMut(*mut c_void)>(listener: *mut wl_listener, data: *mut c_void) {
+    let listener = &mut *(listener as *mut CallbackListener<F>);
+    (listener.callback)(data)
+}
+
+unsafe fn free_callback_listener<F>(listener: *mut wl_listener) {
+    wl_list_remove(&mut (*listener).link);
+    drop(Box::from_raw(listener as *mut CallbackListener<F>));
+}
+
+/// Listener of a signal, that may fire multiple times
+///
+/// Dropping this removes the listener from the signal
+pub struct Listener {
+    ptr: *mut wl_listener,
+    free: unsafe fn(*mut wl_listener),
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        unsafe { (self.free)(self.ptr) }
+    }
+}
+
+/// Call a function with every newly connected client
+pub unsafe fn on_client_created<F: FnMut(*mut c_void)>(display: *mut wl_display, callback: F) -> Listener {
+    let listener = Box::into_raw(Box::new(CallbackListener {
+                                              listener: wl_listener {
+                                                  link: wl_list {
+                                                      prev: ptr::null_mut(),
+                                                      next: ptr::null_mut(),
+                                                  },
+                                                  notify: callback_notify::<F>,
+                                              },
+                                              callback: callback,
+                                          })) as *mut wl_listener;
+    wl_display_add_client_created_listener(display, listener);
+    Listener {
+        ptr: listener,
+        free: free_callback_listener::<F>,
+    }
+}
+
+unsafe extern "C" fn collect_resources(resource: *mut wl_resource, user_data: *mut c_void) -> c_int {
+    let data = &mut *(user_data as *mut (&CStr, Vec<*mut wl_resource>));
+    if CStr::from_ptr(wl_resource_get_class(resource)) == data.0 {
+        data.1.push(resource);
+    }
+    WL_ITERATOR_CONTINUE
+}
+
+/// Get all resources of a client with the given interface
+pub unsafe fn client_resources(client: *mut wl_client, interface: &wl_interface) -> Vec<*mut wl_resource> {
+    let mut data = (CStr::from_ptr(interface.name), Vec::new());
+    wl_client_for_each_resource(client,
+                                collect_resources,
+                                &mut data as *mut (&CStr, Vec<*mut wl_resource>) as *mut c_void);
+    data.1
+}
+
+/// Store a weak reference to a protocol state as resource or global user data
+pub fn into_user_data<T>(state: &Rc<RefCell<T>>) -> *mut c_void {
+    Box::into_raw(Box::new(Rc::downgrade(state))) as *mut c_void
+}
+
+/// Get the protocol state back from the user data of a resource
+pub unsafe fn state<T>(resource: *mut wl_resource) -> Option<Rc<RefCell<T>>> {
+    from_user_data(wl_resource_get_user_data(resource))
+}
+
+/// Get the protocol state back from user data created by `into_user_data`
+pub unsafe fn from_user_data<T>(data: *mut c_void) -> Option<Rc<RefCell<T>>> {
+    if data.is_null() {
+        None
+    } else {
+        (*(data as *const Weak<RefCell<T>>)).upgrade()
+    }
+}
+
+/// Free user data created by `into_user_data`
+pub unsafe fn free_user_data<T>(data: *mut c_void) {
+    if !data.is_null() {
+        drop(Box::from_raw(data as *mut Weak<RefCell<T>>));
+    }
+}
+
+/// Resource destructor freeing user data created by `into_user_data`
+pub unsafe extern "C" fn destroy_user_data<T>(resource: *mut wl_resource) {
+    free_user_data::<T>(wl_resource_get_user_data(resource))
+}
+
+/// Create a resource for a new id implemented by a static implementation,
+/// that holds a weak reference to the protocol state
+pub unsafe fn create_resource<T, I>(client: *mut wl_client, interface: &'static wl_interface, version: u32,
+                                    id: u32, implementation: &'static I, state: &Rc<RefCell<T>>)
+                                    -> Option<*mut wl_resource> {
+    let resource = wl_resource_create(client, interface, version as c_int, id);
+    if resource.is_null() {
+        wl_client_post_no_memory(client);
+        None
+    } else {
+        wl_resource_set_implementation(resource,
+                                       implementation as *const I as *const c_void,
+                                       into_user_data(state),
+                                       Some(destroy_user_data::<T>));
+        Some(resource)
+    }
+}
+
+/// Registered global
+///
+/// Dropping this will remove the global and the reference to the protocol
+/// state
+pub struct Global<T> {
+    ptr: *mut wl_global,
+    data: *mut c_void,
+    _state: PhantomData<T>,
+}
+
+impl<T> Global<T> {
+    /// Create a new global on the display, binding to it will call `bind` with
+    /// a reference to the protocol state as user data
+    pub unsafe fn new(display: *mut wl_display, interface: &'static wl_interface, version: u32,
+                      state: &Rc<RefCell<T>>, bind: wl_global_bind_func_t)
+                      -> Global<T> {
+        let data = into_user_data(state);
+        Global {
+            ptr: wl_global_create(display, interface, version as c_int, data, bind),
+            data: data,
+            _state: PhantomData,
+        }
+    }
+}
+
+impl<T> Drop for Global<T> {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.ptr.is_null() {
+                wl_global_destroy(self.ptr);
+            }
+            free_user_data::<T>(self.data);
+        }
+    }
+}
@@ -0,0 +1,488 @@
+//! Screencopy protocol for screenshot and screen-sharing clients
+//!
+//! Implements the `zwlr_screencopy_manager_v1` protocol (version 1).
+//! Clients may request a copy of a whole `Output` or a region of it into a
+//! `wl_shm` buffer. Every request passes the `ScreencopyPolicy` first.
+//!
+//! Frames are read from the framebuffer with `GLES2Renderer::pixels_read`,
+//! so forward `Callback::output_render_post` and `Callback::output_destroyed`
+//! to the `Screencopy` global. The cursor is not included in the frames,
+//! `overlay_cursor` is ignored.
+//!
+//! Enabled by features `wayland` and `render`
+
+use {Geometry, Output, Point, Size, WeakOutput};
+use render::{GLES2PixelFormat, GLES2Renderer, RenderInstance, RenderOutput, Renderer};
+
+use super::Display;
+use wayland::raw;
+
+use ffi;
+use libc::{self, c_void};
+
+use std::cell::RefCell;
+use std::cmp;
+use std::mem;
+use std::rc::Rc;
+use std::slice;
+
+use wayland_server::Client;
+
+const VERSION: u32 = 1;
+
+const FRAME_EVENT_BUFFER: u32 = 0;
+const FRAME_EVENT_FLAGS: u32 = 1;
+const FRAME_EVENT_READY: u32 = 2;
+const FRAME_EVENT_FAILED: u32 = 3;
+
+const FRAME_ERROR_ALREADY_USED: u32 = 0;
+const FRAME_ERROR_INVALID_BUFFER: u32 = 1;
+
+const WL_SHM_FORMAT_ARGB8888: u32 = 0;
+const WL_SHM_FORMAT_XRGB8888: u32 = 1;
+
+/// Decides which clients may capture an `Output`
+///
+/// An Implementation for `FnMut` is provided, so you may use a (anonymous)
+/// function instead
+pub trait ScreencopyPolicy {
+    /// Return `true` to let the client capture the output
+    fn allow(&mut self, client: &Client, output: &Output) -> bool;
+}
+
+impl<F> ScreencopyPolicy for F
+    where F: FnMut(&Client, &Output) -> bool
+{
+    fn allow(&mut self, client: &Client, output: &Output) -> bool {
+        self(client, output)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum FrameStatus {
+    /// Waiting for the client to send a buffer
+    Waiting,
+    /// Waiting for the next frame of the output
+    Copying(*mut raw::wl_resource),
+    /// Either ready or failed
+    Done,
+}
+
+struct Frame {
+    resource: *mut raw::wl_resource,
+    output: Option<WeakOutput>,
+    region: Geometry,
+    status: FrameStatus,
+}
+
+struct State {
+    frames: Vec<Frame>,
+    buffers: Vec<*mut raw::wl_resource>,
+    policy: Box<ScreencopyPolicy>,
+}
+
+/// Clamp a requested region to the resolution of an output
+fn capture_region(x: i32, y: i32, width: i32, height: i32, resolution: Size) -> Option<Geometry> {
+    if width <= 0 || height <= 0 {
+        return None;
+    }
+
+    let x1 = cmp::max(x, 0);
+    let y1 = cmp::max(y, 0);
+    let x2 = cmp::min(x.saturating_add(width), resolution.w as i32);
+    let y2 = cmp::min(y.saturating_add(height), resolution.h as i32);
+
+    if x2 <= x1 || y2 <= y1 {
+        None
+    } else {
+        Some(Geometry {
+                 origin: Point { x: x1, y: y1 },
+                 size: Size {
+                     w: (x2 - x1) as u32,
+                     h: (y2 - y1) as u32,
+                 },
+             })
+    }
+}
+
+/// Copy pixels read by `pixels_read` into a `wl_shm` buffer
+///
+/// OpenGL reads the framebuffer bottom-up as RGBA, while the buffer is
+/// expected top-down in `XRGB8888` (BGRX in memory) with the given stride.
+fn copy_pixels(src: &[u8], size: Size, dst: &mut [u8], stride: usize) {
+    let row = size.w as usize * 4;
+    for (y, src_row) in src.chunks(row).take(size.h as usize).enumerate() {
+        let offset = (size.h as usize - 1 - y) * stride;
+        for (dst, src) in dst[offset..offset + row].chunks_mut(4).zip(src_row.chunks(4)) {
+            dst[0] = src[2];
+            dst[1] = src[1];
+            dst[2] = src[0];
+            dst[3] = 0xff;
+        }
+    }
+}
+
+unsafe fn fail(frame: &mut Frame) {
+    raw::wl_resource_post_event(frame.resource, FRAME_EVENT_FAILED);
+    frame.status = FrameStatus::Done;
+}
+
+unsafe fn send_ready(frame: &mut Frame) {
+    let mut time: libc::timespec = mem::zeroed();
+    libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut time);
+    let sec = time.tv_sec as u64;
+
+    raw::wl_resource_post_event(frame.resource, FRAME_EVENT_FLAGS, 0u32);
+    raw::wl_resource_post_event(frame.resource,
+                                FRAME_EVENT_READY,
+                                (sec >> 32) as u32,
+                                sec as u32,
+                                time.tv_nsec as u32);
+    frame.status = FrameStatus::Done;
+}
+
+unsafe fn copy_frame(frame: &mut Frame, buffer: *mut raw::wl_resource, renderer: &GLES2Renderer) {
+    let mut geometry = frame.region;
+    let pixels = renderer.pixels_read(GLES2PixelFormat::RGBA8888, &mut geometry);
+    if geometry.size != frame.region.size {
+        // the output was resized after the buffer was announced
+        return fail(frame);
+    }
+
+    let shm = raw::wl_shm_buffer_get(buffer);
+    let stride = raw::wl_shm_buffer_get_stride(shm) as usize;
+    raw::wl_shm_buffer_begin_access(shm);
+    let data = slice::from_raw_parts_mut(raw::wl_shm_buffer_get_data(shm) as *mut u8,
+                                         stride * geometry.size.h as usize);
+    copy_pixels(&pixels, geometry.size, data, stride);
+    raw::wl_shm_buffer_end_access(shm);
+
+    send_ready(frame);
+}
+
+/// Screencopy global
+///
+/// Dropping this removes the global, pending frames will never be ready.
+pub struct Screencopy {
+    state: Rc<RefCell<State>>,
+    _global: raw::Global<State>,
+}
+
+impl Screencopy {
+    /// Advertise the screencopy global on the given `Display`
+    pub fn new<P: ScreencopyPolicy + 'static>(display: &Display, policy: P) -> Screencopy {
+        let state = Rc::new(RefCell::new(State {
+                                              frames: Vec::new(),
+                                              buffers: Vec::new(),
+                                              policy: Box::new(policy),
+                                          }));
+
+        let global = unsafe {
+            raw::Global::new(display.ptr(),
+                             &MANAGER_INTERFACE,
+                             VERSION,
+                             &state,
+                             bind_screencopy_manager)
+        };
+
+        Screencopy {
+            state: state,
+            _global: global,
+        }
+    }
+
+    /// Check if any client waits for a frame of the given output
+    pub fn is_pending(&self, output: &Output) -> bool {
+        let weak = Some(output.weak_reference());
+        self.state.borrow().frames.iter().any(|frame| match frame.status {
+                                                   FrameStatus::Copying(_) => frame.output == weak,
+                                                   _ => false,
+                                               })
+    }
+
+    /// Forward of `Callback::output_render_post`
+    ///
+    /// Fulfills all pending capture requests of the output
+    pub fn output_render_post(&mut self, output: &mut RenderOutput) {
+        if !self.is_pending(output) {
+            return;
+        }
+
+        let weak = Some(output.weak_reference());
+        let renderer = match output.get_renderer() {
+            RenderInstance::GLES2(renderer) => Some(renderer),
+            RenderInstance::None(_) => None,
+        };
+
+        let mut state = self.state.borrow_mut();
+        for frame in state.frames.iter_mut().filter(|frame| frame.output == weak) {
+            if let FrameStatus::Copying(buffer) = frame.status {
+                unsafe {
+                    match renderer {
+                        Some(ref renderer) => copy_frame(frame, buffer, renderer),
+                        None => fail(frame),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Forward of `Callback::output_destroyed`
+    pub fn output_destroyed(&mut self, output: &Output) {
+        let weak = Some(output.weak_reference());
+        let mut state = self.state.borrow_mut();
+        for frame in state.frames.iter_mut().filter(|frame| frame.output == weak) {
+            if frame.status != FrameStatus::Done {
+                unsafe { fail(frame) };
+            }
+        }
+    }
+}
+
+unsafe extern "C" fn bind_screencopy_manager(client: *mut raw::wl_client, data: *mut c_void, version: u32,
+                                             id: u32) {
+    if let Some(state) = raw::from_user_data::<State>(data) {
+        raw::create_resource(client,
+                             &MANAGER_INTERFACE,
+                             cmp::min(version, VERSION),
+                             id,
+                             &MANAGER_IMPLEMENTATION,
+                             &state);
+    }
+}
+
+unsafe fn create_frame(client: *mut raw::wl_client, manager: *mut raw::wl_resource, id: u32,
+                       output: *mut raw::wl_resource, region: Option<(i32, i32, i32, i32)>) {
+    let state = match raw::state::<State>(manager) {
+        Some(state) => state,
+        None => return,
+    };
+    let version = raw::wl_resource_get_version(manager) as u32;
+    let resource = match raw::create_resource(client,
+                                              &FRAME_INTERFACE,
+                                              version,
+                                              id,
+                                              &FRAME_IMPLEMENTATION,
+                                              &state) {
+        Some(resource) => resource,
+        None => return,
+    };
+    raw::on_resource_destroyed(resource, |resource| if let Some(state) = raw::state::<State>(resource) {
+                                   state.borrow_mut().frames.retain(|frame| frame.resource != resource);
+                               });
+
+    let output = match ffi::wlc_handle_from_wl_output_resource(output as *mut _) {
+        0 => None,
+        handle => Some(::output::from_handle(handle)),
+    };
+
+    let mut state = state.borrow_mut();
+    let mut frame = Frame {
+        resource: resource,
+        output: output.as_ref().map(|output| output.weak_reference()),
+        region: Geometry {
+            origin: Point { x: 0, y: 0 },
+            size: Size { w: 0, h: 0 },
+        },
+        status: FrameStatus::Waiting,
+    };
+
+    let region = output.and_then(|output| {
+        if !state.policy.allow(&Client::from_ptr(client as *mut _), output) {
+            return None;
+        }
+        let resolution = output.resolution();
+        match region {
+            Some((x, y, width, height)) => capture_region(x, y, width, height, resolution),
+            None => capture_region(0, 0, resolution.w as i32, resolution.h as i32, resolution),
+        }
+    });
+
+    match region {
+        Some(region) => {
+            raw::wl_resource_post_event(resource,
+                                        FRAME_EVENT_BUFFER,
+                                        WL_SHM_FORMAT_XRGB8888,
+                                        region.size.w,
+                                        region.size.h,
+                                        region.size.w * 4);
+            frame.region = region;
+        }
+        None => fail(&mut frame),
+    }
+    state.frames.push(frame);
+}
+
+unsafe extern "C" fn capture_output(client: *mut raw::wl_client, resource: *mut raw::wl_resource, id: u32,
+                                    _overlay_cursor: i32, output: *mut raw::wl_resource) {
+    create_frame(client, resource, id, output, None);
+}
+
+unsafe extern "C" fn capture_output_region(client: *mut raw::wl_client, resource: *mut raw::wl_resource,
+                                           id: u32, _overlay_cursor: i32, output: *mut raw::wl_resource,
+                                           x: i32, y: i32, width: i32, height: i32) {
+    create_frame(client, resource, id, output, Some((x, y, width, height)));
+}
+
+unsafe fn valid_buffer(buffer: *mut raw::wl_resource, size: Size) -> bool {
+    let shm = raw::wl_shm_buffer_get(buffer);
+    if shm.is_null() {
+        return false;
+    }
+    let format = raw::wl_shm_buffer_get_format(shm);
+    (format == WL_SHM_FORMAT_XRGB8888 || format == WL_SHM_FORMAT_ARGB8888) &&
+    raw::wl_shm_buffer_get_width(shm) == size.w as i32 &&
+    raw::wl_shm_buffer_get_height(shm) == size.h as i32 &&
+    raw::wl_shm_buffer_get_stride(shm) >= size.w as i32 * 4
+}
+
+unsafe extern "C" fn copy(_client: *mut raw::wl_client, resource: *mut raw::wl_resource,
+                          buffer: *mut raw::wl_resource) {
+    let state = match raw::state::<State>(resource) {
+        Some(state) => state,
+        None => return,
+    };
+
+    let weak = Rc::downgrade(&state);
+    let output = {
+        let mut state = state.borrow_mut();
+        let output = match state.frames.iter_mut().find(|frame| frame.resource == resource) {
+            Some(frame) => {
+                if frame.status != FrameStatus::Waiting {
+                    raw::wl_resource_post_error(resource,
+                                                FRAME_ERROR_ALREADY_USED,
+                                                cstr!("frame was already used"));
+                    return;
+                }
+                if !valid_buffer(buffer, frame.region.size) {
+                    raw::wl_resource_post_error(resource,
+                                                FRAME_ERROR_INVALID_BUFFER,
+                                                cstr!("invalid buffer"));
+                    return;
+                }
+                frame.status = FrameStatus::Copying(buffer);
+                frame.output.clone()
+            }
+            None => return,
+        };
+
+        if !state.buffers.contains(&buffer) {
+            // fail frames, that would write into a buffer destroyed by the client
+            state.buffers.push(buffer);
+            raw::on_resource_destroyed(buffer, move |buffer| if let Some(state) = weak.upgrade() {
+                let mut state = state.borrow_mut();
+                state.buffers.retain(|other| *other != buffer);
+                for frame in state.frames
+                        .iter_mut()
+                        .filter(|frame| frame.status == FrameStatus::Copying(buffer)) {
+                    fail(frame);
+                }
+            });
+        }
+
+        output
+    };
+
+    if let Some(output) = output {
+        output.run(|output| output.schedule_render());
+    }
+}
+
+unsafe extern "C" fn destroy(_client: *mut raw::wl_client, resource: *mut raw::wl_resource) {
+    raw::wl_resource_destroy(resource);
+}
+
+#[repr(C)]
+struct ManagerImplementation {
+    capture_output: unsafe extern "C" fn(*mut raw::wl_client,
+                                         *mut raw::wl_resource,
+                                         u32,
+                                         i32,
+                                         *mut raw::wl_resource),
+    capture_output_region: unsafe extern "C" fn(*mut raw::wl_client,
+                                                *mut raw::wl_resource,
+                                                u32,
+                                                i32,
+                                                *mut raw::wl_resource,
+                                                i32,
+                                                i32,
+                                                i32,
+                                                i32),
+    destroy: unsafe extern "C" fn(*mut raw::wl_client, *mut raw::wl_resource),
+}
+
+#[repr(C)]
+struct FrameImplementation {
+    copy: unsafe extern "C" fn(*mut raw::wl_client, *mut raw::wl_resource, *mut raw::wl_resource),
+    destroy: unsafe extern "C" fn(*mut raw::wl_client, *mut raw::wl_resource),
+}
+
+static MANAGER_IMPLEMENTATION: ManagerImplementation = ManagerImplementation {
+    capture_output: capture_output,
+    capture_output_region: capture_output_region,
+    destroy: destroy,
+};
+
+static FRAME_IMPLEMENTATION: FrameImplementation = FrameImplementation {
+    copy: copy,
+    destroy: destroy,
+};
+
+static CAPTURE_OUTPUT_TYPES: raw::Types<[*const raw::wl_interface; 3]> =
+    raw::Types([&FRAME_INTERFACE as *const _,
+                0 as *const _,
+                unsafe { &raw::wl_output_interface as *const _ }]);
+static CAPTURE_OUTPUT_REGION_TYPES: raw::Types<[*const raw::wl_interface; 7]> =
+    raw::Types([&FRAME_INTERFACE as *const _,
+                0 as *const _,
+                unsafe { &raw::wl_output_interface as *const _ },
+                0 as *const _,
+                0 as *const _,
+                0 as *const _,
+                0 as *const _]);
+static COPY_TYPES: raw::Types<[*const raw::wl_interface; 1]> =
+    raw::Types([unsafe { &raw::wl_buffer_interface as *const _ }]);
+
+static MANAGER_REQUESTS: [raw::wl_message; 3] =
+    [message!("capture_output", "nio", &CAPTURE_OUTPUT_TYPES),
+     message!("capture_output_region", "nioiiii", &CAPTURE_OUTPUT_REGION_TYPES),
+     message!("destroy", "", &raw::NULL_TYPES)];
+static MANAGER_EVENTS: [raw::wl_message; 0] = [];
+
+static MANAGER_INTERFACE: raw::wl_interface =
+    interface!("zwlr_screencopy_manager_v1", 1, MANAGER_REQUESTS, MANAGER_EVENTS);
+
+static FRAME_REQUESTS: [raw::wl_message; 2] = [message!("copy", "o", &COPY_TYPES),
+                                               message!("destroy", "", &raw::NULL_TYPES)];
+static FRAME_EVENTS: [raw::wl_message; 4] = [message!("buffer", "uuuu", &raw::NULL_TYPES),
+                                             message!("flags", "u", &raw::NULL_TYPES),
+                                             message!("ready", "uuu", &raw::NULL_TYPES),
+                                             message!("failed", "", &raw::NULL_TYPES)];
+
+static FRAME_INTERFACE: raw::wl_interface =
+    interface!("zwlr_screencopy_frame_v1", 1, FRAME_REQUESTS, FRAME_EVENTS);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_util::geometry;
+
+    #[test]
+    fn test_capture_region_clamped() {
+        let resolution = Size { w: 1920, h: 1080 };
+        assert_eq!(capture_region(-10, 1000, 100, 200, resolution), Some(geometry(0, 1000, 90, 80)));
+        assert_eq!(capture_region(1920, 0, 10, 10, resolution), None);
+        assert_eq!(capture_region(0, 0, 0, 10, resolution), None);
+    }
+
+    #[test]
+    fn test_copy_pixels_flips_and_swizzles() {
+        // 2x2 RGBA, bottom row first
+        let src = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16];
+        // stride with 4 bytes of padding per row
+        let mut dst = [0u8; 24];
+        copy_pixels(&src, Size { w: 2, h: 2 }, &mut dst, 12);
+        assert_eq!(dst,
+                   [11, 10, 9, 255, 15, 14, 13, 255, 0, 0, 0, 0, 3, 2, 1, 255, 7, 6, 5, 255, 0, 0, 0, 0]);
+    }
+}
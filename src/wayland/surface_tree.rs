@@ -0,0 +1,147 @@
+//! Traversal of a view's surface tree
+//!
+//! `WlcSurface::sub_surfaces` only returns the direct children of a surface
+//! and their geometry is relative to the parent. A `SurfaceTree` walks all
+//! surfaces of a view depth-first, starting with the main surface, in the
+//! order wlc stacks them. Every surface is yielded with its absolute
+//! geometry, so the last surface containing a point is the topmost one.
+//! `WlcSurface::render_tree` renders the surfaces in the same order.
+
+use {Geometry, Point, View};
+
+#[cfg(feature = "render")]
+use render::Renderer;
+
+use super::{WlcSubSurface, WlcSurface};
+
+use std::mem;
+
+/// Absolute geometry of a subsurface from the absolute geometry of its parent
+fn child_geometry(parent: Geometry, relative: Geometry) -> Geometry {
+    Geometry {
+        origin: Point {
+            x: parent.origin.x + relative.origin.x,
+            y: parent.origin.y + relative.origin.y,
+        },
+        size: relative.size,
+    }
+}
+
+fn contains(geometry: Geometry, point: Point) -> bool {
+    point.x >= geometry.origin.x && point.y >= geometry.origin.y &&
+    point.x < geometry.origin.x + geometry.size.w as i32 &&
+    point.y < geometry.origin.y + geometry.size.h as i32
+}
+
+/// Depth-first iterator over a surface and all its subsurfaces
+///
+/// Yields every surface with its absolute geometry, parents before their
+/// children.
+pub struct SurfaceTree<'a> {
+    stack: Vec<(&'a WlcSurface, Geometry)>,
+}
+
+impl<'a> SurfaceTree<'a> {
+    /// Walk the tree of the given surface placed at `geometry`
+    pub fn new(surface: &'a WlcSurface, geometry: Geometry) -> SurfaceTree<'a> {
+        SurfaceTree { stack: vec![(surface, geometry)] }
+    }
+
+    /// Find the topmost surface under the given point
+    ///
+    /// Returns the surface and the point in its local coordinates
+    pub fn surface_at(self, point: Point) -> Option<(&'a WlcSurface, Point)> {
+        self.filter(|&(_, geometry)| contains(geometry, point))
+            .last()
+            .map(|(surface, geometry)| {
+                     (surface,
+                      Point {
+                          x: point.x - geometry.origin.x,
+                          y: point.y - geometry.origin.y,
+                      })
+                 })
+    }
+}
+
+impl<'a> Iterator for SurfaceTree<'a> {
+    type Item = (&'a WlcSurface, Geometry);
+
+    fn next(&mut self) -> Option<(&'a WlcSurface, Geometry)> {
+        let (surface, geometry) = match self.stack.pop() {
+            Some(next) => next,
+            None => return None,
+        };
+
+        // push in reverse, so the first child is visited next
+        for sub_surface in surface.sub_surfaces().iter().rev() {
+            self.stack.push((sub_surface.surface(), child_geometry(geometry, sub_surface.geometry())));
+        }
+
+        Some((surface, geometry))
+    }
+}
+
+impl WlcSubSurface {
+    /// Returns the surface of this subsurface
+    pub fn surface(&self) -> &WlcSurface {
+        unsafe { mem::transmute::<&WlcSubSurface, &WlcSurface>(self) }
+    }
+
+    /// Returns the surface of this subsurface, that may be modified
+    pub fn surface_mut(&mut self) -> &mut WlcSurface {
+        unsafe { mem::transmute::<&mut WlcSubSurface, &mut WlcSurface>(self) }
+    }
+}
+
+impl WlcSurface {
+    /// Render the surface placed at `geometry` and all its subsurfaces
+    ///
+    /// Surfaces are rendered in the order of `SurfaceTree`, parents below
+    /// their children.
+    ///
+    /// Enabled by feature `render`
+    #[cfg(feature = "render")]
+    pub fn render_tree<R: Renderer>(&mut self, geometry: Geometry, renderer: &mut R) {
+        renderer.render_surface(self, geometry);
+        for sub_surface in self.sub_surfaces_mut() {
+            let child = child_geometry(geometry, sub_surface.geometry());
+            sub_surface.surface_mut().render_tree(child, renderer);
+        }
+    }
+}
+
+impl View {
+    /// Walk all surfaces of the view, starting at `View::geometry`
+    pub fn surface_tree(&self) -> SurfaceTree {
+        SurfaceTree::new(self.wl_surface(), self.geometry())
+    }
+
+    /// Find the topmost surface of the view under the given point
+    ///
+    /// Returns the surface and the point in its local coordinates
+    pub fn surface_at(&self, point: Point) -> Option<(&WlcSurface, Point)> {
+        self.surface_tree().surface_at(point)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_util::geometry;
+
+    #[test]
+    fn test_child_geometry_is_absolute() {
+        let parent = geometry(100, 50, 800, 600);
+        let relative = geometry(-10, 20, 30, 40);
+        assert_eq!(child_geometry(parent, relative), geometry(90, 70, 30, 40));
+    }
+
+    #[test]
+    fn test_contains_excludes_far_edges() {
+        let area = geometry(10, 10, 10, 10);
+        assert!(contains(area, Point { x: 10, y: 10 }));
+        assert!(contains(area, Point { x: 19, y: 19 }));
+        assert!(!contains(area, Point { x: 20, y: 15 }));
+        assert!(!contains(area, Point { x: 15, y: 9 }));
+    }
+}
@@ -0,0 +1,506 @@
+//! Foreign toplevel management protocol for external taskbars and docks
+//!
+//! Implements the `zwlr_foreign_toplevel_manager_v1` protocol (version 2).
+//! Subscribed clients get a list of all `View`s forwarded to the
+//! `ToplevelManager` with their title, app_id, `ViewState` and `Output` and
+//! may request to activate, close, minimize, maximize or fullscreen them.
+//! Every request passes the `ToplevelPolicy` before it is applied.
+//!
+//! Create a `ToplevelManager` once the compositor is ready and forward
+//! `view_created`, `view_destroyed`, `view_focus`, `view_move_to_output` and
+//! `view_properties_updated` for every view that should appear in a taskbar.
+//! If you change the state of a view yourself call `ToplevelManager::update`.
+
+use {Output, View, ViewPropertyUpdate, ViewState, ViewType, WeakView};
+
+use super::Display;
+use wayland::raw;
+
+use ffi;
+use libc::c_void;
+
+use std::cell::RefCell;
+use std::cmp;
+use std::ffi::CString;
+use std::rc::Rc;
+
+const VERSION: u32 = 2;
+
+const MANAGER_EVENT_TOPLEVEL: u32 = 0;
+const MANAGER_EVENT_FINISHED: u32 = 1;
+
+const HANDLE_EVENT_TITLE: u32 = 0;
+const HANDLE_EVENT_APP_ID: u32 = 1;
+const HANDLE_EVENT_OUTPUT_ENTER: u32 = 2;
+const HANDLE_EVENT_OUTPUT_LEAVE: u32 = 3;
+const HANDLE_EVENT_STATE: u32 = 4;
+const HANDLE_EVENT_DONE: u32 = 5;
+const HANDLE_EVENT_CLOSED: u32 = 6;
+
+const STATE_MAXIMIZED: u32 = 0;
+const STATE_MINIMIZED: u32 = 1;
+const STATE_ACTIVATED: u32 = 2;
+const STATE_FULLSCREEN: u32 = 3;
+
+/// Request of a taskbar client for a `View`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ToplevelRequest {
+    /// Focus the view and bring it to front
+    Activate,
+    /// Close the view
+    Close,
+    /// Set or unset `ViewState::Maximized`
+    Maximize(bool),
+    /// Minimize or restore the view
+    ///
+    /// wlc has no concept of minimized views, the `ToplevelManager` only
+    /// reports the state to clients. Hide or show the view in your policy.
+    Minimize(bool),
+    /// Set or unset `ViewState::Fullscreen`
+    Fullscreen(bool),
+}
+
+/// Decides which client requests are applied
+///
+/// An Implementation for `FnMut` is provided, so you may use a (anonymous)
+/// function instead
+pub trait ToplevelPolicy {
+    /// Return `true` to let the `ToplevelManager` apply the request
+    fn allow(&mut self, view: &View, request: ToplevelRequest) -> bool;
+}
+
+impl<F> ToplevelPolicy for F
+    where F: FnMut(&View, ToplevelRequest) -> bool
+{
+    fn allow(&mut self, view: &View, request: ToplevelRequest) -> bool {
+        self(view, request)
+    }
+}
+
+struct Toplevel {
+    view: WeakView,
+    minimized: bool,
+}
+
+struct Handle {
+    resource: *mut raw::wl_resource,
+    view: WeakView,
+}
+
+struct State {
+    managers: Vec<*mut raw::wl_resource>,
+    toplevels: Vec<Toplevel>,
+    handles: Vec<Handle>,
+    policy: Option<Box<ToplevelPolicy>>,
+}
+
+impl State {
+    fn handles<'a>(&'a self, view: &'a View) -> Box<Iterator<Item = *mut raw::wl_resource> + 'a> {
+        Box::new(self.handles.iter().filter(move |handle| handle.view == *view).map(|handle| handle.resource))
+    }
+
+    fn minimized(&self, view: &View) -> bool {
+        self.toplevels.iter().any(|toplevel| toplevel.view == *view && toplevel.minimized)
+    }
+
+    fn send_state(&self, view: &View) {
+        let minimized = self.minimized(view);
+        for handle in self.handles(view) {
+            unsafe {
+                send_state(handle, view, minimized);
+                raw::wl_resource_post_event(handle, HANDLE_EVENT_DONE);
+            }
+        }
+    }
+}
+
+unsafe fn send_string(handle: *mut raw::wl_resource, event: u32, string: &str) {
+    if let Ok(string) = CString::new(string) {
+        raw::wl_resource_post_event(handle, event, string.as_ptr());
+    }
+}
+
+unsafe fn send_output(handle: *mut raw::wl_resource, event: u32, output: &Output) {
+    let client = raw::wl_resource_get_client(handle);
+    for resource in raw::client_resources(client, &raw::wl_output_interface) {
+        if ffi::wlc_handle_from_wl_output_resource(resource as *mut _) == ::output::handle(output) {
+            raw::wl_resource_post_event(handle, event, resource);
+        }
+    }
+}
+
+unsafe fn send_state(handle: *mut raw::wl_resource, view: &View, minimized: bool) {
+    let view_state = view.state();
+    let mut states = Vec::new();
+    if view_state.contains(ViewState::Maximized) {
+        states.push(STATE_MAXIMIZED);
+    }
+    if minimized {
+        states.push(STATE_MINIMIZED);
+    }
+    if view_state.contains(ViewState::Activated) {
+        states.push(STATE_ACTIVATED);
+    }
+    if view_state.contains(ViewState::Fullscreen) && raw::wl_resource_get_version(handle) >= 2 {
+        states.push(STATE_FULLSCREEN);
+    }
+
+    let mut array = raw::wl_array::from_slice(&states);
+    raw::wl_resource_post_event(handle, HANDLE_EVENT_STATE, &mut array as *mut raw::wl_array);
+}
+
+unsafe fn create_handle(state: &Rc<RefCell<State>>, manager: *mut raw::wl_resource, view: &View) {
+    let client = raw::wl_resource_get_client(manager);
+    let version = raw::wl_resource_get_version(manager) as u32;
+    let handle = match raw::create_resource(client,
+                                            &HANDLE_INTERFACE,
+                                            version,
+                                            0,
+                                            &HANDLE_IMPLEMENTATION,
+                                            state) {
+        Some(handle) => handle,
+        None => return,
+    };
+    raw::on_resource_destroyed(handle, |handle| if let Some(state) = raw::state::<State>(handle) {
+                                   state.borrow_mut().handles.retain(|other| other.resource != handle);
+                               });
+
+    raw::wl_resource_post_event(manager, MANAGER_EVENT_TOPLEVEL, handle);
+    send_string(handle, HANDLE_EVENT_TITLE, &view.title());
+    send_string(handle, HANDLE_EVENT_APP_ID, &view.app_id());
+    send_output(handle, HANDLE_EVENT_OUTPUT_ENTER, view.output());
+
+    let mut state = state.borrow_mut();
+    send_state(handle, view, state.minimized(view));
+    raw::wl_resource_post_event(handle, HANDLE_EVENT_DONE);
+    state.handles.push(Handle {
+                           resource: handle,
+                           view: view.weak_reference(),
+                       });
+}
+
+/// Foreign toplevel management global
+///
+/// Dropping this removes the global, existing clients are no longer updated.
+pub struct ToplevelManager {
+    state: Rc<RefCell<State>>,
+    _global: raw::Global<State>,
+}
+
+impl ToplevelManager {
+    /// Advertise the toplevel management global on the given `Display`
+    pub fn new<P: ToplevelPolicy + 'static>(display: &Display, policy: P) -> ToplevelManager {
+        let state = Rc::new(RefCell::new(State {
+                                              managers: Vec::new(),
+                                              toplevels: Vec::new(),
+                                              handles: Vec::new(),
+                                              policy: Some(Box::new(policy)),
+                                          }));
+
+        let global = unsafe {
+            raw::Global::new(display.ptr(),
+                             &MANAGER_INTERFACE,
+                             VERSION,
+                             &state,
+                             bind_toplevel_manager)
+        };
+
+        ToplevelManager {
+            state: state,
+            _global: global,
+        }
+    }
+
+    /// Check if the view was minimized by a client or `set_minimized`
+    pub fn is_minimized(&self, view: &View) -> bool {
+        self.state.borrow().minimized(view)
+    }
+
+    /// Report a view as minimized or restored to the clients
+    pub fn set_minimized(&mut self, view: &View, minimized: bool) {
+        let mut state = self.state.borrow_mut();
+        for toplevel in state.toplevels.iter_mut().filter(|toplevel| toplevel.view == *view) {
+            toplevel.minimized = minimized;
+        }
+        state.send_state(view);
+    }
+
+    /// Send the current `ViewState` of the view to all clients
+    pub fn update(&mut self, view: &View) {
+        self.state.borrow().send_state(view);
+    }
+
+    /// Forward of `Callback::view_created`
+    ///
+    /// Popups and unmanaged views are ignored.
+    pub fn view_created(&mut self, view: &View) {
+        if view.view_type().intersects(ViewType::Popup | ViewType::Unmanaged | ViewType::OverrideRedirect) {
+            return;
+        }
+
+        let managers = {
+            let mut state = self.state.borrow_mut();
+            if state.toplevels.iter().any(|toplevel| toplevel.view == *view) {
+                return;
+            }
+            state.toplevels.push(Toplevel {
+                                     view: view.weak_reference(),
+                                     minimized: false,
+                                 });
+            state.managers.clone()
+        };
+
+        for manager in managers {
+            unsafe { create_handle(&self.state, manager, view) };
+        }
+    }
+
+    /// Forward of `Callback::view_destroyed`
+    pub fn view_destroyed(&mut self, view: &View) {
+        let mut state = self.state.borrow_mut();
+        for handle in state.handles(view) {
+            unsafe { raw::wl_resource_post_event(handle, HANDLE_EVENT_CLOSED) };
+        }
+        state.handles.retain(|handle| handle.view != *view);
+        state.toplevels.retain(|toplevel| toplevel.view != *view);
+    }
+
+    /// Forward of `Callback::view_focus`
+    pub fn view_focus(&mut self, view: &View, _focus: bool) {
+        self.state.borrow().send_state(view);
+    }
+
+    /// Forward of `Callback::view_properties_updated`
+    pub fn view_properties_updated(&mut self, view: &View, mask: ViewPropertyUpdate::Flags) {
+        let state = self.state.borrow();
+        for handle in state.handles(view) {
+            unsafe {
+                if mask.contains(ViewPropertyUpdate::Title) {
+                    send_string(handle, HANDLE_EVENT_TITLE, &view.title());
+                }
+                if mask.contains(ViewPropertyUpdate::AppID) {
+                    send_string(handle, HANDLE_EVENT_APP_ID, &view.app_id());
+                }
+                raw::wl_resource_post_event(handle, HANDLE_EVENT_DONE);
+            }
+        }
+    }
+
+    /// Forward of `Callback::view_move_to_output`
+    pub fn view_move_to_output(&mut self, view: &View, from: &Output, to: &Output) {
+        let state = self.state.borrow();
+        for handle in state.handles(view) {
+            unsafe {
+                send_output(handle, HANDLE_EVENT_OUTPUT_LEAVE, from);
+                send_output(handle, HANDLE_EVENT_OUTPUT_ENTER, to);
+                raw::wl_resource_post_event(handle, HANDLE_EVENT_DONE);
+            }
+        }
+    }
+}
+
+unsafe extern "C" fn bind_toplevel_manager(client: *mut raw::wl_client, data: *mut c_void, version: u32,
+                                           id: u32) {
+    let state = match raw::from_user_data::<State>(data) {
+        Some(state) => state,
+        None => return,
+    };
+    let manager = match raw::create_resource(client,
+                                             &MANAGER_INTERFACE,
+                                             cmp::min(version, VERSION),
+                                             id,
+                                             &MANAGER_IMPLEMENTATION,
+                                             &state) {
+        Some(manager) => manager,
+        None => return,
+    };
+    raw::on_resource_destroyed(manager, |manager| if let Some(state) = raw::state::<State>(manager) {
+                                   state.borrow_mut().managers.retain(|other| *other != manager);
+                               });
+
+    let views: Vec<WeakView> = {
+        let mut state = state.borrow_mut();
+        state.managers.push(manager);
+        state.toplevels.iter().map(|toplevel| toplevel.view.clone()).collect()
+    };
+    for view in views {
+        view.run(|view| create_handle(&state, manager, view));
+    }
+}
+
+unsafe extern "C" fn manager_stop(_client: *mut raw::wl_client, resource: *mut raw::wl_resource) {
+    raw::wl_resource_post_event(resource, MANAGER_EVENT_FINISHED);
+    raw::wl_resource_destroy(resource);
+}
+
+unsafe fn handle_request(resource: *mut raw::wl_resource, request: ToplevelRequest) {
+    let state = match raw::state::<State>(resource) {
+        Some(state) => state,
+        None => return,
+    };
+
+    // the policy is taken out of the state, as focusing or closing the view
+    // will trigger callbacks, that need to borrow it
+    let (view, policy) = {
+        let mut state = state.borrow_mut();
+        let view = state.handles
+            .iter()
+            .find(|handle| handle.resource == resource)
+            .map(|handle| handle.view.clone());
+        match view {
+            Some(view) => (view, state.policy.take()),
+            None => return,
+        }
+    };
+    let mut policy = match policy {
+        Some(policy) => policy,
+        None => return,
+    };
+
+    view.run(|view| if policy.allow(view, request) {
+                 match request {
+                     ToplevelRequest::Activate => {
+                         view.bring_to_front();
+                         view.focus();
+                     }
+                     ToplevelRequest::Close => view.close(),
+                     ToplevelRequest::Maximize(toggle) => view.set_state(ViewState::Maximized, toggle),
+                     ToplevelRequest::Fullscreen(toggle) => view.set_state(ViewState::Fullscreen, toggle),
+                     ToplevelRequest::Minimize(toggle) => {
+                         let mut state = state.borrow_mut();
+                         for toplevel in state.toplevels
+                                 .iter_mut()
+                                 .filter(|toplevel| toplevel.view == *view) {
+                             toplevel.minimized = toggle;
+                         }
+                     }
+                 }
+                 state.borrow().send_state(view);
+             });
+
+    state.borrow_mut().policy = Some(policy);
+}
+
+unsafe extern "C" fn set_maximized(_client: *mut raw::wl_client, resource: *mut raw::wl_resource) {
+    handle_request(resource, ToplevelRequest::Maximize(true));
+}
+
+unsafe extern "C" fn unset_maximized(_client: *mut raw::wl_client, resource: *mut raw::wl_resource) {
+    handle_request(resource, ToplevelRequest::Maximize(false));
+}
+
+unsafe extern "C" fn set_minimized(_client: *mut raw::wl_client, resource: *mut raw::wl_resource) {
+    handle_request(resource, ToplevelRequest::Minimize(true));
+}
+
+unsafe extern "C" fn unset_minimized(_client: *mut raw::wl_client, resource: *mut raw::wl_resource) {
+    handle_request(resource, ToplevelRequest::Minimize(false));
+}
+
+unsafe extern "C" fn activate(_client: *mut raw::wl_client, resource: *mut raw::wl_resource,
+                              _seat: *mut raw::wl_resource) {
+    // wlc only has a single seat
+    handle_request(resource, ToplevelRequest::Activate);
+}
+
+unsafe extern "C" fn close(_client: *mut raw::wl_client, resource: *mut raw::wl_resource) {
+    handle_request(resource, ToplevelRequest::Close);
+}
+
+unsafe extern "C" fn set_rectangle(_client: *mut raw::wl_client, _resource: *mut raw::wl_resource,
+                                   _surface: *mut raw::wl_resource, _x: i32, _y: i32, _width: i32,
+                                   _height: i32) {
+    // only a hint for minimize animations
+}
+
+unsafe extern "C" fn destroy(_client: *mut raw::wl_client, resource: *mut raw::wl_resource) {
+    raw::wl_resource_destroy(resource);
+}
+
+unsafe extern "C" fn set_fullscreen(_client: *mut raw::wl_client, resource: *mut raw::wl_resource,
+                                    _output: *mut raw::wl_resource) {
+    handle_request(resource, ToplevelRequest::Fullscreen(true));
+}
+
+unsafe extern "C" fn unset_fullscreen(_client: *mut raw::wl_client, resource: *mut raw::wl_resource) {
+    handle_request(resource, ToplevelRequest::Fullscreen(false));
+}
+
+#[repr(C)]
+struct ManagerImplementation {
+    stop: unsafe extern "C" fn(*mut raw::wl_client, *mut raw::wl_resource),
+}
+
+#[repr(C)]
+struct HandleImplementation {
+    set_maximized: unsafe extern "C" fn(*mut raw::wl_client, *mut raw::wl_resource),
+    unset_maximized: unsafe extern "C" fn(*mut raw::wl_client, *mut raw::wl_resource),
+    set_minimized: unsafe extern "C" fn(*mut raw::wl_client, *mut raw::wl_resource),
+    unset_minimized: unsafe extern "C" fn(*mut raw::wl_client, *mut raw::wl_resource),
+    activate: unsafe extern "C" fn(*mut raw::wl_client, *mut raw::wl_resource, *mut raw::wl_resource),
+    close: unsafe extern "C" fn(*mut raw::wl_client, *mut raw::wl_resource),
+    set_rectangle: unsafe extern "C" fn(*mut raw::wl_client,
+                                        *mut raw::wl_resource,
+                                        *mut raw::wl_resource,
+                                        i32,
+                                        i32,
+                                        i32,
+                                        i32),
+    destroy: unsafe extern "C" fn(*mut raw::wl_client, *mut raw::wl_resource),
+    set_fullscreen: unsafe extern "C" fn(*mut raw::wl_client, *mut raw::wl_resource, *mut raw::wl_resource),
+    unset_fullscreen: unsafe extern "C" fn(*mut raw::wl_client, *mut raw::wl_resource),
+}
+
+static MANAGER_IMPLEMENTATION: ManagerImplementation = ManagerImplementation { stop: manager_stop };
+
+static HANDLE_IMPLEMENTATION: HandleImplementation = HandleImplementation {
+    set_maximized: set_maximized,
+    unset_maximized: unset_maximized,
+    set_minimized: set_minimized,
+    unset_minimized: unset_minimized,
+    activate: activate,
+    close: close,
+    set_rectangle: set_rectangle,
+    destroy: destroy,
+    set_fullscreen: set_fullscreen,
+    unset_fullscreen: unset_fullscreen,
+};
+
+static TOPLEVEL_TYPES: raw::Types<[*const raw::wl_interface; 1]> =
+    raw::Types([&HANDLE_INTERFACE as *const _]);
+static OUTPUT_TYPES: raw::Types<[*const raw::wl_interface; 1]> =
+    raw::Types([unsafe { &raw::wl_output_interface as *const _ }]);
+static SET_RECTANGLE_TYPES: raw::Types<[*const raw::wl_interface; 5]> =
+    raw::Types([unsafe { &raw::wl_surface_interface as *const _ },
+                0 as *const _,
+                0 as *const _,
+                0 as *const _,
+                0 as *const _]);
+
+static MANAGER_REQUESTS: [raw::wl_message; 1] = [message!("stop", "", &raw::NULL_TYPES)];
+static MANAGER_EVENTS: [raw::wl_message; 2] = [message!("toplevel", "n", &TOPLEVEL_TYPES),
+                                               message!("finished", "", &raw::NULL_TYPES)];
+
+static MANAGER_INTERFACE: raw::wl_interface =
+    interface!("zwlr_foreign_toplevel_manager_v1", 2, MANAGER_REQUESTS, MANAGER_EVENTS);
+
+static HANDLE_REQUESTS: [raw::wl_message; 10] = [message!("set_maximized", "", &raw::NULL_TYPES),
+                                                 message!("unset_maximized", "", &raw::NULL_TYPES),
+                                                 message!("set_minimized", "", &raw::NULL_TYPES),
+                                                 message!("unset_minimized", "", &raw::NULL_TYPES),
+                                                 message!("activate", "o", &raw::NULL_TYPES),
+                                                 message!("close", "", &raw::NULL_TYPES),
+                                                 message!("set_rectangle", "oiiii", &SET_RECTANGLE_TYPES),
+                                                 message!("destroy", "", &raw::NULL_TYPES),
+                                                 message!("set_fullscreen", "2?o", &OUTPUT_TYPES),
+                                                 message!("unset_fullscreen", "2", &raw::NULL_TYPES)];
+static HANDLE_EVENTS: [raw::wl_message; 7] = [message!("title", "s", &raw::NULL_TYPES),
+                                              message!("app_id", "s", &raw::NULL_TYPES),
+                                              message!("output_enter", "o", &OUTPUT_TYPES),
+                                              message!("output_leave", "o", &OUTPUT_TYPES),
+                                              message!("state", "a", &raw::NULL_TYPES),
+                                              message!("done", "", &raw::NULL_TYPES),
+                                              message!("closed", "", &raw::NULL_TYPES)];
+
+static HANDLE_INTERFACE: raw::wl_interface =
+    interface!("zwlr_foreign_toplevel_handle_v1", 2, HANDLE_REQUESTS, HANDLE_EVENTS);
@@ -0,0 +1,508 @@
+//! Named workspaces on top of `Visibility` slots
+//!
+//! Every workspace, that is shown or contains views, is mapped to one of the
+//! 32 `Visibility` slots. Switching an output to a workspace sets the
+//! visibility of the output to the slot of the workspace, views are assigned
+//! to workspaces by setting their visibility to the slots of all workspaces
+//! they are tagged with. Views may be tagged with multiple workspaces like
+//! in dwm or be sticky and visible on every workspace.
+//!
+//! Workspaces either exist once and span all outputs (`Scope::Global`) or
+//! every output has its own set of workspaces (`Scope::PerOutput`). If more
+//! than 32 workspaces are used, the slots of the least recently shown
+//! workspaces are reused. Views of workspaces without a slot are hidden
+//! until their workspace is shown again.
+//!
+//! Switching workspaces sets the visibility of all views on the output.
+//! Views hidden for other reasons have to be marked, `scratchpad::Scratchpad`
+//! takes its views out of the manager with `Workspaces::set_managed` and
+//! `tree::Trees` marks inactive tabs with `Workspaces::set_hidden`. Do the
+//! same when hiding views elsewhere.
+//!
+//! Forward `output_created`, `output_destroyed`, `view_created`,
+//! `view_destroyed` and `view_move_to_output`.
+
+use {Output, View, Visibility, WeakOutput, WeakView};
+
+use std::collections::HashMap;
+
+/// Number of `Visibility` slots
+const SLOTS: usize = 32;
+
+/// Visibility flags of a single slot
+fn slot_flags(slot: usize) -> Visibility::Flags {
+    Visibility::Flags::from_bits_truncate(1 << slot)
+}
+
+/// Whether workspaces are shared by all outputs
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Scope {
+    /// Workspaces span all outputs, switching affects every output
+    Global,
+    /// Every output has its own workspaces
+    PerOutput,
+}
+
+struct Workspace {
+    name: String,
+    slot: Option<usize>,
+    /// Time the workspace was shown last
+    used: u64,
+}
+
+/// Set of workspaces sharing the slots
+#[derive(Default)]
+struct Space {
+    workspaces: Vec<Workspace>,
+    current: Option<String>,
+    previous: Option<String>,
+}
+
+impl Space {
+    fn workspace(&self, name: &str) -> Option<&Workspace> {
+        self.workspaces.iter().find(|workspace| workspace.name == name)
+    }
+
+    fn ensure(&mut self, name: &str) {
+        if self.workspace(name).is_none() {
+            self.workspaces.push(Workspace {
+                                     name: name.into(),
+                                     slot: None,
+                                     used: 0,
+                                 });
+        }
+    }
+
+    fn slot(&self, name: &str) -> Option<usize> {
+        self.workspace(name).and_then(|workspace| workspace.slot)
+    }
+
+    /// Give the workspace a slot, taking it from the least recently used
+    /// workspace if all slots are taken
+    fn assign_slot(&mut self, name: &str, time: u64) {
+        self.ensure(name);
+        if let Some(workspace) = self.workspaces.iter_mut().find(|workspace| workspace.name == name) {
+            workspace.used = time;
+            if workspace.slot.is_some() {
+                return;
+            }
+        }
+
+        let free = (0..SLOTS).find(|&slot| {
+                                       self.workspaces.iter().all(|workspace| workspace.slot != Some(slot))
+                                   });
+
+        let slot = match free {
+            Some(slot) => Some(slot),
+            None => {
+                let current = self.current.clone();
+                self.workspaces
+                    .iter_mut()
+                    .filter(|workspace| {
+                                workspace.slot.is_some() && Some(&workspace.name) != current.as_ref()
+                            })
+                    .min_by_key(|workspace| workspace.used)
+                    .and_then(|workspace| workspace.slot.take())
+            }
+        };
+
+        if let Some(workspace) = self.workspaces.iter_mut().find(|workspace| workspace.name == name) {
+            workspace.slot = slot;
+        }
+    }
+}
+
+struct Tags {
+    workspaces: Vec<String>,
+    sticky: bool,
+    /// `false` for views, whose visibility is controlled by someone else
+    managed: bool,
+    /// Hidden regardless of the shown workspaces
+    hidden: bool,
+}
+
+impl Default for Tags {
+    fn default() -> Tags {
+        Tags {
+            workspaces: Vec::new(),
+            sticky: false,
+            managed: true,
+            hidden: false,
+        }
+    }
+}
+
+impl Tags {
+    /// Visibility of a view with these tags, `None` if it is not managed
+    fn visibility(&self, space: Option<&Space>) -> Option<Visibility::Flags> {
+        if !self.managed {
+            return None;
+        }
+        if self.hidden {
+            return Some(Visibility::Null);
+        }
+        if self.sticky {
+            return Some(Visibility::Flags::all());
+        }
+
+        Some(match space {
+                 Some(space) => {
+                     self.workspaces
+                         .iter()
+                         .filter_map(|name| space.slot(name))
+                         .fold(Visibility::Null, |flags, slot| flags | slot_flags(slot))
+                 }
+                 None => Visibility::Null,
+             })
+    }
+}
+
+/// Manager of named workspaces
+pub struct Workspaces {
+    scope: Scope,
+    default: String,
+    spaces: HashMap<Option<WeakOutput>, Space>,
+    outputs: Vec<WeakOutput>,
+    views: HashMap<WeakView, Tags>,
+    time: u64,
+}
+
+impl Workspaces {
+    /// Create a new manager, outputs initially show the workspace `default`
+    pub fn new<S: Into<String>>(scope: Scope, default: S) -> Workspaces {
+        Workspaces {
+            scope: scope,
+            default: default.into(),
+            spaces: HashMap::new(),
+            outputs: Vec::new(),
+            views: HashMap::new(),
+            time: 0,
+        }
+    }
+
+    /// Scope of the workspaces
+    pub fn scope(&self) -> Scope {
+        self.scope
+    }
+
+    fn key(&self, output: &Output) -> Option<WeakOutput> {
+        match self.scope {
+            Scope::Global => None,
+            Scope::PerOutput => Some(output.weak_reference()),
+        }
+    }
+
+    fn space(&self, output: &Output) -> Option<&Space> {
+        self.spaces.get(&self.key(output))
+    }
+
+    /// Workspace shown on the output
+    pub fn current(&self, output: &Output) -> Option<&str> {
+        self.space(output).and_then(|space| space.current.as_ref()).map(|name| name.as_str())
+    }
+
+    /// Workspace shown on the output before the current one
+    pub fn previous(&self, output: &Output) -> Option<&str> {
+        self.space(output).and_then(|space| space.previous.as_ref()).map(|name| name.as_str())
+    }
+
+    /// Names of all workspaces of the output, that were used so far
+    pub fn names(&self, output: &Output) -> Vec<&str> {
+        self.space(output)
+            .map(|space| space.workspaces.iter().map(|workspace| workspace.name.as_str()).collect())
+            .unwrap_or_else(Vec::new)
+    }
+
+    /// Views tagged with the workspace of the output, not including sticky
+    /// views
+    pub fn views(&self, output: &Output, name: &str) -> Vec<WeakView> {
+        let key = self.key(output);
+        self.views
+            .iter()
+            .filter(|&(view, tags)| {
+                        tags.workspaces.iter().any(|tag| tag == name) &&
+                        view.run(|view| self.key(view.output()) == key).unwrap_or(false)
+                    })
+            .map(|(view, _)| view.clone())
+            .collect()
+    }
+
+    /// Show the workspace on the output, or on all outputs for
+    /// `Scope::Global`
+    pub fn switch(&mut self, output: &Output, name: &str) {
+        if !self.outputs.iter().any(|other| *other == *output) {
+            self.outputs.push(output.weak_reference());
+        }
+        let key = self.key(output);
+        self.time += 1;
+        let time = self.time;
+
+        {
+            let space = self.spaces.entry(key.clone()).or_insert_with(Space::default);
+            if space.current.as_ref().map(|current| current == name).unwrap_or(false) {
+                return;
+            }
+            space.previous = space.current.take();
+            space.current = Some(name.into());
+            space.assign_slot(name, time);
+        }
+        self.update(&key);
+    }
+
+    /// Switch the output back to the previous workspace
+    pub fn back_and_forth(&mut self, output: &Output) {
+        if let Some(previous) = self.previous(output).map(String::from) {
+            self.switch(output, &previous);
+        }
+    }
+
+    /// Move a view to a single workspace, replacing all its tags and making
+    /// it non-sticky
+    pub fn move_view(&mut self, view: &View, name: &str) {
+        self.ensure(view, name);
+        if let Some(tags) = self.views.get_mut(&view.weak_reference()) {
+            tags.workspaces = vec![name.into()];
+            tags.sticky = false;
+        }
+        self.update_view(view);
+    }
+
+    /// Additionally show the view on the workspace
+    pub fn tag(&mut self, view: &View, name: &str) {
+        self.ensure(view, name);
+        if let Some(tags) = self.views.get_mut(&view.weak_reference()) {
+            if !tags.workspaces.iter().any(|tag| tag == name) {
+                tags.workspaces.push(name.into());
+            }
+        }
+        self.update_view(view);
+    }
+
+    /// Stop showing the view on the workspace
+    ///
+    /// A view without any workspace is hidden.
+    pub fn untag(&mut self, view: &View, name: &str) {
+        if let Some(tags) = self.views.get_mut(&view.weak_reference()) {
+            tags.workspaces.retain(|tag| tag != name);
+        }
+        self.update_view(view);
+    }
+
+    /// Workspaces the view is tagged with
+    pub fn tags(&self, view: &View) -> Vec<&str> {
+        self.views
+            .get(&view.weak_reference())
+            .map(|tags| tags.workspaces.iter().map(|tag| tag.as_str()).collect())
+            .unwrap_or_else(Vec::new)
+    }
+
+    /// Show the view on every workspace
+    pub fn set_sticky(&mut self, view: &View, sticky: bool) {
+        if let Some(tags) = self.views.get_mut(&view.weak_reference()) {
+            tags.sticky = sticky;
+        }
+        self.update_view(view);
+    }
+
+    /// Check if the view is shown on every workspace
+    pub fn is_sticky(&self, view: &View) -> bool {
+        self.views.get(&view.weak_reference()).map(|tags| tags.sticky).unwrap_or(false)
+    }
+
+    /// Hand the visibility of the view to someone else or take it back
+    ///
+    /// Unmanaged views keep their tags, but their visibility is not touched
+    /// anymore, not even when switching workspaces. Everything hiding views
+    /// on its own, like `scratchpad::Scratchpad`, needs to do this to keep
+    /// its views hidden.
+    pub fn set_managed(&mut self, view: &View, managed: bool) {
+        self.views.entry(view.weak_reference()).or_insert_with(Tags::default).managed = managed;
+        self.update_view(view);
+    }
+
+    /// Check if the visibility of the view is set by the manager
+    pub fn is_managed(&self, view: &View) -> bool {
+        self.views.get(&view.weak_reference()).map(|tags| tags.managed).unwrap_or(false)
+    }
+
+    /// Hide the view on all its workspaces or show it again
+    ///
+    /// Used for views, that are temporarily covered on their workspace, e.g.
+    /// by `tree::Trees` for inactive children of tabbed and stacked
+    /// containers, which would otherwise be shown again by the next switch.
+    /// Views unknown to the manager are ignored.
+    pub fn set_hidden(&mut self, view: &View, hidden: bool) {
+        if let Some(tags) = self.views.get_mut(&view.weak_reference()) {
+            tags.hidden = hidden;
+        }
+        self.update_view(view);
+    }
+
+    /// Make sure the view is tracked and the workspace exists
+    fn ensure(&mut self, view: &View, name: &str) {
+        let key = self.key(view.output());
+        self.spaces.entry(key).or_insert_with(Space::default).ensure(name);
+        self.views.entry(view.weak_reference()).or_insert_with(Tags::default);
+    }
+
+    fn update_view(&self, view: &View) {
+        let visibility = self.views
+            .get(&view.weak_reference())
+            .and_then(|tags| tags.visibility(self.space(view.output())));
+        if let Some(visibility) = visibility {
+            view.set_visibility(visibility);
+        }
+    }
+
+    /// Apply the slots of a space to its outputs and views
+    fn update(&self, key: &Option<WeakOutput>) {
+        let flags = self.spaces
+            .get(key)
+            .and_then(|space| space.current.as_ref().and_then(|current| space.slot(current)))
+            .map(slot_flags)
+            .unwrap_or(Visibility::Null);
+        for output in &self.outputs {
+            output.run(|output| if self.key(output) == *key {
+                           output.set_visibility(flags);
+                       });
+        }
+
+        for view in self.views.keys() {
+            view.run(|view| if self.key(view.output()) == *key {
+                         self.update_view(view);
+                     });
+        }
+    }
+
+    /// Forward of `Callback::output_created`
+    pub fn output_created(&mut self, output: &Output) {
+        let name = self.current(output).unwrap_or(&self.default).to_string();
+        let key = self.key(output);
+        if !self.outputs.iter().any(|other| *other == *output) {
+            self.outputs.push(output.weak_reference());
+        }
+        if self.spaces.get(&key).and_then(|space| space.current.as_ref()).is_some() {
+            self.update(&key);
+        } else {
+            self.switch(output, &name);
+        }
+    }
+
+    /// Forward of `Callback::output_destroyed`
+    pub fn output_destroyed(&mut self, output: &Output) {
+        self.outputs.retain(|other| *other != *output);
+        if self.scope == Scope::PerOutput {
+            self.spaces.remove(&Some(output.weak_reference()));
+        }
+    }
+
+    /// Forward of `Callback::view_created`
+    ///
+    /// The view is placed on the current workspace of its output
+    pub fn view_created(&mut self, view: &View) {
+        if self.current(view.output()).is_none() {
+            let default = self.default.clone();
+            self.switch(view.output(), &default);
+        }
+        let current = self.current(view.output()).unwrap().to_string();
+        self.move_view(view, &current);
+    }
+
+    /// Forward of `Callback::view_destroyed`
+    pub fn view_destroyed(&mut self, view: &View) {
+        self.views.remove(&view.weak_reference());
+    }
+
+    /// Forward of `Callback::view_move_to_output`
+    ///
+    /// For `Scope::PerOutput` the view is moved to the current workspace of
+    /// the new output
+    pub fn view_move_to_output(&mut self, view: &View, _from: &Output, to: &Output) {
+        match self.scope {
+            Scope::Global => self.update_view(view),
+            Scope::PerOutput => {
+                if self.current(to).is_none() {
+                    let default = self.default.clone();
+                    self.switch(to, &default);
+                }
+                let current = self.current(to).unwrap().to_string();
+                self.move_view(view, &current);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slot_flags() {
+        assert_eq!(slot_flags(0), Visibility::Slot1);
+        assert_eq!(slot_flags(31), Visibility::Slot32);
+    }
+
+    #[test]
+    fn test_tags_visibility() {
+        let mut space = Space::default();
+        space.assign_slot("1", 1);
+        space.assign_slot("2", 2);
+        let mut tags = Tags::default();
+        tags.workspaces = vec!["1".into(), "2".into(), "unshown".into()];
+
+        assert_eq!(tags.visibility(Some(&space)), Some(Visibility::Slot1 | Visibility::Slot2));
+        assert_eq!(tags.visibility(None), Some(Visibility::Null));
+
+        tags.sticky = true;
+        assert_eq!(tags.visibility(Some(&space)), Some(Visibility::Flags::all()));
+
+        tags.hidden = true;
+        assert_eq!(tags.visibility(Some(&space)), Some(Visibility::Null));
+
+        tags.managed = false;
+        assert_eq!(tags.visibility(Some(&space)), None);
+    }
+
+    #[test]
+    fn test_unmanaged_views_survive_switches() {
+        // a view moved to the scratchpad from workspace "1"
+        let mut space = Space::default();
+        space.assign_slot("1", 1);
+        let mut tags = Tags::default();
+        tags.workspaces = vec!["1".into()];
+        tags.managed = false;
+
+        space.current = Some("2".into());
+        space.assign_slot("2", 2);
+        assert_eq!(tags.visibility(Some(&space)), None);
+        space.current = Some("1".into());
+        space.assign_slot("1", 3);
+        assert_eq!(tags.visibility(Some(&space)), None);
+
+        // taken back from the scratchpad
+        tags.managed = true;
+        assert_eq!(tags.visibility(Some(&space)), Some(Visibility::Slot1));
+    }
+
+    #[test]
+    fn test_least_recently_used_slot_is_reused() {
+        let mut space = Space::default();
+        for i in 0..SLOTS {
+            space.assign_slot(&i.to_string(), i as u64 + 1);
+        }
+        // "0" was used longest ago, but is shown again
+        space.current = Some("0".into());
+        space.assign_slot("0", 100);
+        space.assign_slot("overflow", 101);
+
+        assert_eq!(space.slot("0"), Some(0));
+        assert_eq!(space.slot("1"), None);
+        assert_eq!(space.slot("overflow"), Some(1));
+
+        // workspaces without slot get one back
+        space.current = Some("1".into());
+        space.assign_slot("1", 102);
+        assert_eq!(space.slot("1"), Some(2));
+        assert_eq!(space.slot("2"), None);
+    }
+}